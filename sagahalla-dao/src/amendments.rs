@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId};
+
+use crate::mana_structs::{ProjectPlan, TaskPlan};
+use crate::permissions::PROJECT_PLAN;
+use crate::voting::{ProjectPlanStatus, ProjectPlanVote};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TaskHoursChange {
+    pub task_plan_id: u64,
+    pub old_mana_hours: u64,
+    pub new_mana_hours: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeveloperBudgetChange {
+    pub developer_id: AccountId,
+    pub old_mana_hours_budgeted: u64,
+    pub new_mana_hours_budgeted: u64,
+    pub old_mana_token_allocated: U128,
+    pub new_mana_token_allocated: U128,
+}
+
+// Structural difference between two versions of a project plan
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlanDiff {
+    pub added_tasks: Vec<u64>,
+    pub removed_tasks: Vec<u64>,
+    pub changed_tasks: Vec<TaskHoursChange>,
+    pub changed_budgets: Vec<DeveloperBudgetChange>, // Includes developers added or removed, with zero on the missing side
+    pub old_total_mana_hours: u64,
+    pub new_total_mana_hours: u64,
+    pub change_bps: u64, // Largest of the mana hours and token allocation change, relative to the old plan
+}
+
+impl PlanDiff {
+    pub fn between(old: &ProjectPlan, new: &ProjectPlan) -> Self {
+        let old_tasks: HashMap<u64, &TaskPlan> = old.tasks().map(|task| (task.id, task)).collect();
+        let new_tasks: HashMap<u64, &TaskPlan> = new.tasks().map(|task| (task.id, task)).collect();
+
+        let mut added_tasks = vec![];
+        let mut removed_tasks = vec![];
+        let mut changed_tasks = vec![];
+        let mut hours_changed: u64 = 0;
+        for (id, task) in new_tasks.iter() {
+            match old_tasks.get(id) {
+                None => {
+                    added_tasks.push(*id);
+                    hours_changed += task.estimated_mana_hours;
+                }
+                Some(old_task) if old_task.estimated_mana_hours != task.estimated_mana_hours => {
+                    changed_tasks.push(TaskHoursChange {
+                        task_plan_id: *id,
+                        old_mana_hours: old_task.estimated_mana_hours,
+                        new_mana_hours: task.estimated_mana_hours,
+                    });
+                    hours_changed += old_task.estimated_mana_hours.abs_diff(task.estimated_mana_hours);
+                }
+                Some(_) => {}
+            }
+        }
+        for (id, task) in old_tasks.iter() {
+            if !new_tasks.contains_key(id) {
+                removed_tasks.push(*id);
+                hours_changed += task.estimated_mana_hours;
+            }
+        }
+        added_tasks.sort_unstable();
+        removed_tasks.sort_unstable();
+        changed_tasks.sort_by_key(|change| change.task_plan_id);
+        hours_changed = hours_changed.max(old.total_mana_hours.abs_diff(new.total_mana_hours));
+
+        let mut changed_budgets = vec![];
+        let mut tokens_changed: u128 = 0;
+        let mut developer_ids: Vec<&AccountId> =
+            old.developers.keys().chain(new.developers.keys()).collect();
+        developer_ids.sort();
+        developer_ids.dedup();
+        for developer_id in developer_ids {
+            let (old_hours, old_tokens) = old
+                .developers
+                .get(developer_id)
+                .map(|developer| (developer.mana_hours_budgeted, developer.mana_token_allocated.0))
+                .unwrap_or((0, 0));
+            let (new_hours, new_tokens) = new
+                .developers
+                .get(developer_id)
+                .map(|developer| (developer.mana_hours_budgeted, developer.mana_token_allocated.0))
+                .unwrap_or((0, 0));
+            if old_hours != new_hours || old_tokens != new_tokens
+                || !old.developers.contains_key(developer_id)
+                || !new.developers.contains_key(developer_id)
+            {
+                changed_budgets.push(DeveloperBudgetChange {
+                    developer_id: developer_id.clone(),
+                    old_mana_hours_budgeted: old_hours,
+                    new_mana_hours_budgeted: new_hours,
+                    old_mana_token_allocated: U128(old_tokens),
+                    new_mana_token_allocated: U128(new_tokens),
+                });
+                tokens_changed += old_tokens.abs_diff(new_tokens);
+            }
+        }
+        let old_total_tokens: u128 = old
+            .developers
+            .values()
+            .map(|developer| developer.mana_token_allocated.0)
+            .sum();
+
+        let change_bps = std::cmp::max(
+            to_bps(hours_changed as u128, old.total_mana_hours as u128),
+            to_bps(tokens_changed, old_total_tokens),
+        );
+
+        Self {
+            added_tasks,
+            removed_tasks,
+            changed_tasks,
+            changed_budgets,
+            old_total_mana_hours: old.total_mana_hours,
+            new_total_mana_hours: new.total_mana_hours,
+            change_bps,
+        }
+    }
+}
+
+// `part` as basis points of `total`. Any change against an empty total counts as 100%.
+fn to_bps(part: u128, total: u128) -> u64 {
+    if part == 0 {
+        return 0;
+    }
+    match (part * 10_000).checked_div(total) {
+        Some(bps) => bps.min(u64::MAX as u128) as u64,
+        None => 10_000,
+    }
+}
+
+// Checks that token allocations only change while the current plan has no treasury proposal pending
+// or paid out, and returns whether any developer's allocation changed, counting missing developers as zero.
+fn check_allocation_change(current: &ProjectPlan, amended: &ProjectPlan) -> bool {
+    let allocated = |plan: &ProjectPlan, developer_id: &AccountId| {
+        plan.developers
+            .get(developer_id)
+            .map_or(0, |developer| developer.mana_token_allocated.0)
+    };
+    let changed = current
+        .developers
+        .keys()
+        .chain(amended.developers.keys())
+        .any(|developer_id| allocated(current, developer_id) != allocated(amended, developer_id));
+    if changed {
+        require!(
+            current.treasury_proposal.as_ref().is_none_or(|proposal| proposal.status.can_refile()),
+            "Token allocations cannot change while the treasury proposal is pending or paid out"
+        );
+    }
+    changed
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AmendmentStatus {
+    Pending,   // Waiting for re-approval
+    Applied,   // Plan was replaced by this version
+    Rejected,  // Re-approval failed, plan kept its previous version
+    Cancelled, // Withdrawn by the proposer or owner before re-approval
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlanAmendment {
+    pub id: u64,
+    pub project_plan_id: u64,
+    pub version: u64, // Plan version this amendment becomes once applied
+    pub proposer: AccountId,
+    pub description: String,
+    pub plan: ProjectPlan,
+    pub diff: PlanDiff, // Against the last approved version of the plan
+    pub status: AmendmentStatus,
+    pub vote: Option<ProjectPlanVote>, // Re-approval vote, only for changes above the threshold
+    pub created_at: u64,
+}
+
+impl Contract {
+    // Plan as last approved by vote. Amendments are measured against it so that small amendments
+    // applied without a vote can't add up to a large change.
+    fn internal_get_approved_plan(&self, project_plan_id: u64) -> ProjectPlan {
+        self.approved_plans
+            .get(&project_plan_id)
+            .expect("Project plan has no approved version")
+    }
+
+    // Replaces the plan with the amended version. Claims carry over to tasks that still exist,
+    // claims on removed tasks are released. The treasury proposal carries over unless the token
+    // allocations changed, in which case one paying the new allocations is filed.
+    fn internal_apply_amendment(&mut self, amendment: &mut PlanAmendment) {
+        let mut current = self.internal_get_plan(amendment.project_plan_id);
+        let mut plan = amendment.plan.clone();
+        let task_ids: Vec<u64> = current.tasks().map(|task| task.id).collect();
+        for task_id in task_ids {
+            let old_task = current.task_mut(task_id).unwrap();
+            match plan.task_mut(task_id) {
+                Some(task) => {
+                    task.claim = old_task.claim.take();
+                    task.status = old_task.status.clone();
+                }
+                None if old_task.claim.is_some() => self.internal_release_claim(old_task),
+                None => {}
+            }
+        }
+        plan.version = amendment.version;
+        if check_allocation_change(&current, &plan) {
+            self.internal_file_treasury_proposal(&mut plan);
+        } else {
+            plan.treasury_proposal = current.treasury_proposal.take();
+        }
+        self.project_plans.insert(&plan.id, &plan);
+        amendment.status = AmendmentStatus::Applied;
+
+        env::log_str(&format!(
+            "Project plan {} amended to version {}",
+            amendment.project_plan_id, amendment.version
+        ));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Proposes a new version of an approved plan. Changes within the configured threshold of the last
+    // approved version are applied right away, larger ones open a re-approval vote. Only the owner or a developer on the plan can propose.
+    pub fn propose_plan_amendment(&mut self, project_plan: ProjectPlan, description: String) -> u64 {
+        let project_plan_id = project_plan.id;
+        let current = self.internal_get_plan(project_plan_id);
+        let proposer = env::predecessor_account_id();
+        require!(
+            proposer == self.owner_id || current.developers.contains_key(&proposer),
+            "Only the owner or a developer on the plan can propose amendments"
+        );
+        require!(
+            self.internal_get_plan_vote(project_plan_id).status == ProjectPlanStatus::Approved,
+            "Only approved project plans can be amended"
+        );
+        for (developer_id, developer) in project_plan.developers.iter() {
+            require!(
+                *developer_id == developer.developer_id,
+                "Developer plan must be keyed by its developer_id"
+            );
+        }
+        require!(project_plan.has_unique_task_ids(), "Task ids must be unique across the plan");
+        require!(
+            project_plan.tasks().all(|task| task.claim.is_none()),
+            "Amended tasks keep their claims and must not set them"
+        );
+        require!(
+            project_plan.treasury_proposal.is_none(),
+            "Amended plans keep their treasury proposal and must not set it"
+        );
+        check_allocation_change(&current, &project_plan);
+        let mut amendment_ids = self.plan_amendment_ids.get(&project_plan_id).unwrap_or_default();
+        if let Some(last_id) = amendment_ids.last() {
+            require!(
+                self.plan_amendments.get(last_id).unwrap().status != AmendmentStatus::Pending,
+                "Project plan already has a pending amendment"
+            );
+        }
+
+        let diff = PlanDiff::between(&self.internal_get_approved_plan(project_plan_id), &project_plan);
+        let vote = if diff.change_bps > self.plan_config.amendment_threshold_bps {
+            Some(self.voting_module.internal_new_plan_vote(project_plan_id))
+        } else {
+            None
+        };
+        let id = self.last_amendment_id;
+        let mut amendment = PlanAmendment {
+            id,
+            project_plan_id,
+            version: current.version + 1,
+            proposer,
+            description,
+            plan: project_plan,
+            diff,
+            status: AmendmentStatus::Pending,
+            vote,
+            created_at: env::block_timestamp(),
+        };
+        if amendment.vote.is_none() {
+            self.internal_apply_amendment(&mut amendment);
+        }
+        self.plan_amendments.insert(&id, &amendment);
+        amendment_ids.push(id);
+        self.plan_amendment_ids.insert(&project_plan_id, &amendment_ids);
+        self.last_amendment_id += 1;
+        id
+    }
+
+    // Votes on the re-approval of a pending amendment. The amendment is applied once approved.
+    pub fn vote_plan_amendment(&mut self, amendment_id: u64, approve: bool) {
+        self.assert_permission(PROJECT_PLAN, "Vote");
+        let mut amendment = self
+            .plan_amendments
+            .get(&amendment_id)
+            .expect("No amendment found with this id");
+        require!(amendment.status == AmendmentStatus::Pending, "Amendment is not pending");
+        let mut vote = amendment.vote.take().expect("Amendment does not need re-approval");
+        self.voting_module
+            .internal_tally_plan_vote(&mut vote, &env::predecessor_account_id(), approve);
+        match vote.status {
+            ProjectPlanStatus::Approved => {
+                self.internal_apply_amendment(&mut amendment);
+                let plan = self.internal_get_plan(amendment.project_plan_id);
+                self.approved_plans.insert(&plan.id, &plan);
+            }
+            ProjectPlanStatus::Rejected => amendment.status = AmendmentStatus::Rejected,
+            ProjectPlanStatus::Active => {}
+        }
+        amendment.vote = Some(vote);
+        self.plan_amendments.insert(&amendment_id, &amendment);
+    }
+
+    // Withdraws a pending amendment, e.g. one whose re-approval vote stalled below quorum, so that
+    // the plan can be amended again. Only the proposer or the owner can cancel.
+    pub fn cancel_plan_amendment(&mut self, amendment_id: u64) {
+        let mut amendment = self
+            .plan_amendments
+            .get(&amendment_id)
+            .expect("No amendment found with this id");
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == amendment.proposer || caller == self.owner_id,
+            "Only the proposer or the owner can cancel the amendment"
+        );
+        require!(amendment.status == AmendmentStatus::Pending, "Amendment is not pending");
+        amendment.status = AmendmentStatus::Cancelled;
+        self.plan_amendments.insert(&amendment_id, &amendment);
+    }
+
+    // Computes the diff an amendment would have against the last approved plan, without storing anything
+    pub fn preview_plan_amendment(&self, project_plan: ProjectPlan) -> PlanDiff {
+        PlanDiff::between(&self.internal_get_approved_plan(project_plan.id), &project_plan)
+    }
+
+    pub fn get_plan_amendment(&self, amendment_id: u64) -> Option<PlanAmendment> {
+        self.plan_amendments.get(&amendment_id)
+    }
+
+    // All amendments of a plan, oldest first
+    pub fn get_plan_amendments(&self, project_plan_id: u64) -> Vec<PlanAmendment> {
+        self.plan_amendment_ids
+            .get(&project_plan_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|id| self.plan_amendments.get(id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use near_sdk::serde_json::{self, json, Value};
+    use near_sdk::PromiseResult;
+
+    use crate::bridge::TreasuryProposalStatus;
+    use crate::mana_structs::TaskStatus;
+    use crate::test_utils::*;
+
+    use super::*;
+
+    // Plan 1 with bob's 10 hour task estimated at `estimated_mana_hours` instead
+    fn amended_plan(estimated_mana_hours: u64) -> ProjectPlan {
+        let mut plan = project_plan(1, &[(accounts(1), 1_000)]);
+        plan.task_mut(1).unwrap().estimated_mana_hours = estimated_mana_hours;
+        plan
+    }
+
+    fn setup_amendments() -> Contract {
+        let mut contract = setup_contract();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        contract
+    }
+
+    #[test]
+    fn test_small_amendment_applied() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        assert_eq!(contract.preview_plan_amendment(amended_plan(11)).change_bps, 1_000);
+        let id = contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+
+        let amendment = contract.get_plan_amendment(id).unwrap();
+        assert_eq!(amendment.status, AmendmentStatus::Applied);
+        assert!(amendment.vote.is_none());
+        let plan = contract.get_project_plan(1).unwrap();
+        assert_eq!((plan.version, plan.tasks().next().unwrap().estimated_mana_hours), (2, 11));
+    }
+
+    #[test]
+    fn test_large_amendment_reapproved() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(3));
+        contract.claim_task(1, 1, None);
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        let amendment = contract.get_plan_amendment(id).unwrap();
+        assert_eq!(amendment.status, AmendmentStatus::Pending);
+        assert_eq!(amendment.diff.changed_tasks[0].new_mana_hours, 20);
+        assert_eq!(contract.get_project_plan(1).unwrap().version, 1);
+
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(id, true);
+        assert_eq!(contract.get_plan_amendment(id).unwrap().status, AmendmentStatus::Applied);
+        let plan = contract.get_project_plan(1).unwrap();
+        let task = plan.tasks().next().unwrap();
+        assert_eq!((plan.version, task.estimated_mana_hours), (2, 20));
+        // The claim carries over to the amended task.
+        assert_eq!(task.claim.as_ref().unwrap().claimer_id, accounts(3));
+        assert!(matches!(task.status, TaskStatus::InProgress));
+    }
+
+    #[test]
+    fn test_large_amendment_rejected() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(id, false);
+        assert_eq!(contract.get_plan_amendment(id).unwrap().status, AmendmentStatus::Rejected);
+        let plan = contract.get_project_plan(1).unwrap();
+        assert_eq!((plan.version, plan.tasks().next().unwrap().estimated_mana_hours), (1, 10));
+        assert_eq!(contract.get_plan_amendments(1).len(), 1);
+    }
+
+    #[test]
+    fn test_small_amendments_cannot_drift() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        let first = contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+        assert_eq!(contract.get_plan_amendment(first).unwrap().status, AmendmentStatus::Applied);
+
+        // One more hour is within the threshold of the current plan, but not of the approved one.
+        let second = contract.propose_plan_amendment(amended_plan(12), "One more hour".to_string());
+        let amendment = contract.get_plan_amendment(second).unwrap();
+        assert_eq!(amendment.status, AmendmentStatus::Pending);
+        assert_eq!((amendment.diff.change_bps, amendment.version), (2_000, 3));
+        assert_eq!(contract.get_project_plan(1).unwrap().version, 2);
+
+        // Once re-approved, the amended plan is what later amendments are measured against.
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(second, true);
+        call_as(&accounts(1));
+        let third = contract.propose_plan_amendment(amended_plan(13), "One more hour".to_string());
+        assert_eq!(contract.get_plan_amendment(third).unwrap().status, AmendmentStatus::Applied);
+        assert_eq!(contract.get_project_plan(1).unwrap().version, 4);
+    }
+
+    // Plan 1 approved with bob's allocation of 1000 and its treasury proposal 7 synced as `status`
+    fn setup_treasury_proposal(status: TreasuryProposalStatus) -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_bridge_config();
+        config.dao_id = Some("treasury.sputnik-dao.near".parse().unwrap());
+        config.token_id = Some("mana.near".parse().unwrap());
+        call_as(&owner());
+        contract.update_bridge_config(config);
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        contract.on_treasury_proposal_filed(1);
+        callback_with(PromiseResult::Successful(
+            json!({ "id": 7, "status": status }).to_string().into_bytes(),
+        ));
+        contract.on_treasury_proposal_status(1);
+        contract
+    }
+
+    #[test]
+    fn test_reallocation_refiles_treasury_proposal() {
+        let mut contract = setup_treasury_proposal(TreasuryProposalStatus::Rejected);
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(
+            project_plan(1, &[(accounts(1), 2_000)]),
+            "Double the pay".to_string(),
+        );
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(id, true);
+        let (_, method_name, args, _) = &function_calls()[0];
+        assert_eq!(method_name, "add_proposal");
+        let args: Value = serde_json::from_slice(args).unwrap();
+        assert_eq!(args["proposal"]["kind"]["Transfer"]["amount"], "2000");
+        let treasury_proposal = contract.get_project_plan(1).unwrap().treasury_proposal.unwrap();
+        assert_eq!(treasury_proposal.status, TreasuryProposalStatus::Filing);
+    }
+
+    #[test]
+    fn test_amendment_keeps_treasury_proposal() {
+        let mut contract = setup_treasury_proposal(TreasuryProposalStatus::InProgress);
+        call_as(&accounts(1));
+        contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+        assert!(function_calls().is_empty());
+        let treasury_proposal = contract.get_project_plan(1).unwrap().treasury_proposal.unwrap();
+        assert_eq!(treasury_proposal.proposal_id, Some(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token allocations cannot change while the treasury proposal is pending or paid out")]
+    fn test_reallocation_while_treasury_proposal_pending() {
+        let mut contract = setup_treasury_proposal(TreasuryProposalStatus::InProgress);
+        call_as(&accounts(1));
+        contract.propose_plan_amendment(
+            project_plan(1, &[(accounts(1), 2_000)]),
+            "Double the pay".to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Project plan already has a pending amendment")]
+    fn test_amendment_while_pending() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+    }
+
+    #[test]
+    fn test_cancel_pending_amendment() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        contract.cancel_plan_amendment(id);
+        assert_eq!(contract.get_plan_amendment(id).unwrap().status, AmendmentStatus::Cancelled);
+
+        // The plan can be amended again.
+        let id = contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+        assert_eq!(contract.get_plan_amendment(id).unwrap().status, AmendmentStatus::Applied);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposer or the owner can cancel the amendment")]
+    fn test_cancel_amendment_by_other_account() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        call_as(&accounts(2));
+        contract.cancel_plan_amendment(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amendment is not pending")]
+    fn test_vote_cancelled_amendment() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(1));
+        let id = contract.propose_plan_amendment(amended_plan(20), "Double the task".to_string());
+        call_as(&owner());
+        contract.cancel_plan_amendment(id);
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(id, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or a developer on the plan can propose amendments")]
+    fn test_amendment_by_other_account() {
+        let mut contract = setup_amendments();
+        call_as(&accounts(3));
+        contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only approved project plans can be amended")]
+    fn test_amend_unapproved_plan() {
+        let mut contract = setup_contract();
+        call_as(&accounts(1));
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 1_000)]));
+        contract.propose_plan_amendment(amended_plan(11), "One more hour".to_string());
+    }
+}
@@ -0,0 +1,511 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, json};
+use near_sdk::{env, ext_contract, near_bindgen, require, AccountId, Gas, Promise, PromiseResult};
+
+use crate::mana_structs::ProjectPlan;
+use crate::voting::ProjectPlanStatus;
+use crate::*;
+
+const GAS_FOR_ADD_PROPOSAL: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_GET_PROPOSAL: Gas = Gas(10_000_000_000_000);
+pub(crate) const GAS_FOR_BRIDGE_CALLBACK: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+
+// Subset of the sputnikdao2 proposal input that sagahalla-dao files, serialized the same way
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SputnikProposalInput {
+    pub description: String,
+    pub kind: SputnikProposalKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SputnikProposalKind {
+    FunctionCall {
+        receiver_id: AccountId,
+        actions: Vec<SputnikActionCall>,
+    },
+    Transfer {
+        token_id: String, // "" for NEAR
+        receiver_id: AccountId,
+        amount: U128,
+        msg: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SputnikActionCall {
+    pub method_name: String,
+    pub args: Base64VecU8,
+    pub deposit: U128,
+    pub gas: U64,
+}
+
+// Fields of a sputnikdao2 `get_proposal` output that the bridge reads
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct SputnikProposalOutput {
+    status: TreasuryProposalStatus,
+}
+
+#[ext_contract(ext_sputnik)]
+pub trait ExtSputnik {
+    fn add_proposal(&mut self, proposal: SputnikProposalInput) -> u64;
+    fn get_proposal(&self, id: u64);
+}
+
+#[ext_contract(ext_self)]
+pub trait ExtSelf {
+    fn on_treasury_proposal_filed(&mut self, project_plan_id: u64) -> Option<u64>;
+    fn on_treasury_proposal_status(&mut self, project_plan_id: u64);
+    fn on_funding_payout_filed(&mut self, proposal_id: u64) -> Option<u64>;
+    fn on_funding_payout_status(&mut self, proposal_id: u64);
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TreasuryProposalStatus {
+    Filing,       // add_proposal call is in flight
+    FilingFailed, // add_proposal failed, can be filed again
+    // Statuses mirrored from sputnikdao2
+    InProgress,
+    Approved,
+    Rejected,
+    Removed,
+    Expired,
+    Moved,
+    Failed,
+    Queued,    // Approved and waiting for the timelock
+    Cancelled, // Queued proposal cancelled by the guardian
+}
+
+impl TreasuryProposalStatus {
+    // Whether the proposal ended without paying out the plan, so that it can be filed again
+    pub fn can_refile(&self) -> bool {
+        matches!(
+            self,
+            Self::FilingFailed | Self::Rejected | Self::Removed | Self::Expired | Self::Failed | Self::Cancelled
+        )
+    }
+}
+
+// Treasury proposal paying out an approved plan on the sputnikdao2 DAO
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryProposal {
+    pub dao_id: AccountId,
+    pub proposal_id: Option<u64>,
+    pub status: TreasuryProposalStatus,
+    pub updated_at: u64,
+}
+
+impl TreasuryProposal {
+    // Records the id of the filed proposal, or marks the filing as failed
+    pub fn record_filing(&mut self, proposal_id: Option<u64>) {
+        self.proposal_id = proposal_id;
+        self.status = match proposal_id {
+            Some(_) => TreasuryProposalStatus::InProgress,
+            None => TreasuryProposalStatus::FilingFailed,
+        };
+    }
+}
+
+// Id of the filed proposal returned to a filing callback, None if the filing failed
+pub(crate) fn filed_proposal_id() -> Option<u64> {
+    require!(env::promise_results_count() == 1, "Unexpected callback promises");
+    match env::promise_result(0) {
+        PromiseResult::Successful(data) => serde_json::from_slice::<u64>(&data).ok(),
+        _ => None,
+    }
+}
+
+// Proposal status returned to a sync callback, None if it could not be fetched
+pub(crate) fn synced_proposal_status() -> Option<TreasuryProposalStatus> {
+    require!(env::promise_results_count() == 1, "Unexpected callback promises");
+    match env::promise_result(0) {
+        PromiseResult::Successful(data) => serde_json::from_slice::<SputnikProposalOutput>(&data)
+            .ok()
+            .map(|output| output.status),
+        _ => None,
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BridgeConfig {
+    pub dao_id: Option<AccountId>, // sputnikdao2 holding the treasury, no proposals are filed while unset
+    pub token_id: Option<AccountId>, // MANA token the allocations are paid in
+    pub proposal_bond: U128, // Must match the proposal bond of the sputnikdao2 policy
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            dao_id: None,
+            token_id: None,
+            proposal_bond: U128(10u128.pow(24)),
+        }
+    }
+}
+
+impl BridgeConfig {
+    // DAO and MANA token the treasury proposals are filed with, None until both are configured
+    pub(crate) fn treasury(&self) -> Option<(AccountId, AccountId)> {
+        match (&self.dao_id, &self.token_id) {
+            (Some(dao_id), Some(token_id)) => Some((dao_id.clone(), token_id.clone())),
+            _ => None,
+        }
+    }
+
+    // Transfer for a single developer, a batch of `ft_transfer` calls on the token otherwise.
+    // None when the plan does not allocate any tokens.
+    fn proposal_kind(&self, token_id: &AccountId, plan: &ProjectPlan) -> Option<SputnikProposalKind> {
+        let mut allocations: Vec<(&AccountId, U128)> = plan
+            .developers
+            .iter()
+            .filter(|(_, developer)| developer.mana_token_allocated.0 > 0)
+            .map(|(developer_id, developer)| (developer_id, developer.mana_token_allocated))
+            .collect();
+        allocations.sort_by(|a, b| a.0.cmp(b.0));
+
+        if let [(receiver_id, amount)] = allocations[..] {
+            return Some(SputnikProposalKind::Transfer {
+                token_id: token_id.to_string(),
+                receiver_id: receiver_id.clone(),
+                amount,
+                msg: None,
+            });
+        }
+        if allocations.is_empty() {
+            return None;
+        }
+        Some(SputnikProposalKind::FunctionCall {
+            receiver_id: token_id.clone(),
+            actions: allocations
+                .into_iter()
+                .map(|(receiver_id, amount)| SputnikActionCall {
+                    method_name: "ft_transfer".to_string(),
+                    args: Base64VecU8(
+                        json!({ "receiver_id": receiver_id, "amount": amount })
+                            .to_string()
+                            .into_bytes(),
+                    ),
+                    deposit: U128(1),
+                    gas: U64(GAS_FOR_FT_TRANSFER.0),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Contract {
+    // Files the proposal on the sputnikdao2 DAO, returning it as tracked until the filing callback and
+    // the add_proposal promise to attach that callback to.
+    // The bond is paid from this contract's balance and returned by sputnikdao2 once the proposal is finalized.
+    pub(crate) fn internal_add_treasury_proposal(
+        &self,
+        dao_id: AccountId,
+        proposal: SputnikProposalInput,
+    ) -> (TreasuryProposal, Promise) {
+        let promise = ext_sputnik::add_proposal(
+            proposal,
+            dao_id.clone(),
+            self.bridge_config.proposal_bond.0,
+            GAS_FOR_ADD_PROPOSAL,
+        );
+        let treasury_proposal = TreasuryProposal {
+            dao_id,
+            proposal_id: None,
+            status: TreasuryProposalStatus::Filing,
+            updated_at: env::block_timestamp(),
+        };
+        (treasury_proposal, promise)
+    }
+
+    // Fetches the current status of a filed proposal, returning the promise to attach the sync callback to
+    pub(crate) fn internal_get_treasury_proposal(&self, treasury_proposal: TreasuryProposal) -> Promise {
+        let proposal_id = treasury_proposal.proposal_id.expect("Treasury proposal is not filed yet");
+        ext_sputnik::get_proposal(proposal_id, treasury_proposal.dao_id, 0, GAS_FOR_GET_PROPOSAL)
+    }
+
+    // Files the treasury proposal paying out an approved plan, if a sputnikdao2 DAO is configured
+    pub(crate) fn internal_file_treasury_proposal(&mut self, plan: &mut ProjectPlan) {
+        let (dao_id, token_id) = match self.bridge_config.treasury() {
+            Some(treasury) => treasury,
+            None => return,
+        };
+        let kind = match self.bridge_config.proposal_kind(&token_id, plan) {
+            Some(kind) => kind,
+            None => return,
+        };
+        let proposal = SputnikProposalInput {
+            description: format!("sagahalla-dao project plan {}: {}", plan.id, plan.project_name),
+            kind,
+        };
+        let (treasury_proposal, promise) = self.internal_add_treasury_proposal(dao_id, proposal);
+        promise.then(ext_self::on_treasury_proposal_filed(
+            plan.id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_BRIDGE_CALLBACK,
+        ));
+        plan.treasury_proposal = Some(treasury_proposal);
+    }
+
+    fn internal_update_treasury_proposal(
+        &mut self,
+        project_plan_id: u64,
+        update: impl FnOnce(&mut TreasuryProposal),
+    ) {
+        let mut plan = self.internal_get_plan(project_plan_id);
+        let treasury_proposal = plan
+            .treasury_proposal
+            .as_mut()
+            .expect("Project plan has no treasury proposal");
+        update(treasury_proposal);
+        treasury_proposal.updated_at = env::block_timestamp();
+        self.project_plans.insert(&project_plan_id, &plan);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Records the id of the filed proposal, or marks the filing as failed
+    #[private]
+    pub fn on_treasury_proposal_filed(&mut self, project_plan_id: u64) -> Option<u64> {
+        let proposal_id = filed_proposal_id();
+        self.internal_update_treasury_proposal(project_plan_id, |treasury_proposal| {
+            treasury_proposal.record_filing(proposal_id);
+        });
+        proposal_id
+    }
+
+    #[private]
+    pub fn on_treasury_proposal_status(&mut self, project_plan_id: u64) {
+        if let Some(status) = synced_proposal_status() {
+            self.internal_update_treasury_proposal(project_plan_id, |treasury_proposal| {
+                treasury_proposal.status = status;
+            });
+        }
+    }
+
+    // Files the treasury proposal of an approved plan whose filing failed or whose proposal ended
+    // without paying out, as last synced, or that was approved before the bridge was configured. Owner only.
+    pub fn refile_treasury_proposal(&mut self, project_plan_id: u64) {
+        self.assert_owner();
+        let mut plan = self.internal_get_plan(project_plan_id);
+        require!(
+            self.internal_get_plan_vote(project_plan_id).status == ProjectPlanStatus::Approved,
+            "Project plan is not approved"
+        );
+        if let Some(treasury_proposal) = plan.treasury_proposal.as_ref() {
+            require!(
+                treasury_proposal.status.can_refile(),
+                "Treasury proposal is already filed"
+            );
+        }
+        self.internal_file_treasury_proposal(&mut plan);
+        self.project_plans.insert(&project_plan_id, &plan);
+    }
+
+    // Fetches the current status of a plan's treasury proposal from sputnikdao2. Anyone can call this.
+    pub fn sync_treasury_proposal(&mut self, project_plan_id: u64) {
+        let plan = self.internal_get_plan(project_plan_id);
+        let treasury_proposal = plan.treasury_proposal.expect("Project plan has no treasury proposal");
+        self.internal_get_treasury_proposal(treasury_proposal)
+            .then(ext_self::on_treasury_proposal_status(
+                project_plan_id,
+                env::current_account_id(),
+                0,
+                GAS_FOR_BRIDGE_CALLBACK,
+            ));
+    }
+
+    pub fn update_bridge_config(&mut self, config: BridgeConfig) {
+        self.assert_owner();
+        self.bridge_config = config;
+    }
+
+    pub fn get_bridge_config(&self) -> BridgeConfig {
+        self.bridge_config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json::Value;
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn treasury() -> AccountId {
+        "treasury.sputnik-dao.near".parse().unwrap()
+    }
+
+    fn mana() -> AccountId {
+        "mana.near".parse().unwrap()
+    }
+
+    // Bridge configured to the treasury DAO
+    fn setup_bridge() -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_bridge_config();
+        config.dao_id = Some(treasury());
+        config.token_id = Some(mana());
+        call_as(&owner());
+        contract.update_bridge_config(config);
+        contract
+    }
+
+    fn treasury_proposal(contract: &Contract) -> TreasuryProposal {
+        contract.get_project_plan(1).unwrap().treasury_proposal.unwrap()
+    }
+
+    // Proposal filed on the treasury by the last call
+    fn filed_proposal(contract: &Contract) -> Value {
+        let calls = function_calls();
+        let (receiver_id, method_name, args, deposit) = &calls[0];
+        assert_eq!((receiver_id, method_name.as_str()), (&treasury(), "add_proposal"));
+        assert_eq!(*deposit, contract.get_bridge_config().proposal_bond.0);
+        assert_eq!(calls[1].1, "on_treasury_proposal_filed");
+        serde_json::from_slice::<Value>(args).unwrap()["proposal"].clone()
+    }
+
+    #[test]
+    fn test_approved_plan_files_transfer_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        let proposal = filed_proposal(&contract);
+        assert_eq!(
+            proposal["kind"],
+            json!({ "Transfer": { "token_id": mana(), "receiver_id": accounts(1), "amount": "1000", "msg": null } })
+        );
+        assert_eq!(treasury_proposal(&contract).status, TreasuryProposalStatus::Filing);
+
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        assert_eq!(contract.on_treasury_proposal_filed(1), Some(7));
+        let treasury_proposal = treasury_proposal(&contract);
+        assert_eq!(treasury_proposal.status, TreasuryProposalStatus::InProgress);
+        assert_eq!(treasury_proposal.proposal_id, Some(7));
+    }
+
+    #[test]
+    fn test_approved_plan_files_batch_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(
+            &mut contract,
+            1,
+            &[(accounts(1), 1_000), (accounts(3), 500), (accounts(4), 0)],
+        );
+        let proposal = filed_proposal(&contract);
+        let actions = proposal["kind"]["FunctionCall"]["actions"].as_array().unwrap();
+        assert_eq!(proposal["kind"]["FunctionCall"]["receiver_id"], json!(mana()));
+        // Developers without an allocation are not paid.
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|action| action["method_name"] == "ft_transfer"));
+    }
+
+    #[test]
+    fn test_failed_filing_is_refiled() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Failed);
+        assert_eq!(contract.on_treasury_proposal_filed(1), None);
+        assert_eq!(treasury_proposal(&contract).status, TreasuryProposalStatus::FilingFailed);
+
+        call_as(&owner());
+        contract.refile_treasury_proposal(1);
+        filed_proposal(&contract);
+        assert_eq!(treasury_proposal(&contract).status, TreasuryProposalStatus::Filing);
+    }
+
+    #[test]
+    fn test_sync_treasury_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        contract.on_treasury_proposal_filed(1);
+
+        call_as(&accounts(3));
+        contract.sync_treasury_proposal(1);
+        assert_eq!(function_calls()[0].1, "get_proposal");
+        callback_with(PromiseResult::Successful(
+            json!({ "id": 7, "status": "Approved" }).to_string().into_bytes(),
+        ));
+        contract.on_treasury_proposal_status(1);
+        assert_eq!(treasury_proposal(&contract).status, TreasuryProposalStatus::Approved);
+    }
+
+    #[test]
+    fn test_sync_timelocked_treasury_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        contract.on_treasury_proposal_filed(1);
+
+        for status in [TreasuryProposalStatus::Queued, TreasuryProposalStatus::Cancelled].iter() {
+            callback_with(PromiseResult::Successful(
+                json!({ "id": 7, "status": status }).to_string().into_bytes(),
+            ));
+            contract.on_treasury_proposal_status(1);
+            assert_eq!(treasury_proposal(&contract).status, *status);
+        }
+    }
+
+    #[test]
+    fn test_plan_without_allocations_files_nothing() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 0)]);
+        assert!(function_calls().is_empty());
+        assert!(contract.get_project_plan(1).unwrap().treasury_proposal.is_none());
+    }
+
+    #[test]
+    fn test_rejected_proposal_is_refiled() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        contract.on_treasury_proposal_filed(1);
+        callback_with(PromiseResult::Successful(
+            json!({ "id": 7, "status": "Expired" }).to_string().into_bytes(),
+        ));
+        contract.on_treasury_proposal_status(1);
+
+        call_as(&owner());
+        contract.refile_treasury_proposal(1);
+        filed_proposal(&contract);
+        let treasury_proposal = treasury_proposal(&contract);
+        assert_eq!(treasury_proposal.status, TreasuryProposalStatus::Filing);
+        assert_eq!(treasury_proposal.proposal_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Treasury proposal is already filed")]
+    fn test_refile_approved_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        callback_with(PromiseResult::Successful(b"7".to_vec()));
+        contract.on_treasury_proposal_filed(1);
+        callback_with(PromiseResult::Successful(
+            json!({ "id": 7, "status": "Approved" }).to_string().into_bytes(),
+        ));
+        contract.on_treasury_proposal_status(1);
+        call_as(&owner());
+        contract.refile_treasury_proposal(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Treasury proposal is already filed")]
+    fn test_refile_filed_proposal() {
+        let mut contract = setup_bridge();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        call_as(&owner());
+        contract.refile_treasury_proposal(1);
+    }
+}
@@ -0,0 +1,628 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Balance};
+
+use crate::bridge::{
+    ext_self, filed_proposal_id, synced_proposal_status, SputnikProposalInput, SputnikProposalKind,
+    TreasuryProposal, GAS_FOR_BRIDGE_CALLBACK,
+};
+use crate::mana_structs::Proposal;
+use crate::permissions::FUNDING;
+use crate::*;
+
+// Fixed point scale for decay and funding ratios
+const SCALE: u128 = 1_000_000_000;
+const BPS_TO_SCALE: u128 = SCALE / 10_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConvictionConfig {
+    pub period: u64, // Nanoseconds between two conviction updates
+    pub decay_bps: u64, // Share of conviction kept after each period
+    pub max_ratio_bps: u64, // Requests at or above this share of the available funds can never pass
+    pub weight_bps: u64, // Share of the supply voting power that must be staked to pass the smallest request
+}
+
+impl Default for ConvictionConfig {
+    fn default() -> Self {
+        Self {
+            period: 1_000_000_000 * 60 * 60,
+            decay_bps: 9_000,
+            max_ratio_bps: 2_000,
+            weight_bps: 25,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FundingProposalStatus {
+    Active,    // Accumulating conviction
+    Passed,    // Conviction reached the threshold, funds were deducted and their payout filed
+    Cancelled, // Withdrawn by the proposer
+}
+
+// Funding request without a voting window. Staked voting power accumulates conviction over time.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FundingProposal {
+    pub id: u64,
+    pub proposer: AccountId,
+    pub proposal: Proposal, // Requests `mana_tokens_allocated`
+    pub stakes: HashMap<AccountId, u64>,
+    pub total_staked: u64,
+    pub conviction: U128, // As of `last_period`
+    pub last_period: u64,
+    pub status: FundingProposalStatus,
+    pub created_at: u64,
+    pub payout: Option<TreasuryProposal>, // Transfer of the requested MANA to the proposer, filed once passed
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ConvictionModule {
+    pub proposals: UnorderedMap<u64, FundingProposal>,
+    pub staked: LookupMap<AccountId, u64>, // Voting power each account has staked across all proposals
+    pub available_funds: Balance, // MANA the treasury sets aside for conviction funding
+    pub last_proposal_id: u64,
+    pub config: ConvictionConfig,
+}
+
+impl Default for ConvictionModule {
+    fn default() -> Self {
+        Self {
+            proposals: UnorderedMap::new(b"u"),
+            staked: LookupMap::new(b"h"),
+            available_funds: 0,
+            last_proposal_id: 0,
+            config: ConvictionConfig::default(),
+        }
+    }
+}
+
+// `base` to the power of `exp`, both in SCALE fixed point
+fn pow_scaled(mut base: u128, mut exp: u64) -> u128 {
+    let mut result = SCALE;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base / SCALE;
+        }
+        base = base * base / SCALE;
+        exp >>= 1;
+    }
+    result
+}
+
+impl ConvictionModule {
+    fn internal_get_proposal(&self, proposal_id: u64) -> FundingProposal {
+        self.proposals.get(&proposal_id).expect("No funding proposal found with this id")
+    }
+
+    fn current_period(&self) -> u64 {
+        env::block_timestamp() / self.config.period
+    }
+
+    // Conviction after the periods elapsed since the last update, with the stake constant meanwhile:
+    // y(t + n) = a^n * y(t) + staked * (1 - a^n) / (1 - a)
+    fn internal_conviction(&self, proposal: &FundingProposal) -> u128 {
+        let elapsed = self.current_period().saturating_sub(proposal.last_period);
+        let decay = self.config.decay_bps as u128 * BPS_TO_SCALE;
+        let decay_n = pow_scaled(decay, elapsed);
+        proposal.conviction.0 * decay_n / SCALE
+            + proposal.total_staked as u128 * (SCALE - decay_n) / (SCALE - decay)
+    }
+
+    // Conviction needed to pass a request, None if the request is too large to ever pass:
+    // threshold = weight * supply / (1 - a) / (max_ratio - requested / available)^2
+    fn internal_threshold(&self, requested: Balance, supply_voting_power: u128) -> Option<u128> {
+        if self.available_funds == 0 {
+            return None;
+        }
+        let ratio = requested.checked_mul(SCALE)? / self.available_funds;
+        let max_ratio = self.config.max_ratio_bps as u128 * BPS_TO_SCALE;
+        if ratio >= max_ratio {
+            return None;
+        }
+        let margin = max_ratio - ratio;
+        let decay = self.config.decay_bps as u128 * BPS_TO_SCALE;
+        let weighted_supply = supply_voting_power * self.config.weight_bps as u128 / 10_000;
+        let threshold = weighted_supply.saturating_mul(SCALE) / (SCALE - decay);
+        let threshold = threshold.saturating_mul(SCALE) / margin;
+        Some(threshold.saturating_mul(SCALE) / margin)
+    }
+
+    // Brings the proposal's conviction up to the current period, with its stakes as they are
+    fn internal_accrue_conviction(&self, proposal: &mut FundingProposal) {
+        proposal.conviction = U128(self.internal_conviction(proposal));
+        proposal.last_period = self.current_period();
+    }
+}
+
+impl Contract {
+    fn internal_supply_voting_power(&self) -> u128 {
+        self.voting_module
+            .internal_supply_voting_power()
+            .expect("Circulating supply has not been reported")
+    }
+
+    // Brings the proposal's conviction up to the current period. Stakes no longer covered by their
+    // staker's current voting power are reduced first, so lost voting power stops adding conviction.
+    fn internal_update_conviction(&mut self, proposal: &mut FundingProposal) {
+        let stakes: Vec<(AccountId, u64)> = proposal
+            .stakes
+            .iter()
+            .map(|(account_id, stake)| (account_id.clone(), *stake))
+            .collect();
+        for (account_id, stake) in stakes {
+            let staked = self.conviction.staked.get(&account_id).unwrap_or(0);
+            let excess = staked.saturating_sub(self.voting_module.internal_voting_power(&account_id));
+            if excess == 0 {
+                continue;
+            }
+            let reduction = excess.min(stake);
+            if reduction == stake {
+                proposal.stakes.remove(&account_id);
+            } else {
+                proposal.stakes.insert(account_id.clone(), stake - reduction);
+            }
+            proposal.total_staked -= reduction;
+            self.conviction.staked.insert(&account_id, &(staked - reduction));
+        }
+        self.conviction.internal_accrue_conviction(proposal);
+    }
+
+    // Passes the proposal once its conviction reaches the threshold. Stakes are released, the requested
+    // funds are deducted from the available funds and their transfer to the proposer is filed on the treasury.
+    fn internal_check_conviction(&mut self, proposal: &mut FundingProposal) {
+        let requested = proposal.proposal.mana_tokens_allocated.0;
+        let threshold = self
+            .conviction
+            .internal_threshold(requested, self.internal_supply_voting_power());
+        if !matches!(threshold, Some(threshold) if proposal.conviction.0 >= threshold) {
+            return;
+        }
+        proposal.status = FundingProposalStatus::Passed;
+        self.conviction.available_funds -= requested;
+        self.internal_release_stakes(proposal);
+        self.internal_file_funding_payout(proposal);
+
+        env::log_str(&format!(
+            "Funding proposal {} passed with conviction {}, allocating {} MANA",
+            proposal.id, proposal.conviction.0, requested
+        ));
+    }
+
+    // Files the transfer of the requested MANA to the proposer on the sputnikdao2 treasury, if one is configured
+    fn internal_file_funding_payout(&mut self, proposal: &mut FundingProposal) {
+        let (dao_id, token_id) = match self.bridge_config.treasury() {
+            Some(treasury) => treasury,
+            None => return,
+        };
+        let payout = SputnikProposalInput {
+            description: format!("sagahalla-dao funding proposal {}: {}", proposal.id, proposal.proposal.title),
+            kind: SputnikProposalKind::Transfer {
+                token_id: token_id.to_string(),
+                receiver_id: proposal.proposer.clone(),
+                amount: proposal.proposal.mana_tokens_allocated,
+                msg: None,
+            },
+        };
+        let (treasury_proposal, promise) = self.internal_add_treasury_proposal(dao_id, payout);
+        promise.then(ext_self::on_funding_payout_filed(
+            proposal.id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_BRIDGE_CALLBACK,
+        ));
+        proposal.payout = Some(treasury_proposal);
+    }
+
+    fn internal_update_funding_payout(&mut self, proposal_id: u64, update: impl FnOnce(&mut TreasuryProposal)) {
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        let payout = proposal.payout.as_mut().expect("Funding proposal has no payout");
+        update(payout);
+        payout.updated_at = env::block_timestamp();
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+    }
+
+    fn internal_release_stakes(&mut self, proposal: &mut FundingProposal) {
+        for (account_id, amount) in proposal.stakes.drain() {
+            let staked = self.conviction.staked.get(&account_id).unwrap_or(0);
+            self.conviction.staked.insert(&account_id, &(staked - amount));
+        }
+        proposal.total_staked = 0;
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Opens a funding proposal requesting its `mana_tokens_allocated` from the conviction funds
+    pub fn submit_funding_proposal(&mut self, proposal: Proposal) -> u64 {
+        self.assert_permission(FUNDING, "Submit");
+        require!(proposal.mana_tokens_allocated.0 > 0, "Funding proposal must request tokens");
+        let id = self.conviction.last_proposal_id;
+        let funding_proposal = FundingProposal {
+            id,
+            proposer: env::predecessor_account_id(),
+            proposal,
+            stakes: HashMap::new(),
+            total_staked: 0,
+            conviction: U128(0),
+            last_period: self.conviction.current_period(),
+            status: FundingProposalStatus::Active,
+            created_at: env::block_timestamp(),
+            payout: None,
+        };
+        self.conviction.proposals.insert(&id, &funding_proposal);
+        self.conviction.last_proposal_id += 1;
+        id
+    }
+
+    // Stakes voting power on a proposal. An account's stakes across proposals can't exceed its voting power.
+    pub fn stake_conviction(&mut self, proposal_id: u64, amount: u64) {
+        self.assert_permission(FUNDING, "Stake");
+        let account_id = env::predecessor_account_id();
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        require!(proposal.status == FundingProposalStatus::Active, "Funding proposal is not active");
+        self.internal_update_conviction(&mut proposal);
+        let staked = self.conviction.staked.get(&account_id).unwrap_or(0) + amount;
+        require!(
+            staked <= self.voting_module.internal_voting_power(&account_id),
+            "Not enough voting power to stake"
+        );
+
+        *proposal.stakes.entry(account_id.clone()).or_insert(0) += amount;
+        proposal.total_staked += amount;
+        self.conviction.staked.insert(&account_id, &staked);
+        self.internal_check_conviction(&mut proposal);
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+    }
+
+    pub fn withdraw_conviction(&mut self, proposal_id: u64, amount: u64) {
+        let account_id = env::predecessor_account_id();
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        if proposal.status == FundingProposalStatus::Active {
+            self.internal_update_conviction(&mut proposal);
+        }
+        let stake = proposal.stakes.get(&account_id).copied().unwrap_or(0);
+        require!(amount <= stake, "Not enough stake to withdraw");
+        if stake == amount {
+            proposal.stakes.remove(&account_id);
+        } else {
+            proposal.stakes.insert(account_id.clone(), stake - amount);
+        }
+        proposal.total_staked -= amount;
+        let staked = self.conviction.staked.get(&account_id).unwrap_or(0);
+        self.conviction.staked.insert(&account_id, &(staked - amount));
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+    }
+
+    // Updates a proposal's conviction and passes it if it reached the threshold. Anyone can call this.
+    pub fn update_conviction(&mut self, proposal_id: u64) -> FundingProposalStatus {
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        require!(proposal.status == FundingProposalStatus::Active, "Funding proposal is not active");
+        self.internal_update_conviction(&mut proposal);
+        self.internal_check_conviction(&mut proposal);
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+        proposal.status
+    }
+
+    pub fn cancel_funding_proposal(&mut self, proposal_id: u64) {
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        require!(
+            env::predecessor_account_id() == proposal.proposer,
+            "Only the proposer can cancel a funding proposal"
+        );
+        require!(proposal.status == FundingProposalStatus::Active, "Funding proposal is not active");
+        proposal.status = FundingProposalStatus::Cancelled;
+        self.internal_release_stakes(&mut proposal);
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+    }
+
+    // Sets the MANA available to conviction funding. Owner only. Passed proposals are deducted from it
+    // and paid out by the treasury, which should hold at least this much.
+    pub fn update_available_funds(&mut self, available_funds: U128) {
+        self.assert_owner();
+        self.conviction.available_funds = available_funds.0;
+    }
+
+    // Records the id of the filed payout, or marks the filing as failed
+    #[private]
+    pub fn on_funding_payout_filed(&mut self, proposal_id: u64) -> Option<u64> {
+        let payout_id = filed_proposal_id();
+        self.internal_update_funding_payout(proposal_id, |payout| payout.record_filing(payout_id));
+        payout_id
+    }
+
+    #[private]
+    pub fn on_funding_payout_status(&mut self, proposal_id: u64) {
+        if let Some(status) = synced_proposal_status() {
+            self.internal_update_funding_payout(proposal_id, |payout| payout.status = status);
+        }
+    }
+
+    // Files the payout of a passed proposal whose filing failed or whose treasury proposal ended without
+    // paying out, as last synced, or that passed before the bridge was configured. Owner only.
+    pub fn refile_funding_payout(&mut self, proposal_id: u64) {
+        self.assert_owner();
+        let mut proposal = self.conviction.internal_get_proposal(proposal_id);
+        require!(proposal.status == FundingProposalStatus::Passed, "Funding proposal has not passed");
+        if let Some(payout) = proposal.payout.as_ref() {
+            require!(payout.status.can_refile(), "Funding payout is already filed");
+        }
+        self.internal_file_funding_payout(&mut proposal);
+        self.conviction.proposals.insert(&proposal_id, &proposal);
+    }
+
+    // Fetches the current status of a passed proposal's payout from sputnikdao2. Anyone can call this.
+    pub fn sync_funding_payout(&mut self, proposal_id: u64) {
+        let proposal = self.conviction.internal_get_proposal(proposal_id);
+        let payout = proposal.payout.expect("Funding proposal has no payout");
+        self.internal_get_treasury_proposal(payout).then(ext_self::on_funding_payout_status(
+            proposal_id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_BRIDGE_CALLBACK,
+        ));
+    }
+
+    pub fn update_conviction_config(&mut self, config: ConvictionConfig) {
+        self.assert_owner();
+        require!(config.period > 0, "Conviction period must be positive");
+        require!(config.decay_bps < 10_000, "Decay must keep less than 100% of conviction");
+        require!(config.max_ratio_bps <= 10_000, "Max ratio cannot exceed 100% of the funds");
+        self.conviction.config = config;
+    }
+
+    pub fn get_conviction_config(&self) -> ConvictionConfig {
+        self.conviction.config.clone()
+    }
+
+    pub fn get_available_funds(&self) -> U128 {
+        U128(self.conviction.available_funds)
+    }
+
+    // Funding proposal with its conviction as of the current period. Stakes above their staker's
+    // voting power are only reduced by the next update.
+    pub fn get_funding_proposal(&self, proposal_id: u64) -> Option<FundingProposal> {
+        self.conviction.proposals.get(&proposal_id).map(|mut proposal| {
+            if proposal.status == FundingProposalStatus::Active {
+                self.conviction.internal_accrue_conviction(&mut proposal);
+            }
+            proposal
+        })
+    }
+
+    // Conviction a proposal needs to pass with the current funds and supply, None if it can't pass
+    pub fn get_conviction_threshold(&self, proposal_id: u64) -> Option<U128> {
+        let proposal = self.conviction.internal_get_proposal(proposal_id);
+        self.conviction
+            .internal_threshold(
+                proposal.proposal.mana_tokens_allocated.0,
+                self.internal_supply_voting_power(),
+            )
+            .map(U128)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json::{self, json, Value};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::PromiseResult;
+
+    use crate::bridge::TreasuryProposalStatus;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    const HOUR: u64 = 1_000_000_000 * 60 * 60;
+
+    fn funding_proposal(mana_tokens_allocated: u128) -> Proposal {
+        Proposal {
+            id: 1,
+            title: "Saga art book".to_string(),
+            description: None,
+            yes_votes: 0,
+            no_votes: 0,
+            mana_tokens_allocated: U128(mana_tokens_allocated),
+            is_ended: false,
+            submitted_by: accounts(1),
+            mana_hours_budgeted: 10,
+            target_date: None,
+            created_at: "2024-01-01".to_string(),
+            updated_at: None,
+            parent_id: None,
+            sub_projects: vec![],
+            budget_items: vec![],
+        }
+    }
+
+    // 10,000 MANA available with a supply of 10,000 voting power, and bob's proposal 0 requesting
+    // `requested` with charlie holding 1,000 voting power
+    fn setup_funding(requested: u128) -> Contract {
+        let mut contract = setup_contract();
+        contract.update_circulating_supply(U128(10_000), U128(0));
+        contract.update_available_funds(U128(10_000));
+        set_voting_power(&mut contract, &accounts(2), 1_000);
+        call_as(&accounts(1));
+        contract.submit_funding_proposal(funding_proposal(requested));
+        contract
+    }
+
+    #[test]
+    fn test_conviction_passes_over_time() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        // Threshold is 25 / (1 - 0.9) / (0.2 - 0.01)^2, the full stake's conviction grows towards 10,000.
+        assert_eq!(contract.get_conviction_threshold(0), Some(U128(6_921)));
+
+        call_at(&accounts(3), 11 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Active);
+        assert_eq!(contract.get_funding_proposal(0).unwrap().conviction, U128(6_861));
+        call_at(&accounts(3), 12 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Passed);
+        assert_eq!(contract.get_available_funds(), U128(9_900));
+        // Stakes are released once the proposal passed.
+        let proposal = contract.get_funding_proposal(0).unwrap();
+        assert_eq!((proposal.total_staked, proposal.stakes.len()), (0, 0));
+        assert_eq!(contract.conviction.staked.get(&accounts(2)), Some(0));
+    }
+
+    // Bridge configured to a treasury DAO, with proposal 0 passed by charlie's full stake after 12 hours
+    fn setup_passed_with_bridge() -> Contract {
+        let mut contract = setup_funding(100);
+        call_as(&owner());
+        let mut config = contract.get_bridge_config();
+        config.dao_id = Some("treasury.sputnik-dao.near".parse().unwrap());
+        config.token_id = Some("mana.near".parse().unwrap());
+        contract.update_bridge_config(config);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        call_at(&accounts(3), 12 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Passed);
+        contract
+    }
+
+    fn payout(contract: &Contract) -> TreasuryProposal {
+        contract.get_funding_proposal(0).unwrap().payout.unwrap()
+    }
+
+    #[test]
+    fn test_passed_proposal_files_payout() {
+        let mut contract = setup_passed_with_bridge();
+        let calls = function_calls();
+        let (receiver_id, method_name, args, _) = &calls[0];
+        assert_eq!(
+            (receiver_id.as_str(), method_name.as_str()),
+            ("treasury.sputnik-dao.near", "add_proposal")
+        );
+        assert_eq!(calls[1].1, "on_funding_payout_filed");
+        let proposal = serde_json::from_slice::<Value>(args).unwrap()["proposal"].clone();
+        assert_eq!(
+            proposal["kind"],
+            json!({ "Transfer": { "token_id": "mana.near", "receiver_id": accounts(1), "amount": "100", "msg": null } })
+        );
+        assert_eq!(payout(&contract).status, TreasuryProposalStatus::Filing);
+
+        callback_with(PromiseResult::Successful(b"3".to_vec()));
+        assert_eq!(contract.on_funding_payout_filed(0), Some(3));
+        assert_eq!(payout(&contract).proposal_id, Some(3));
+        assert_eq!(payout(&contract).status, TreasuryProposalStatus::InProgress);
+
+        call_as(&accounts(3));
+        contract.sync_funding_payout(0);
+        callback_with(PromiseResult::Successful(
+            json!({ "id": 3, "status": "Approved" }).to_string().into_bytes(),
+        ));
+        contract.on_funding_payout_status(0);
+        assert_eq!(payout(&contract).status, TreasuryProposalStatus::Approved);
+    }
+
+    #[test]
+    fn test_failed_payout_is_refiled() {
+        let mut contract = setup_passed_with_bridge();
+        callback_with(PromiseResult::Failed);
+        assert_eq!(contract.on_funding_payout_filed(0), None);
+        assert_eq!(payout(&contract).status, TreasuryProposalStatus::FilingFailed);
+
+        call_as(&owner());
+        contract.refile_funding_payout(0);
+        assert_eq!(function_calls()[0].1, "add_proposal");
+        assert_eq!(payout(&contract).status, TreasuryProposalStatus::Filing);
+    }
+
+    #[test]
+    #[should_panic(expected = "Funding payout is already filed")]
+    fn test_refile_pending_payout() {
+        let mut contract = setup_passed_with_bridge();
+        call_as(&owner());
+        contract.refile_funding_payout(0);
+    }
+
+    #[test]
+    fn test_conviction_decays_after_withdraw() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        call_at(&accounts(2), 10 * HOUR);
+        contract.withdraw_conviction(0, 1_000);
+        let conviction = contract.get_funding_proposal(0).unwrap().conviction.0;
+
+        call_at(&accounts(2), 20 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Active);
+        assert!(contract.get_funding_proposal(0).unwrap().conviction.0 < conviction / 2);
+    }
+
+    #[test]
+    fn test_request_too_large_never_passes() {
+        let mut contract = setup_funding(2_000);
+        assert_eq!(contract.get_conviction_threshold(0), None);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        call_at(&accounts(2), 1_000 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Active);
+        assert_eq!(contract.get_available_funds(), U128(10_000));
+    }
+
+    #[test]
+    fn test_stake_reduced_with_voting_power() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        // Charlie's voting power drops, so only 400 of the stake keeps adding conviction.
+        set_voting_power(&mut contract, &accounts(2), 400);
+
+        call_at(&accounts(3), 12 * HOUR);
+        assert_eq!(contract.update_conviction(0), FundingProposalStatus::Active);
+        let proposal = contract.get_funding_proposal(0).unwrap();
+        assert_eq!((proposal.total_staked, proposal.stakes[&accounts(2)]), (400, 400));
+        assert_eq!(contract.conviction.staked.get(&accounts(2)), Some(400));
+        assert!(proposal.conviction.0 < 4_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough stake to withdraw")]
+    fn test_withdraw_stake_lost_with_voting_power() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 1_000);
+        set_voting_power(&mut contract, &accounts(2), 400);
+        call_as(&accounts(2));
+        contract.withdraw_conviction(0, 401);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough voting power to stake")]
+    fn test_stake_over_voting_power() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(1));
+        contract.submit_funding_proposal(funding_proposal(100));
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 600);
+        contract.stake_conviction(1, 401);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough stake to withdraw")]
+    fn test_withdraw_over_stake() {
+        let mut contract = setup_funding(100);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 600);
+        contract.withdraw_conviction(0, 601);
+    }
+
+    #[test]
+    #[should_panic(expected = "Funding proposal is not active")]
+    fn test_stake_on_cancelled_proposal() {
+        let mut contract = setup_funding(100);
+        contract.cancel_funding_proposal(0);
+        call_as(&accounts(2));
+        contract.stake_conviction(0, 600);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{UnorderedMap, Vector};
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId};
+
+use crate::permissions::CURATION;
+use crate::voting::{quadratic_weight, VotingMode};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum WorkKind {
+    Art,
+    Story,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WorkMetadata {
+    pub title: String,
+    pub description: Option<String>,
+    pub kind: WorkKind,
+    pub media: Option<String>, // URL or content-addressed link to the work itself
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum WorkStatus {
+    Submitted, // Waiting for its round to close
+    Accepted,  // Entered the curated collection
+    Rejected,
+}
+
+// Digital art or story submitted by a creator to a curation round
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Work {
+    pub id: u64,
+    pub round_id: u64,
+    pub creator_id: AccountId,
+    pub content_hash: Base58CryptoHash, // sha256 of the work's content
+    pub metadata: WorkMetadata,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub votes: HashMap<AccountId, bool>, // Curator votes, true to accept
+    pub status: WorkStatus,
+    pub submitted_at: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CurationRoundStatus {
+    Open,
+    Closed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CurationRound {
+    pub id: u64,
+    pub title: String,
+    pub start_time: u64,
+    pub end_time: u64, // Submissions and votes are accepted until this block timestamp
+    pub work_ids: Vec<u64>,
+    pub status: CurationRoundStatus,
+    pub mode: VotingMode,
+    pub credits_spent: HashMap<AccountId, u64>, // Quadratic credits each curator spent in this round
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CurationConfig {
+    pub quorum: u64, // Vote weight in favour a work needs, on top of a majority, to be accepted
+    pub credits_per_voting_power: u64, // Quadratic credit budget per round for each unit of voting power
+}
+
+impl Default for CurationConfig {
+    fn default() -> Self {
+        Self {
+            quorum: 1,
+            credits_per_voting_power: 1,
+        }
+    }
+}
+
+// Curation rounds, submitted works and the curated collection
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CurationModule {
+    pub rounds: UnorderedMap<u64, CurationRound>,
+    pub works: UnorderedMap<u64, Work>,
+    pub collection: Vector<u64>, // Accepted work ids in order of acceptance
+    pub last_round_id: u64,
+    pub last_work_id: u64,
+    pub config: CurationConfig,
+}
+
+impl Default for CurationModule {
+    fn default() -> Self {
+        Self {
+            rounds: UnorderedMap::new(b"c"),
+            works: UnorderedMap::new(b"w"),
+            collection: Vector::new(b"k"),
+            last_round_id: 0,
+            last_work_id: 0,
+            config: CurationConfig::default(),
+        }
+    }
+}
+
+impl CurationModule {
+    fn internal_get_round(&self, round_id: u64) -> CurationRound {
+        self.rounds.get(&round_id).expect("No curation round found with this id")
+    }
+
+    pub(crate) fn internal_get_work(&self, work_id: u64) -> Work {
+        self.works.get(&work_id).expect("No work found with this id")
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Opens a curation round accepting submissions and votes for `duration` nanoseconds.
+    // Votes weigh the curator's voting power unless the round uses quadratic voting.
+    pub fn open_curation_round(&mut self, title: String, duration: u64, mode: Option<VotingMode>) -> u64 {
+        self.assert_owner();
+        let curation = &mut self.curation;
+        let id = curation.last_round_id;
+        let start_time = env::block_timestamp();
+        curation.rounds.insert(
+            &id,
+            &CurationRound {
+                id,
+                title,
+                start_time,
+                end_time: start_time + duration,
+                work_ids: vec![],
+                status: CurationRoundStatus::Open,
+                mode: mode.unwrap_or_default(),
+                credits_spent: HashMap::new(),
+            },
+        );
+        curation.last_round_id += 1;
+        id
+    }
+
+    // Submits a work created by the caller to an open round
+    pub fn submit_work(&mut self, round_id: u64, content_hash: Base58CryptoHash, metadata: WorkMetadata) -> u64 {
+        self.assert_permission(CURATION, "Submit");
+        let curation = &mut self.curation;
+        let mut round = curation.internal_get_round(round_id);
+        require!(
+            round.status == CurationRoundStatus::Open && env::block_timestamp() <= round.end_time,
+            "Curation round is not accepting submissions"
+        );
+        let id = curation.last_work_id;
+        curation.works.insert(
+            &id,
+            &Work {
+                id,
+                round_id,
+                creator_id: env::predecessor_account_id(),
+                content_hash,
+                metadata,
+                votes_for: 0,
+                votes_against: 0,
+                votes: HashMap::new(),
+                status: WorkStatus::Submitted,
+                submitted_at: env::block_timestamp(),
+            },
+        );
+        round.work_ids.push(id);
+        curation.rounds.insert(&round_id, &round);
+        curation.last_work_id += 1;
+        id
+    }
+
+    // Curator vote on a work, weighted by the caller's governance voting power.
+    // In quadratic rounds the curator spends `credits` from their round budget and the vote weighs sqrt(credits).
+    pub fn vote_work(&mut self, work_id: u64, accept: bool, credits: Option<u64>) {
+        self.assert_permission(CURATION, "Vote");
+        let voter = env::predecessor_account_id();
+        let voting_power = self.voting_module.internal_voting_power(&voter);
+        require!(voting_power > 0, "Voter has no voting power");
+
+        let mut work = self.curation.internal_get_work(work_id);
+        let mut round = self.curation.internal_get_round(work.round_id);
+        require!(
+            round.status == CurationRoundStatus::Open && env::block_timestamp() <= round.end_time,
+            "Curation round is not open for voting"
+        );
+        require!(work.creator_id != voter, "Creators cannot vote on their own work");
+        let weight = match round.mode {
+            VotingMode::Standard => {
+                require!(credits.is_none(), "Credits are only spent in quadratic rounds");
+                voting_power
+            }
+            VotingMode::Quadratic => {
+                let credits = credits.expect("Quadratic rounds need credits to spend");
+                require!(credits > 0, "Credits must be positive");
+                let budget = voting_power * self.curation.config.credits_per_voting_power;
+                let spent = round.credits_spent.entry(voter.clone()).or_insert(0);
+                require!(*spent + credits <= budget, "Not enough credits left in this round");
+                *spent += credits;
+                self.curation.rounds.insert(&round.id, &round);
+                quadratic_weight(credits)
+            }
+        };
+        require!(work.votes.insert(voter, accept).is_none(), "Voter has already voted");
+        if accept {
+            work.votes_for += weight;
+        } else {
+            work.votes_against += weight;
+        }
+        self.curation.works.insert(&work_id, &work);
+    }
+
+    // Quadratic credits the account has left in a round
+    pub fn get_remaining_credits(&self, round_id: u64, account_id: AccountId) -> u64 {
+        let round = self.curation.internal_get_round(round_id);
+        if round.mode != VotingMode::Quadratic {
+            return 0;
+        }
+        let budget = self.voting_module.internal_voting_power(&account_id) * self.curation.config.credits_per_voting_power;
+        budget.saturating_sub(round.credits_spent.get(&account_id).copied().unwrap_or(0))
+    }
+
+    // Closes a round once it ended. Works with a majority in favour that reach the quorum join the collection.
+    pub fn close_curation_round(&mut self, round_id: u64) {
+        let curation = &mut self.curation;
+        let mut round = curation.internal_get_round(round_id);
+        require!(round.status == CurationRoundStatus::Open, "Curation round is already closed");
+        require!(env::block_timestamp() > round.end_time, "Curation round has not ended");
+
+        for work_id in round.work_ids.iter() {
+            let mut work = curation.internal_get_work(*work_id);
+            if work.votes_for > work.votes_against && work.votes_for >= curation.config.quorum {
+                work.status = WorkStatus::Accepted;
+                curation.collection.push(work_id);
+            } else {
+                work.status = WorkStatus::Rejected;
+            }
+            curation.works.insert(work_id, &work);
+        }
+        round.status = CurationRoundStatus::Closed;
+        curation.rounds.insert(&round_id, &round);
+
+        env::log_str(&format!("Curation round {} closed", round_id));
+    }
+
+    pub fn update_curation_config(&mut self, config: CurationConfig) {
+        self.assert_owner();
+        self.curation.config = config;
+    }
+
+    pub fn get_curation_config(&self) -> CurationConfig {
+        self.curation.config.clone()
+    }
+
+    pub fn get_curation_round(&self, round_id: u64) -> Option<CurationRound> {
+        self.curation.rounds.get(&round_id)
+    }
+
+    pub fn get_work(&self, work_id: u64) -> Option<Work> {
+        self.curation.works.get(&work_id)
+    }
+
+    // Works of the curated collection in order of acceptance
+    pub fn get_curated_works(&self, from_index: u64, limit: u64) -> Vec<Work> {
+        (from_index..std::cmp::min(from_index + limit, self.curation.collection.len()))
+            .filter_map(|index| self.curation.collection.get(index))
+            .filter_map(|work_id| self.curation.works.get(&work_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn metadata(title: &str) -> WorkMetadata {
+        WorkMetadata {
+            title: title.to_string(),
+            description: None,
+            kind: WorkKind::Story,
+            media: None,
+        }
+    }
+
+    // Round 0 of 100 nanoseconds with bob's work 0, and curators charlie (4) and danny (1)
+    fn setup_round(mode: Option<VotingMode>) -> Contract {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(2), 4);
+        set_voting_power(&mut contract, &accounts(3), 1);
+        call_as(&owner());
+        contract.open_curation_round("Round 1".to_string(), 100, mode);
+        call_as(&accounts(1));
+        contract.submit_work(0, [1; 32].into(), metadata("Saga"));
+        contract
+    }
+
+    #[test]
+    fn test_curation_round() {
+        let mut contract = setup_round(None);
+        call_as(&accounts(1));
+        contract.submit_work(0, [2; 32].into(), metadata("Edda"));
+        call_as(&accounts(2));
+        contract.vote_work(0, true, None);
+        contract.vote_work(1, false, None);
+        call_as(&accounts(3));
+        contract.vote_work(1, true, None);
+
+        call_at(&accounts(3), 101);
+        contract.close_curation_round(0);
+        assert_eq!(contract.get_work(0).unwrap().status, WorkStatus::Accepted);
+        assert_eq!(contract.get_work(1).unwrap().status, WorkStatus::Rejected);
+        let curated: Vec<u64> = contract.get_curated_works(0, 10).iter().map(|work| work.id).collect();
+        assert_eq!(curated, vec![0]);
+    }
+
+    #[test]
+    fn test_quadratic_round() {
+        let mut contract = setup_round(Some(VotingMode::Quadratic));
+        call_as(&accounts(2));
+        assert_eq!(contract.get_remaining_credits(0, accounts(2)), 4);
+        contract.vote_work(0, false, Some(4));
+        assert_eq!(contract.get_remaining_credits(0, accounts(2)), 0);
+        call_as(&accounts(3));
+        contract.vote_work(0, true, Some(1));
+
+        // Four credits only weigh twice as much as one.
+        let work = contract.get_work(0).unwrap();
+        assert_eq!((work.votes_for, work.votes_against), (1, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough credits left in this round")]
+    fn test_quadratic_vote_over_budget() {
+        let mut contract = setup_round(Some(VotingMode::Quadratic));
+        call_as(&accounts(3));
+        contract.vote_work(0, true, Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Creators cannot vote on their own work")]
+    fn test_vote_own_work() {
+        let mut contract = setup_round(None);
+        set_voting_power(&mut contract, &accounts(1), 1);
+        call_as(&accounts(1));
+        contract.vote_work(0, true, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Curation round is not open for voting")]
+    fn test_vote_after_round_ended() {
+        let mut contract = setup_round(None);
+        call_at(&accounts(2), 101);
+        contract.vote_work(0, true, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Curation round has not ended")]
+    fn test_close_round_early() {
+        let mut contract = setup_round(None);
+        contract.close_curation_round(0);
+    }
+}
@@ -0,0 +1,379 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Promise};
+
+use crate::permissions::DISPUTE;
+use crate::voting::{ProjectExecutionStatus, ProjectExecutionVote};
+use crate::*;
+
+// How an open dispute gets ruled on
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DisputeMethod {
+    Arbitration,            // A role allowed to arbitrate disputes rules on the dispute
+    ReVote { quorum: u64 }, // The execution goes back to a vote with a larger quorum
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DisputeStatus {
+    Open,       // Waiting for the arbitration or the re-vote
+    Upheld,     // Rejection stands, bond is forfeited to the DAO
+    Overturned, // Execution is approved, bond is returned
+    Expired,    // No ruling within the dispute period, rejection stands and bond is returned
+}
+
+// Appeal against a rejected project execution, stored on its ProjectExecutionVote
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Dispute {
+    pub appellant_id: AccountId,
+    pub reason: String,
+    pub bond: U128,
+    pub method: DisputeMethod,
+    pub status: DisputeStatus,
+    pub opened_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DisputeConfig {
+    pub appeal_period: u64, // Nanoseconds after a rejection during which a dispute can be opened
+    pub appeal_bond: U128,
+    pub dispute_period: u64, // Nanoseconds an open dispute waits for its ruling before it can be expired
+    pub arbitrated: bool, // Arbitrated disputes are ruled on by a role allowed to arbitrate, others go to a re-vote
+    pub revote_quorum_multiplier: u64,
+}
+
+impl Default for DisputeConfig {
+    fn default() -> Self {
+        Self {
+            appeal_period: 1_000_000_000 * 60 * 60 * 24 * 3,
+            appeal_bond: U128(10u128.pow(24)),
+            dispute_period: 1_000_000_000 * 60 * 60 * 24 * 7,
+            arbitrated: false,
+            revote_quorum_multiplier: 2,
+        }
+    }
+}
+
+impl Contract {
+    // Closes the open dispute on `vote`. Overturning it returns the bond and completes the execution's tasks.
+    pub(crate) fn internal_resolve_dispute(&mut self, vote: &mut ProjectExecutionVote, overturn: bool) {
+        let now = env::block_timestamp();
+        let mut dispute = vote.dispute.take().expect("Project execution has no dispute");
+        require!(dispute.status == DisputeStatus::Open, "Dispute is already resolved");
+
+        dispute.resolved_at = Some(now);
+        self.locked_amount -= dispute.bond.0;
+        if overturn {
+            dispute.status = DisputeStatus::Overturned;
+            vote.status = ProjectExecutionStatus::Approved;
+            Promise::new(dispute.appellant_id.clone()).transfer(dispute.bond.0);
+            self.internal_complete_tasks(vote.project_execution_id);
+        } else {
+            // Forfeited bond stays in the DAO balance.
+            dispute.status = DisputeStatus::Upheld;
+            vote.status = ProjectExecutionStatus::Rejected;
+        }
+        vote.resolved_at = Some(now);
+
+        env::log_str(&format!(
+            "Dispute on project execution {} resolved: {:?}",
+            vote.project_execution_id, dispute.status
+        ));
+        vote.dispute = Some(dispute);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Appeals a rejected execution. Only its developer can call this, within the appeal period,
+    // attaching exactly the appeal bond.
+    #[payable]
+    pub fn open_dispute(&mut self, project_execution_id: u64, reason: String) {
+        let mut vote = self.internal_get_execution_vote(project_execution_id);
+        let config = self.dispute_config.clone();
+        let now = env::block_timestamp();
+
+        require!(vote.status == ProjectExecutionStatus::Rejected, "Only rejected executions can be disputed");
+        require!(vote.dispute.is_none(), "Project execution has already been disputed");
+        require!(
+            env::predecessor_account_id() == vote.developer_id,
+            "Only the developer of the execution can open a dispute"
+        );
+        require!(
+            now <= vote.resolved_at.unwrap_or(0) + config.appeal_period,
+            "Appeal period has ended"
+        );
+        require!(env::attached_deposit() == config.appeal_bond.0, "Attached deposit must equal the appeal bond");
+
+        let method = if config.arbitrated {
+            DisputeMethod::Arbitration
+        } else {
+            let quorum = vote.quorum.saturating_mul(config.revote_quorum_multiplier);
+            vote.votes_for = 0;
+            vote.votes_against = 0;
+            vote.voters.clear();
+            vote.quorum = quorum;
+            DisputeMethod::ReVote { quorum }
+        };
+        vote.dispute = Some(Dispute {
+            appellant_id: env::predecessor_account_id(),
+            reason,
+            bond: config.appeal_bond,
+            method,
+            status: DisputeStatus::Open,
+            opened_at: now,
+            resolved_at: None,
+        });
+        vote.status = ProjectExecutionStatus::Disputed;
+        self.locked_amount += env::attached_deposit();
+        self.voting_module
+            .project_execution_votes
+            .insert(&project_execution_id, &vote);
+
+        env::log_str(&format!("Dispute opened on project execution {}", project_execution_id));
+    }
+
+    // Arbitration ruling on an open dispute, by a role allowed to arbitrate.
+    // `overturn` approves the execution and returns the bond.
+    pub fn resolve_dispute(&mut self, project_execution_id: u64, overturn: bool) {
+        let mut vote = self.internal_get_execution_vote(project_execution_id);
+        match vote.dispute.as_ref().map(|dispute| &dispute.method) {
+            Some(DisputeMethod::Arbitration) => self.assert_permission(DISPUTE, "Arbitrate"),
+            Some(DisputeMethod::ReVote { .. }) => env::panic_str("Dispute is resolved by re-vote"),
+            None => env::panic_str("Project execution has no dispute"),
+        }
+        self.internal_resolve_dispute(&mut vote, overturn);
+        self.voting_module
+            .project_execution_votes
+            .insert(&project_execution_id, &vote);
+    }
+
+    // Closes a dispute that got no ruling within the dispute period. The rejection stands, and the bond
+    // is returned since the appellant is not at fault for the missing ruling. Anyone can call this.
+    pub fn expire_dispute(&mut self, project_execution_id: u64) {
+        let mut vote = self.internal_get_execution_vote(project_execution_id);
+        let now = env::block_timestamp();
+        let dispute = vote.dispute.as_mut().expect("Project execution has no dispute");
+        require!(dispute.status == DisputeStatus::Open, "Dispute is already resolved");
+        require!(
+            now > dispute.opened_at + self.dispute_config.dispute_period,
+            "Dispute period has not ended"
+        );
+
+        dispute.status = DisputeStatus::Expired;
+        dispute.resolved_at = Some(now);
+        self.locked_amount -= dispute.bond.0;
+        Promise::new(dispute.appellant_id.clone()).transfer(dispute.bond.0);
+        vote.status = ProjectExecutionStatus::Rejected;
+        vote.resolved_at = Some(now);
+        self.voting_module
+            .project_execution_votes
+            .insert(&project_execution_id, &vote);
+
+        env::log_str(&format!("Dispute on project execution {} expired", project_execution_id));
+    }
+
+    pub fn update_dispute_config(&mut self, config: DisputeConfig) {
+        self.assert_owner();
+        require!(config.revote_quorum_multiplier > 0, "Re-vote quorum multiplier must be positive");
+        self.dispute_config = config;
+    }
+
+    pub fn get_dispute_config(&self) -> DisputeConfig {
+        self.dispute_config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use sputnik_policy::RoleKind;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    const BOND: u128 = 1_000;
+
+    // Execution 1 of bob's approved plan, rejected by danny at quorum 2. Disputes are arbitrated by an
+    // "arbitrator" role of eugene if `arbitrated` is set, otherwise go to a re-vote where eugene has 4 voting power.
+    fn setup_rejected_execution(arbitrated: bool) -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_voting_config();
+        config.execution_quorum = 2;
+        call_as(&owner());
+        contract.update_voting_config(config);
+        contract.update_dispute_config(DisputeConfig {
+            appeal_bond: U128(BOND),
+            arbitrated,
+            ..DisputeConfig::default()
+        });
+        if arbitrated {
+            let mut everyone = contract.get_roles().remove(0);
+            everyone.permissions.remove("dispute:Arbitrate");
+            contract.add_or_update_role(everyone);
+            contract.add_or_update_role(RolePermission {
+                name: "arbitrator".to_string(),
+                kind: RoleKind::Group(vec![accounts(4)].into_iter().collect()),
+                permissions: vec!["dispute:Arbitrate".to_string()].into_iter().collect(),
+            });
+        }
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        set_voting_power(&mut contract, &accounts(3), 2);
+        set_voting_power(&mut contract, &accounts(4), 4);
+
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10)]));
+        call_as(&accounts(3));
+        contract.vote_project_execution(1, false);
+        contract
+    }
+
+    fn open_dispute(contract: &mut Contract) {
+        call_with_deposit(&accounts(1), BOND);
+        contract.open_dispute(1, "Task 1 was delivered".to_string());
+        assert_eq!(contract.locked_amount, BOND);
+    }
+
+    fn dispute_status(contract: &Contract) -> DisputeStatus {
+        contract.get_project_execution_vote(1).unwrap().dispute.unwrap().status
+    }
+
+    #[test]
+    fn test_dispute_overturned_by_arbitrator() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        let vote = contract.get_project_execution_vote(1).unwrap();
+        assert_eq!(vote.status, ProjectExecutionStatus::Disputed);
+
+        call_as(&accounts(4));
+        contract.resolve_dispute(1, true);
+        assert_eq!(transfers(), vec![(accounts(1), BOND)]);
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(dispute_status(&contract), DisputeStatus::Overturned);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_dispute_upheld_by_revote() {
+        let mut contract = setup_rejected_execution(false);
+        open_dispute(&mut contract);
+        let vote = contract.get_project_execution_vote(1).unwrap();
+        assert_eq!((vote.quorum, vote.votes_against), (4, 0));
+
+        // Danny voted on the original execution vote and can vote again in the re-vote.
+        call_as(&accounts(3));
+        contract.vote_project_execution(1, false);
+        assert_eq!(dispute_status(&contract), DisputeStatus::Open);
+        call_as(&accounts(4));
+        contract.vote_project_execution(1, false);
+        assert!(transfers().is_empty());
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(dispute_status(&contract), DisputeStatus::Upheld);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Rejected
+        );
+    }
+
+    #[test]
+    fn test_dispute_expired() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        call_at(&accounts(3), contract.get_dispute_config().dispute_period + 1);
+        contract.expire_dispute(1);
+        assert_eq!(transfers(), vec![(accounts(1), BOND)]);
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(dispute_status(&contract), DisputeStatus::Expired);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Rejected
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Dispute period has not ended")]
+    fn test_expire_dispute_early() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        call_at(&accounts(3), contract.get_dispute_config().dispute_period);
+        contract.expire_dispute(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dispute is already resolved")]
+    fn test_expire_resolved_dispute() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        call_as(&accounts(4));
+        contract.resolve_dispute(1, false);
+        call_at(&accounts(3), contract.get_dispute_config().dispute_period + 1);
+        contract.expire_dispute(1);
+    }
+
+    #[test]
+    fn test_revote_quorum_saturates() {
+        let mut contract = setup_rejected_execution(false);
+        let mut config = contract.get_dispute_config();
+        config.revote_quorum_multiplier = u64::MAX;
+        call_as(&owner());
+        contract.update_dispute_config(config);
+        open_dispute(&mut contract);
+        assert_eq!(contract.get_project_execution_vote(1).unwrap().quorum, u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Appeal period has ended")]
+    fn test_dispute_after_appeal_period() {
+        let mut contract = setup_rejected_execution(true);
+        testing_env!(context(&accounts(1))
+            .attached_deposit(BOND)
+            .block_timestamp(contract.get_dispute_config().appeal_period + 1)
+            .build());
+        contract.open_dispute(1, "Task 1 was delivered".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the developer of the execution can open a dispute")]
+    fn test_dispute_by_other_account() {
+        let mut contract = setup_rejected_execution(true);
+        call_with_deposit(&accounts(2), BOND);
+        contract.open_dispute(1, "Task 1 was delivered".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must equal the appeal bond")]
+    fn test_dispute_without_bond() {
+        let mut contract = setup_rejected_execution(true);
+        call_with_deposit(&accounts(1), BOND - 1);
+        contract.open_dispute(1, "Task 1 was delivered".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Project execution has already been disputed")]
+    fn test_dispute_twice() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        call_as(&accounts(4));
+        contract.resolve_dispute(1, false);
+        open_dispute(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller has no role allowing this action")]
+    fn test_resolve_dispute_by_other_account() {
+        let mut contract = setup_rejected_execution(true);
+        open_dispute(&mut contract);
+        call_as(&accounts(1));
+        contract.resolve_dispute(1, true);
+    }
+}
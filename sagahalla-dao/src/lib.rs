@@ -1,16 +1,62 @@
 pub mod mana_structs;
-mod voting;
+mod amendments;
+pub mod bridge;
+mod conviction;
+pub mod curation;
+mod disputes;
+mod nft;
+mod permissions;
+mod plans;
+mod rewards;
+pub mod snapshots;
+mod supply;
+#[cfg(test)]
+mod test_utils;
+mod variance;
+pub mod voting;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
-use near_sdk::{env, near_bindgen, AccountId};
-use voting::{VotingModule, ProjectPlanStatus, ProjectExecutionStatus};
+use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, require, AccountId, Balance, PanicOnDefault};
+use amendments::PlanAmendment;
+use bridge::BridgeConfig;
+use conviction::ConvictionModule;
+use curation::CurationModule;
+use disputes::DisputeConfig;
+use mana_structs::{ProjectExecution, ProjectPlan, TaskFeedback};
+use nft::NftConfig;
+use permissions::{PROJECT_EXECUTION, default_roles};
+use plans::PlanConfig;
+use sputnik_policy::RolePermission;
+use rewards::RewardsModule;
+use snapshots::SnapshotModule;
+use voting::{GovernanceData, VotingModule, VotingConfig, ProjectExecutionStatus, ProjectExecutionVote, ProjectPlanStatus};
 
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     pub owner_id: AccountId,
+    pub roles: Vec<RolePermission>, // `<kind>:<action>` permissions, see `permissions`
     pub voting_module: VotingModule,
+    pub dispute_config: DisputeConfig,
+    pub project_plans: UnorderedMap<u64, ProjectPlan>,
+    pub plan_config: PlanConfig,
+    pub plan_amendments: UnorderedMap<u64, PlanAmendment>,
+    pub plan_amendment_ids: UnorderedMap<u64, Vec<u64>>, // Amendment ids per project plan, oldest first
+    pub approved_plans: LookupMap<u64, ProjectPlan>, // Last version of each plan approved by vote
+    pub last_amendment_id: u64,
+    pub project_executions: UnorderedMap<u64, ProjectExecution>,
+    pub task_feedback: LookupMap<u64, Vec<TaskFeedback>>, // Feedback per project execution
+    pub curation: CurationModule,
+    pub nft_config: NftConfig,
+    pub rewards: RewardsModule,
+    pub bridge_config: BridgeConfig,
+    pub snapshots: SnapshotModule,
+    pub supply_relayer_id: Option<AccountId>, // Account allowed to report the circulating supply
+    pub governance_relayer_id: Option<AccountId>, // Account allowed to report governance data from Aurora
+    pub conviction: ConvictionModule,
+    pub locked_amount: Balance, // NEAR held for dispute and task claim bonds, and reward pools
 }
 
 #[near_bindgen]
@@ -19,16 +65,314 @@ impl Contract {
     pub fn new(owner_id: AccountId) -> Self {
         Self {
             owner_id: owner_id.clone(),
-            voting_module: VotingModule {
-                shld_holders: UnorderedMap::new(b"s"),
-                project_contributions: UnorderedMap::new(b"p"),
-                proposals: UnorderedMap::new(b"r"),
-                project_plan_votes: UnorderedMap::new(b"v"),
-                project_execution_votes: UnorderedMap::new(b"e"),
-                governance_data: UnorderedMap::new(b"g"),
-            },
+            roles: default_roles(),
+            voting_module: VotingModule::init_voting(),
+            dispute_config: DisputeConfig::default(),
+            project_plans: UnorderedMap::new(b"l"),
+            plan_config: PlanConfig::default(),
+            plan_amendments: UnorderedMap::new(b"a"),
+            plan_amendment_ids: UnorderedMap::new(b"i"),
+            approved_plans: LookupMap::new(b"q"),
+            last_amendment_id: 0,
+            project_executions: UnorderedMap::new(b"x"),
+            task_feedback: LookupMap::new(b"f"),
+            curation: CurationModule::default(),
+            nft_config: NftConfig::new(owner_id.clone()),
+            rewards: RewardsModule::default(),
+            bridge_config: BridgeConfig::default(),
+            snapshots: SnapshotModule::default(),
+            supply_relayer_id: None,
+            governance_relayer_id: None,
+            conviction: ConvictionModule::default(),
+            locked_amount: 0,
         }
     }
 
     // Contract methods for governance and project voting can call methods in `voting_module`
-}
\ No newline at end of file
+
+    // Records an account's MANA balances and voting power. Callable by the owner or the governance
+    // relayer, which reports the balances read from Aurora.
+    pub fn update_governance_data(
+        &mut self,
+        account_id: AccountId,
+        mana_balance: U128,
+        mana_collateral_balance: U128,
+        voting_power: u64,
+        transaction_id: u64,
+    ) {
+        let reporter = env::predecessor_account_id();
+        require!(
+            reporter == self.owner_id || Some(&reporter) == self.governance_relayer_id.as_ref(),
+            "Only the owner or the governance relayer can update governance data"
+        );
+        self.voting_module.update_governance_data(
+            account_id,
+            mana_balance,
+            mana_collateral_balance,
+            voting_power,
+            transaction_id,
+        );
+    }
+
+    pub fn set_governance_relayer(&mut self, relayer_id: Option<AccountId>) {
+        self.assert_owner();
+        self.governance_relayer_id = relayer_id;
+    }
+
+    pub fn get_governance_relayer(&self) -> Option<AccountId> {
+        self.governance_relayer_id.clone()
+    }
+
+    pub fn get_governance_data(&self, account_id: AccountId) -> Option<GovernanceData> {
+        self.voting_module.get_governance_data(account_id)
+    }
+
+    // Stores a project execution of an approved plan and opens its vote, with the caller as its developer.
+    // Executions over their plan's budget also need the budget override vote to pass before approval.
+    pub fn submit_project_execution(&mut self, project_execution: ProjectExecution) {
+        self.assert_permission(PROJECT_EXECUTION, "Submit");
+        require!(
+            self.internal_get_plan_vote(project_execution.project_plan_id).status == ProjectPlanStatus::Approved,
+            "Project plan is not approved"
+        );
+        require!(
+            project_execution
+                .tasks
+                .iter()
+                .all(|task| task.project_execution_id == project_execution.id),
+            "Task executions must belong to this project execution"
+        );
+        require!(
+            project_execution.tasks.iter().map(|task| task.actual_mana_hours).sum::<u64>()
+                == project_execution.actual_mana_hours,
+            "Project execution mana hours must equal the sum of its task executions"
+        );
+        let variance = self.internal_execution_variance(&project_execution);
+        if variance.project.over_budget {
+            env::log_str(&format!(
+                "Project execution {} is over budget by {} mana hours",
+                project_execution.id, variance.project.variance
+            ));
+        }
+        self.voting_module.internal_open_execution_vote(
+            project_execution.id,
+            project_execution.project_plan_id,
+            env::predecessor_account_id(),
+            variance.project.over_budget,
+        );
+        self.project_executions.insert(&project_execution.id, &project_execution);
+    }
+
+    // Votes on a project execution with the caller's governance voting power.
+    // A vote that reaches the quorum during a dispute re-vote also resolves the dispute.
+    pub fn vote_project_execution(&mut self, project_execution_id: u64, approve: bool) {
+        self.assert_permission(PROJECT_EXECUTION, "Review");
+        let mut vote = self.internal_get_execution_vote(project_execution_id);
+        let voter = env::predecessor_account_id();
+        if let Some(approved) = self
+            .voting_module
+            .internal_tally_execution_vote(&mut vote, &voter, approve)
+        {
+            self.internal_finalize_execution_vote(&mut vote, approved);
+        }
+        self.voting_module
+            .project_execution_votes
+            .insert(&project_execution_id, &vote);
+    }
+
+    // Votes on approving an over-budget execution. If the execution vote already reached its approval
+    // quorum, passing the override approves the execution; failing it rejects the execution.
+    pub fn vote_budget_override(&mut self, project_execution_id: u64, approve: bool) {
+        self.assert_permission(PROJECT_EXECUTION, "Review");
+        let mut vote = self.internal_get_execution_vote(project_execution_id);
+        let mut budget_override = vote
+            .budget_override
+            .take()
+            .expect("Project execution is not over budget");
+        self.voting_module
+            .internal_tally_plan_vote(&mut budget_override, &env::predecessor_account_id(), approve);
+        let status = budget_override.status.clone();
+        vote.budget_override = Some(budget_override);
+
+        let open = matches!(vote.status, ProjectExecutionStatus::Active | ProjectExecutionStatus::Disputed);
+        match status {
+            ProjectPlanStatus::Approved if open && vote.votes_for >= vote.quorum => {
+                self.internal_finalize_execution_vote(&mut vote, true)
+            }
+            ProjectPlanStatus::Rejected if open => self.internal_finalize_execution_vote(&mut vote, false),
+            _ => {}
+        }
+        self.voting_module
+            .project_execution_votes
+            .insert(&project_execution_id, &vote);
+    }
+
+    pub fn get_project_execution_vote(&self, project_execution_id: u64) -> Option<ProjectExecutionVote> {
+        self.voting_module.project_execution_votes.get(&project_execution_id)
+    }
+
+    pub fn update_voting_config(&mut self, config: VotingConfig) {
+        self.assert_owner();
+        require!(config.voting_power_unit.0 > 0, "Voting power unit must be positive");
+        require!(
+            config.plan_quorum_bps <= 10_000 && config.execution_quorum_bps <= 10_000,
+            "Quorum cannot exceed 100% of the supply"
+        );
+        self.voting_module.config = config;
+    }
+
+    pub fn get_voting_config(&self) -> VotingConfig {
+        self.voting_module.config.clone()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_execution_vote(&self, project_execution_id: u64) -> ProjectExecutionVote {
+        self.voting_module
+            .project_execution_votes
+            .get(&project_execution_id)
+            .expect("No vote found for this project execution")
+    }
+
+    // Applies the outcome of an execution vote. Approval waits while the execution is over budget
+    // without a passed override; a dispute re-vote outcome also resolves the dispute.
+    // Approval completes the tasks the execution delivered.
+    pub(crate) fn internal_finalize_execution_vote(&mut self, vote: &mut ProjectExecutionVote, approved: bool) {
+        if approved && !vote.budget_cleared() {
+            env::log_str(&format!(
+                "Project execution {} reached approval but is waiting for the budget override vote",
+                vote.project_execution_id
+            ));
+            return;
+        }
+        if vote.status == ProjectExecutionStatus::Disputed {
+            self.internal_resolve_dispute(vote, approved);
+        } else {
+            vote.status = if approved {
+                ProjectExecutionStatus::Approved
+            } else {
+                ProjectExecutionStatus::Rejected
+            };
+            vote.resolved_at = Some(env::block_timestamp());
+            if approved {
+                self.internal_complete_tasks(vote.project_execution_id);
+            }
+        }
+    }
+
+    pub(crate) fn assert_owner(&self) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only the owner can call this method"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    // Approved plan 1 with bob's single task, and execution 1 of it submitted by bob.
+    // Charlie (1) and danny (2) can review it, with an execution quorum of 2.
+    fn setup_execution(actual_mana_hours: u64) -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_voting_config();
+        config.execution_quorum = 2;
+        call_as(&owner());
+        contract.update_voting_config(config);
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        set_voting_power(&mut contract, &accounts(3), 2);
+
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, actual_mana_hours)]));
+        contract
+    }
+
+    #[test]
+    fn test_execution_approved_at_quorum() {
+        let mut contract = setup_execution(10);
+        let vote = contract.get_project_execution_vote(1).unwrap();
+        assert_eq!((vote.developer_id, vote.quorum), (accounts(1), 2));
+        assert!(!vote.over_budget);
+
+        call_as(&accounts(2));
+        contract.vote_project_execution(1, true);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Active
+        );
+        call_as(&accounts(3));
+        contract.vote_project_execution(1, true);
+        let vote = contract.get_project_execution_vote(1).unwrap();
+        assert_eq!(vote.status, ProjectExecutionStatus::Approved);
+        assert!(vote.resolved_at.is_some());
+    }
+
+    #[test]
+    fn test_execution_rejected_at_quorum() {
+        let mut contract = setup_execution(10);
+        call_as(&accounts(3));
+        contract.vote_project_execution(1, false);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Rejected
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Project execution is not open for voting")]
+    fn test_vote_on_resolved_execution() {
+        let mut contract = setup_execution(10);
+        call_as(&accounts(3));
+        contract.vote_project_execution(1, true);
+        call_as(&accounts(2));
+        contract.vote_project_execution(1, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Project plan is not approved")]
+    fn test_submit_execution_of_unapproved_plan() {
+        let mut contract = setup_contract();
+        call_as(&accounts(1));
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 1_000)]));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Project execution has already been submitted")]
+    fn test_submit_execution_twice() {
+        let mut contract = setup_execution(10);
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10)]));
+    }
+
+    #[test]
+    fn test_governance_relayer() {
+        let mut contract = setup_contract();
+        contract.set_governance_relayer(Some(accounts(4)));
+        call_as(&accounts(4));
+        contract.update_governance_data(accounts(1), U128(500), U128(100), 5, 1);
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(1)), 5);
+        assert!(contract.get_governance_data(accounts(1)).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or the governance relayer can update governance data")]
+    fn test_governance_data_reporter() {
+        let mut contract = setup_contract();
+        call_as(&accounts(1));
+        contract.update_governance_data(accounts(1), U128(500), U128(100), 5, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Quorum cannot exceed 100% of the supply")]
+    fn test_update_voting_config_quorum_bps() {
+        let mut contract = setup_contract();
+        let mut config = contract.get_voting_config();
+        config.plan_quorum_bps = 10_001;
+        contract.update_voting_config(config);
+    }
+}
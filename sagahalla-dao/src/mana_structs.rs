@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Serialize, Deserialize};
 use near_sdk::json_types::U128;
 use near_sdk::AccountId;
 
+use crate::bridge::TreasuryProposal;
+
 // Enum for tracking the lifecycle of a task from planning through execution
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -99,15 +101,44 @@ pub struct ProjectPlan {
     pub voting_power: Option<String>,
     pub created_at: String,
     pub updated_at: Option<String>,
-    pub developers: HashMap<String, DeveloperProjectPlan>,
+    pub developers: HashMap<AccountId, DeveloperProjectPlan>,
     pub proposal: Option<Proposal>,
+    #[serde(default)]
+    pub version: u64, // Set on-chain: 1 on submission, incremented by each applied amendment
+    #[serde(default)]
+    pub treasury_proposal: Option<TreasuryProposal>, // Set on-chain once the plan is approved
+}
+
+impl ProjectPlan {
+    // All tasks of the plan across developers, sub-projects and epics
+    pub fn tasks(&self) -> impl Iterator<Item = &TaskPlan> {
+        self.developers
+            .values()
+            .flat_map(|developer| developer.sub_projects.iter())
+            .flat_map(|sub_project| sub_project.epics.iter())
+            .flat_map(|epic| epic.tasks.iter())
+    }
+
+    pub fn task_mut(&mut self, task_plan_id: u64) -> Option<&mut TaskPlan> {
+        self.developers
+            .values_mut()
+            .flat_map(|developer| developer.sub_projects.iter_mut())
+            .flat_map(|sub_project| sub_project.epics.iter_mut())
+            .flat_map(|epic| epic.tasks.iter_mut())
+            .find(|task| task.id == task_plan_id)
+    }
+
+    pub fn has_unique_task_ids(&self) -> bool {
+        let mut task_ids = HashSet::new();
+        self.tasks().all(|task| task_ids.insert(task.id))
+    }
 }
 
 // Developer-specific project plan details
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub struct DeveloperProjectPlan {
-    pub developer_name: String,
+    pub developer_id: AccountId,
     pub mana_hours_budgeted: u64,
     pub mana_token_allocated: U128,
     pub sub_projects: Vec<SubProjectPlan>,
@@ -143,6 +174,23 @@ pub struct TaskPlan {
     pub estimated_mana_hours: u64,
     pub roles_mana_hours: Vec<TaskRoleManaHours>,
     pub status: TaskStatus, // Unified status field for the task's lifecycle
+    pub claim: Option<TaskClaim>, // Account currently working on the task, if claimed
+}
+
+// Claim of a TaskPlan by an account, modelled on sputnikdao2 bounty claims
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TaskClaim {
+    pub claimer_id: AccountId,
+    pub bond: U128,
+    pub start_time: u64,
+    pub deadline: u64, // Nanoseconds from start_time
+}
+
+impl TaskClaim {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.start_time + self.deadline
+    }
 }
 
 // Main struct for Project Execution, linked to ProjectPlan by project_plan_id
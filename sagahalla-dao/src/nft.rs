@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use near_contract_standards::non_fungible_token::metadata::TokenMetadata;
+use near_contract_standards::non_fungible_token::TokenId;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, ext_contract, near_bindgen, require, AccountId, CryptoHash, Gas, Promise, PromiseResult};
+
+use crate::curation::WorkStatus;
+use crate::*;
+
+const GAS_FOR_NFT_MINT: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_MINT_CALLBACK: Gas = Gas(5_000_000_000_000);
+
+// `ext_contract` adds the receiver, deposit and gas to the arguments of `nft_mint`
+#[allow(clippy::too_many_arguments)]
+mod ext {
+    use super::*;
+
+    #[ext_contract(ext_nft)]
+    pub trait ExtNft {
+        fn nft_mint(
+            &mut self,
+            token_id: TokenId,
+            receiver_id: AccountId,
+            token_metadata: TokenMetadata,
+            royalty: HashMap<AccountId, u32>,
+            refund_id: Option<AccountId>,
+        );
+    }
+}
+
+use ext::ext_nft;
+
+#[ext_contract(ext_self)]
+pub trait ExtSelf {
+    fn on_curated_work_minted(&mut self, work_id: u64, payer_id: AccountId, amount: U128) -> bool;
+}
+
+// NFT collection that curated works are minted to, and the royalty split applied to every token
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftConfig {
+    pub nft_contract_id: Option<AccountId>,
+    pub treasury_id: AccountId,
+    pub creator_royalty_bps: u32,
+    pub treasury_royalty_bps: u32,
+}
+
+impl NftConfig {
+    pub fn new(treasury_id: AccountId) -> Self {
+        Self {
+            nft_contract_id: None,
+            treasury_id,
+            creator_royalty_bps: 500,
+            treasury_royalty_bps: 250,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Mints an accepted work of the curated collection to its creator. Only the creator or the owner
+    // can mint. Attached deposit pays for the token storage on the NFT contract, which refunds the rest
+    // to the caller. All of it is refunded if minting fails.
+    #[payable]
+    pub fn mint_curated_work(&mut self, work_id: u64) -> Promise {
+        let nft_contract_id = self
+            .nft_config
+            .nft_contract_id
+            .clone()
+            .expect("NFT contract is not configured");
+        let work = self.curation.internal_get_work(work_id);
+        require!(work.status == WorkStatus::Accepted, "Only accepted works can be minted");
+        let payer_id = env::predecessor_account_id();
+        require!(
+            payer_id == work.creator_id || payer_id == self.owner_id,
+            "Only the creator or the owner can mint this work"
+        );
+
+        let content_hash: CryptoHash = work.content_hash.into();
+        let token_metadata = TokenMetadata {
+            title: Some(work.metadata.title),
+            description: work.metadata.description,
+            media_hash: work.metadata.media.as_ref().map(|_| Base64VecU8(content_hash.to_vec())),
+            media: work.metadata.media,
+            copies: Some(1),
+            issued_at: Some(env::block_timestamp().to_string()),
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        };
+        let mut royalty = HashMap::new();
+        royalty.insert(work.creator_id.clone(), self.nft_config.creator_royalty_bps);
+        *royalty.entry(self.nft_config.treasury_id.clone()).or_insert(0) +=
+            self.nft_config.treasury_royalty_bps;
+
+        ext_nft::nft_mint(
+            work_id.to_string(),
+            work.creator_id,
+            token_metadata,
+            royalty,
+            Some(payer_id.clone()),
+            nft_contract_id,
+            env::attached_deposit(),
+            GAS_FOR_NFT_MINT,
+        )
+        .then(ext_self::on_curated_work_minted(
+            work_id,
+            payer_id,
+            U128(env::attached_deposit()),
+            env::current_account_id(),
+            0,
+            GAS_FOR_MINT_CALLBACK,
+        ))
+    }
+
+    // Refunds the storage deposit to the caller of `mint_curated_work` if minting failed
+    #[private]
+    pub fn on_curated_work_minted(&mut self, work_id: u64, payer_id: AccountId, amount: U128) -> bool {
+        require!(env::promise_results_count() == 1, "Unexpected callback promises");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => true,
+            _ => {
+                if amount.0 > 0 {
+                    Promise::new(payer_id).transfer(amount.0);
+                }
+                env::log_str(&format!("Minting work {} failed, deposit refunded", work_id));
+                false
+            }
+        }
+    }
+
+    pub fn update_nft_config(&mut self, config: NftConfig) {
+        self.assert_owner();
+        require!(
+            config.creator_royalty_bps + config.treasury_royalty_bps <= 10_000,
+            "Royalties cannot exceed 100%"
+        );
+        self.nft_config = config;
+    }
+
+    pub fn get_nft_config(&self) -> NftConfig {
+        self.nft_config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json::{self, json, Value};
+    use near_sdk::test_utils::accounts;
+
+    use crate::curation::{WorkKind, WorkMetadata};
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn nft() -> AccountId {
+        "nft.sagahalla.near".parse().unwrap()
+    }
+
+    // Bob's work 0 in a round closing at 100, accepted by charlie's vote if `accept`
+    fn setup_work(accept: bool) -> Contract {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(2), 1);
+        call_as(&owner());
+        contract.update_nft_config(NftConfig {
+            nft_contract_id: Some(nft()),
+            ..NftConfig::new(dao())
+        });
+        contract.open_curation_round("Round 1".to_string(), 100, None);
+        call_as(&accounts(1));
+        let metadata = WorkMetadata {
+            title: "Saga".to_string(),
+            description: None,
+            kind: WorkKind::Art,
+            media: Some("ipfs://saga".to_string()),
+        };
+        contract.submit_work(0, [1; 32].into(), metadata);
+        call_as(&accounts(2));
+        contract.vote_work(0, accept, None);
+        call_at(&accounts(2), 101);
+        contract.close_curation_round(0);
+        contract
+    }
+
+    #[test]
+    fn test_mint_curated_work() {
+        let mut contract = setup_work(true);
+        call_with_deposit(&accounts(1), 10_000);
+        contract.mint_curated_work(0);
+
+        let calls = function_calls();
+        let (receiver_id, method_name, args, deposit) = &calls[0];
+        assert_eq!((receiver_id, method_name.as_str(), *deposit), (&nft(), "nft_mint", 10_000));
+        let args: Value = serde_json::from_slice(args).unwrap();
+        assert_eq!(args["token_id"], json!("0"));
+        assert_eq!(args["receiver_id"], json!(accounts(1)));
+        assert_eq!(args["token_metadata"]["title"], json!("Saga"));
+        assert_eq!(args["royalty"], json!({ accounts(1).to_string(): 500, dao().to_string(): 250 }));
+        assert_eq!(args["refund_id"], json!(accounts(1)));
+        assert_eq!(calls[1].1, "on_curated_work_minted");
+    }
+
+    #[test]
+    fn test_mint_failure_refunds_deposit() {
+        let mut contract = setup_work(true);
+        call_with_deposit(&owner(), 10_000);
+        contract.mint_curated_work(0);
+        callback_with(PromiseResult::Failed);
+        assert!(!contract.on_curated_work_minted(0, owner(), U128(10_000)));
+        assert_eq!(transfers(), vec![(owner(), 10_000)]);
+
+        callback_with(PromiseResult::Successful(vec![]));
+        assert!(contract.on_curated_work_minted(0, owner(), U128(10_000)));
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the creator or the owner can mint this work")]
+    fn test_mint_by_other_account() {
+        let mut contract = setup_work(true);
+        call_with_deposit(&accounts(2), 10_000);
+        contract.mint_curated_work(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only accepted works can be minted")]
+    fn test_mint_rejected_work() {
+        let mut contract = setup_work(false);
+        call_with_deposit(&accounts(1), 10_000);
+        contract.mint_curated_work(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalties cannot exceed 100%")]
+    fn test_royalties_over_100_percent() {
+        let mut contract = setup_contract();
+        contract.update_nft_config(NftConfig {
+            creator_royalty_bps: 9_000,
+            treasury_royalty_bps: 1_001,
+            ..NftConfig::new(dao())
+        });
+    }
+}
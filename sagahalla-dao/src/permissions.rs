@@ -0,0 +1,210 @@
+use near_sdk::{env, near_bindgen, require, AccountId};
+use sputnik_policy::{RoleKind, RolePermission, UserInfo};
+
+use crate::mana_structs::TaskFeedback;
+use crate::*;
+
+// Kinds of the `<kind>:<action>` permissions, with the actions each kind supports
+pub const PROJECT_PLAN: &str = "project_plan"; // Submit, Vote
+pub const PROJECT_EXECUTION: &str = "project_execution"; // Submit, Review
+pub const TASK_FEEDBACK: &str = "task_feedback"; // Record
+pub const CURATION: &str = "curation"; // Submit, Vote
+pub const FUNDING: &str = "funding"; // Submit, Stake
+pub const SNAPSHOT: &str = "snapshot"; // Vote
+pub const DISPUTE: &str = "dispute"; // Arbitrate
+
+// Everyone can take part in every action, as before roles existed
+pub fn default_roles() -> Vec<RolePermission> {
+    vec![RolePermission {
+        name: "all".to_string(),
+        kind: RoleKind::Everyone,
+        permissions: vec![
+            "project_plan:Submit",
+            "project_plan:Vote",
+            "project_execution:Submit",
+            "project_execution:Review",
+            "task_feedback:Record",
+            "curation:Submit",
+            "curation:Vote",
+            "funding:Submit",
+            "funding:Stake",
+            "snapshot:Vote",
+            "dispute:Arbitrate",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    }]
+}
+
+impl Contract {
+    fn internal_user_info(&self, account_id: AccountId) -> UserInfo {
+        let amount = self.voting_module.internal_voting_power(&account_id) as u128;
+        UserInfo { account_id, amount }
+    }
+
+    // Panics unless one of the caller's roles allows `action` on `kind`.
+    // Member roles match on the caller's governance voting power.
+    pub(crate) fn assert_permission(&self, kind: &str, action: &str) {
+        let user = self.internal_user_info(env::predecessor_account_id());
+        require!(
+            sputnik_policy::can_execute_action(&self.roles, user, kind, action).1,
+            "Caller has no role allowing this action"
+        );
+    }
+
+    fn internal_get_role_mut(&mut self, role: &str) -> &mut RolePermission {
+        self.roles
+            .iter_mut()
+            .find(|role_permission| role_permission.name == role)
+            .expect("No role found with this name")
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Adds a role, or replaces the role with the same name
+    pub fn add_or_update_role(&mut self, role: RolePermission) {
+        self.assert_owner();
+        match self.roles.iter_mut().find(|existing| existing.name == role.name) {
+            Some(existing) => *existing = role,
+            None => self.roles.push(role),
+        }
+    }
+
+    pub fn remove_role(&mut self, role: String) {
+        self.assert_owner();
+        let len = self.roles.len();
+        self.roles.retain(|existing| existing.name != role);
+        require!(self.roles.len() < len, "No role found with this name");
+    }
+
+    pub fn add_member_to_role(&mut self, role: String, member_id: AccountId) {
+        self.assert_owner();
+        require!(
+            self.internal_get_role_mut(&role).kind.add_member_to_group(&member_id),
+            "Role is not a group"
+        );
+    }
+
+    pub fn remove_member_from_role(&mut self, role: String, member_id: AccountId) {
+        self.assert_owner();
+        require!(
+            self.internal_get_role_mut(&role).kind.remove_member_from_group(&member_id),
+            "Role is not a group"
+        );
+    }
+
+    pub fn get_roles(&self) -> Vec<RolePermission> {
+        self.roles.clone()
+    }
+
+    // Names of the roles allowing `account_id` to perform `action` on `kind`
+    pub fn get_allowed_roles(&self, account_id: AccountId, kind: String, action: String) -> Vec<String> {
+        let user = self.internal_user_info(account_id);
+        sputnik_policy::can_execute_action(&self.roles, user, &kind, &action).0
+    }
+
+    // Records feedback on a task of a submitted project execution
+    pub fn record_task_feedback(&mut self, project_execution_id: u64, feedback: TaskFeedback) {
+        self.assert_permission(TASK_FEEDBACK, "Record");
+        let execution = self
+            .project_executions
+            .get(&project_execution_id)
+            .expect("No project execution found with this id");
+        require!(
+            execution.tasks.iter().any(|task| task.id == feedback.task_execution_id),
+            "Task execution does not belong to this project execution"
+        );
+        let mut feedbacks = self.task_feedback.get(&project_execution_id).unwrap_or_default();
+        feedbacks.push(feedback);
+        self.task_feedback.insert(&project_execution_id, &feedbacks);
+    }
+
+    // Feedback recorded on the tasks of a project execution, oldest first
+    pub fn get_task_feedback(&self, project_execution_id: u64) -> Vec<TaskFeedback> {
+        self.task_feedback.get(&project_execution_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+    use sputnik_policy::RoleKind;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    // Replaces the default roles with a "council" group of bob allowed to vote on plans
+    fn setup_council() -> Contract {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(1), 1);
+        set_voting_power(&mut contract, &accounts(2), 1);
+        contract.add_or_update_role(RolePermission {
+            name: "council".to_string(),
+            kind: RoleKind::Group(vec![accounts(1)].into_iter().collect()),
+            permissions: vec!["project_plan:Vote".to_string()].into_iter().collect(),
+        });
+        contract.remove_role("all".to_string());
+        contract
+    }
+
+    #[test]
+    fn test_group_role() {
+        let mut contract = setup_council();
+        let allowed = |contract: &Contract, account_index| {
+            contract.get_allowed_roles(accounts(account_index), PROJECT_PLAN.to_string(), "Vote".to_string())
+        };
+        assert_eq!(allowed(&contract, 1), vec!["council".to_string()]);
+        assert!(allowed(&contract, 2).is_empty());
+
+        contract.add_member_to_role("council".to_string(), accounts(2));
+        assert_eq!(allowed(&contract, 2), vec!["council".to_string()]);
+        contract.remove_member_from_role("council".to_string(), accounts(1));
+        assert!(allowed(&contract, 1).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller has no role allowing this action")]
+    fn test_submit_without_role() {
+        let mut contract = setup_council();
+        call_as(&accounts(1));
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 1_000)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller has no role allowing this action")]
+    fn test_vote_amendment_without_role() {
+        let mut contract = setup_council();
+        call_as(&accounts(2));
+        contract.vote_plan_amendment(1, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Role is not a group")]
+    fn test_add_member_to_everyone_role() {
+        let mut contract = setup_contract();
+        contract.add_member_to_role("all".to_string(), accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Task execution does not belong to this project execution")]
+    fn test_feedback_on_other_task() {
+        let mut contract = setup_contract();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10)]));
+        contract.record_task_feedback(
+            1,
+            TaskFeedback {
+                id: 1,
+                task_execution_id: 2,
+                user_id: 1,
+                feedback: "Well done".to_string(),
+                rating: 5,
+                created_at: "2024-01-02".to_string(),
+            },
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Promise};
+
+use crate::mana_structs::{ProjectPlan, TaskClaim, TaskPlan, TaskStatus};
+use crate::permissions::PROJECT_PLAN;
+use crate::voting::{ProjectPlanStatus, ProjectPlanVote};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlanConfig {
+    pub task_claim_bond: U128, // Zero allows claiming tasks without a bond
+    pub max_task_deadline: u64, // Longest deadline in nanoseconds a claimer can pick
+    pub amendment_threshold_bps: u64, // Amendments changing the plan by more than this need re-approval
+    pub over_budget_tolerance_bps: u64, // Executions above their estimate by more than this are over budget
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        Self {
+            task_claim_bond: U128(0),
+            max_task_deadline: 1_000_000_000 * 60 * 60 * 24 * 30,
+            amendment_threshold_bps: 1_000,
+            over_budget_tolerance_bps: 0,
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_plan(&self, project_plan_id: u64) -> ProjectPlan {
+        self.project_plans
+            .get(&project_plan_id)
+            .expect("No project plan found with this id")
+    }
+
+    pub(crate) fn internal_get_plan_vote(&self, project_plan_id: u64) -> ProjectPlanVote {
+        self.voting_module
+            .project_plan_votes
+            .get(&project_plan_id)
+            .expect("No vote found for this project plan")
+    }
+
+    // Deadline of a new task claim, the longest one allowed if none is given
+    fn internal_task_deadline(&self, deadline: Option<u64>) -> u64 {
+        let deadline = deadline.unwrap_or(self.plan_config.max_task_deadline);
+        require!(deadline <= self.plan_config.max_task_deadline, "Deadline is too long");
+        deadline
+    }
+
+    // Drops the claim on a task. The bond goes back to the claimer unless the claim expired,
+    // in which case it is forfeited to the DAO.
+    pub(crate) fn internal_release_claim(&mut self, task: &mut TaskPlan) {
+        let claim = task.claim.take().expect("Task is not claimed");
+        self.locked_amount -= claim.bond.0;
+        if !claim.is_expired(env::block_timestamp()) && claim.bond.0 > 0 {
+            Promise::new(claim.claimer_id.clone()).transfer(claim.bond.0);
+        }
+        task.status = TaskStatus::NotStarted;
+    }
+
+    // Marks the tasks an approved execution completed as completed. Their claims end and the bonds
+    // go back to the claimers, expired or not, since the work was delivered.
+    pub(crate) fn internal_complete_tasks(&mut self, project_execution_id: u64) {
+        let execution = self
+            .project_executions
+            .get(&project_execution_id)
+            .expect("No project execution found with this id");
+        let mut plan = self.internal_get_plan(execution.project_plan_id);
+        for task_execution in execution.tasks.iter() {
+            if !matches!(task_execution.status, TaskStatus::Completed) {
+                continue;
+            }
+            let task = match plan.task_mut(task_execution.task_plan_id) {
+                Some(task) => task,
+                None => continue,
+            };
+            if let Some(claim) = task.claim.take() {
+                self.locked_amount -= claim.bond.0;
+                if claim.bond.0 > 0 {
+                    Promise::new(claim.claimer_id).transfer(claim.bond.0);
+                }
+            }
+            task.status = TaskStatus::Completed;
+        }
+        self.project_plans.insert(&plan.id, &plan);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn submit_project_plan(&mut self, project_plan: ProjectPlan) {
+        self.assert_permission(PROJECT_PLAN, "Submit");
+        require!(
+            self.project_plans.get(&project_plan.id).is_none(),
+            "Project plan with this id already exists"
+        );
+        for (developer_id, developer) in project_plan.developers.iter() {
+            require!(
+                *developer_id == developer.developer_id,
+                "Developer plan must be keyed by its developer_id"
+            );
+        }
+        require!(project_plan.has_unique_task_ids(), "Task ids must be unique across the plan");
+        require!(
+            project_plan.tasks().all(|task| task.claim.is_none()),
+            "Tasks must be claimed after the plan is submitted"
+        );
+        require!(
+            project_plan.treasury_proposal.is_none(),
+            "Treasury proposal is filed once the plan is approved"
+        );
+        let mut project_plan = project_plan;
+        project_plan.version = 1;
+        let vote = self.voting_module.internal_new_plan_vote(project_plan.id);
+        self.voting_module.project_plan_votes.insert(&project_plan.id, &vote);
+        self.project_plans.insert(&project_plan.id, &project_plan);
+    }
+
+    // Votes on a submitted project plan with the caller's governance voting power.
+    // Once approved, the plan's token allocations are proposed on the treasury sputnikdao2.
+    pub fn vote_project_plan(&mut self, project_plan_id: u64, approve: bool) {
+        self.assert_permission(PROJECT_PLAN, "Vote");
+        let mut vote = self.internal_get_plan_vote(project_plan_id);
+        self.voting_module
+            .internal_tally_plan_vote(&mut vote, &env::predecessor_account_id(), approve);
+        self.voting_module.project_plan_votes.insert(&project_plan_id, &vote);
+        if vote.status == ProjectPlanStatus::Approved {
+            let mut plan = self.internal_get_plan(project_plan_id);
+            self.internal_file_treasury_proposal(&mut plan);
+            self.project_plans.insert(&project_plan_id, &plan);
+            self.approved_plans.insert(&project_plan_id, &plan);
+        }
+    }
+
+    pub fn get_project_plan_vote(&self, project_plan_id: u64) -> Option<ProjectPlanVote> {
+        self.voting_module.project_plan_votes.get(&project_plan_id)
+    }
+
+    pub fn get_project_plan(&self, project_plan_id: u64) -> Option<ProjectPlan> {
+        self.project_plans.get(&project_plan_id)
+    }
+
+    // Claims a task of an approved plan for the caller. Without a deadline the claim expires after
+    // the longest one allowed. The configured task claim bond must be attached.
+    #[payable]
+    pub fn claim_task(&mut self, project_plan_id: u64, task_plan_id: u64, deadline: Option<u64>) {
+        let mut plan = self.internal_get_plan(project_plan_id);
+        require!(
+            self.internal_get_plan_vote(project_plan_id).status == ProjectPlanStatus::Approved,
+            "Project plan is not approved"
+        );
+        let bond = self.plan_config.task_claim_bond;
+        require!(env::attached_deposit() == bond.0, "Attached deposit must equal the task claim bond");
+        let deadline = self.internal_task_deadline(deadline);
+
+        let task = plan.task_mut(task_plan_id).expect("No task found with this id");
+        require!(task.claim.is_none(), "Task is already claimed");
+        require!(
+            matches!(task.status, TaskStatus::Planned | TaskStatus::NotStarted),
+            "Task is not open for claiming"
+        );
+        task.claim = Some(TaskClaim {
+            claimer_id: env::predecessor_account_id(),
+            bond,
+            start_time: env::block_timestamp(),
+            deadline,
+        });
+        task.status = TaskStatus::InProgress;
+        self.locked_amount += bond.0;
+        self.project_plans.insert(&project_plan_id, &plan);
+    }
+
+    // Releases a task claim. The claimer can give up at any time; anyone else only once the deadline passed.
+    pub fn release_task(&mut self, project_plan_id: u64, task_plan_id: u64) {
+        let mut plan = self.internal_get_plan(project_plan_id);
+        let task = plan.task_mut(task_plan_id).expect("No task found with this id");
+        let claim = task.claim.as_ref().expect("Task is not claimed");
+        require!(
+            env::predecessor_account_id() == claim.claimer_id
+                || claim.is_expired(env::block_timestamp()),
+            "Only the claimer can release the task before its deadline"
+        );
+        self.internal_release_claim(task);
+        self.project_plans.insert(&project_plan_id, &plan);
+    }
+
+    // Owner assigns a task that is unclaimed or whose claim has expired to `account_id`, without a bond.
+    // Without a deadline the assignment expires after the longest one allowed.
+    pub fn reassign_task(
+        &mut self,
+        project_plan_id: u64,
+        task_plan_id: u64,
+        account_id: AccountId,
+        deadline: Option<u64>,
+    ) {
+        self.assert_owner();
+        let deadline = self.internal_task_deadline(deadline);
+        let mut plan = self.internal_get_plan(project_plan_id);
+        let task = plan.task_mut(task_plan_id).expect("No task found with this id");
+        require!(!matches!(task.status, TaskStatus::Completed), "Task is already completed");
+        if let Some(claim) = task.claim.as_ref() {
+            require!(claim.is_expired(env::block_timestamp()), "Task claim has not expired");
+            self.internal_release_claim(task);
+        }
+        task.claim = Some(TaskClaim {
+            claimer_id: account_id,
+            bond: U128(0),
+            start_time: env::block_timestamp(),
+            deadline,
+        });
+        task.status = TaskStatus::InProgress;
+        self.project_plans.insert(&project_plan_id, &plan);
+    }
+
+    pub fn update_plan_config(&mut self, config: PlanConfig) {
+        self.assert_owner();
+        self.plan_config = config;
+    }
+
+    pub fn get_plan_config(&self) -> PlanConfig {
+        self.plan_config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    // Plan 1 by bob waiting for votes, with a quorum of 5 and voters charlie (3), danny (2) and eugene (4)
+    fn setup_plan_vote() -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_voting_config();
+        config.plan_quorum = 5;
+        call_as(&owner());
+        contract.update_voting_config(config);
+        set_voting_power(&mut contract, &accounts(2), 3);
+        set_voting_power(&mut contract, &accounts(3), 2);
+        set_voting_power(&mut contract, &accounts(4), 4);
+
+        call_as(&accounts(1));
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 1_000)]));
+        contract
+    }
+
+    // Approved plan 1 by bob with a task claim bond of 50 and deadlines up to 1000 nanoseconds
+    fn setup_task_claims() -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_plan_config();
+        config.task_claim_bond = U128(50);
+        config.max_task_deadline = 1_000;
+        call_as(&owner());
+        contract.update_plan_config(config);
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000)]);
+        contract
+    }
+
+    #[test]
+    fn test_plan_approved_at_quorum() {
+        let mut contract = setup_plan_vote();
+        assert_eq!(contract.get_project_plan(1).unwrap().version, 1);
+
+        call_as(&accounts(2));
+        contract.vote_project_plan(1, true);
+        let vote = contract.get_project_plan_vote(1).unwrap();
+        assert_eq!((vote.votes_for, vote.status), (3, ProjectPlanStatus::Active));
+
+        call_as(&accounts(4));
+        contract.vote_project_plan(1, false);
+        call_as(&accounts(3));
+        contract.vote_project_plan(1, true);
+        let vote = contract.get_project_plan_vote(1).unwrap();
+        assert_eq!((vote.votes_for, vote.votes_against), (5, 4));
+        assert_eq!(vote.status, ProjectPlanStatus::Approved);
+        // Without a treasury DAO configured no payout proposal is filed.
+        assert!(contract.get_project_plan(1).unwrap().treasury_proposal.is_none());
+    }
+
+    #[test]
+    fn test_plan_rejected_at_quorum() {
+        let mut contract = setup_plan_vote();
+        call_as(&accounts(2));
+        contract.vote_project_plan(1, false);
+        call_as(&accounts(3));
+        contract.vote_project_plan(1, false);
+        assert_eq!(
+            contract.get_project_plan_vote(1).unwrap().status,
+            ProjectPlanStatus::Rejected
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Project plan is not open for voting")]
+    fn test_vote_on_resolved_plan() {
+        let mut contract = setup_plan_vote();
+        for voter in [accounts(2), accounts(3), accounts(4)].iter() {
+            call_as(voter);
+            contract.vote_project_plan(1, true);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Voter has already voted")]
+    fn test_vote_twice() {
+        let mut contract = setup_plan_vote();
+        call_as(&accounts(2));
+        contract.vote_project_plan(1, true);
+        contract.vote_project_plan(1, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Voter has no voting power")]
+    fn test_vote_without_voting_power() {
+        let mut contract = setup_plan_vote();
+        call_as(&accounts(5));
+        contract.vote_project_plan(1, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Project plan with this id already exists")]
+    fn test_submit_plan_twice() {
+        let mut contract = setup_plan_vote();
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 1_000)]));
+    }
+
+    #[test]
+    fn test_task_claim_bond_returned() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, None);
+        assert_eq!(contract.locked_amount, 50);
+        call_as(&accounts(3));
+        contract.release_task(1, 1);
+        assert_eq!(transfers(), vec![(accounts(3), 50)]);
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    #[test]
+    fn test_expired_task_claim_forfeited() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, Some(100));
+
+        // Once the deadline passed anyone can release the claim, and the bond stays with the DAO.
+        call_at(&accounts(4), 101);
+        contract.release_task(1, 1);
+        assert!(transfers().is_empty());
+        assert_eq!(contract.locked_amount, 0);
+        let plan = contract.get_project_plan(1).unwrap();
+        let task = plan.tasks().next().unwrap();
+        assert!(task.claim.is_none());
+        assert!(matches!(task.status, TaskStatus::NotStarted));
+    }
+
+    #[test]
+    fn test_reassign_expired_task() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, Some(100));
+        call_at(&owner(), 101);
+        contract.reassign_task(1, 1, accounts(4), None);
+        let plan = contract.get_project_plan(1).unwrap();
+        let claim = plan.tasks().next().unwrap().claim.clone().unwrap();
+        assert_eq!((claim.claimer_id, claim.bond), (accounts(4), U128(0)));
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    #[test]
+    fn test_approved_execution_completes_tasks() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, Some(100));
+
+        // The claim expired, but the task was delivered before the execution was approved.
+        call_at(&accounts(1), 101);
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10)]));
+        call_as(&accounts(2));
+        contract.vote_project_execution(1, true);
+        assert_eq!(transfers(), vec![(accounts(3), 50)]);
+        assert_eq!(contract.locked_amount, 0);
+        let plan = contract.get_project_plan(1).unwrap();
+        let task = plan.tasks().next().unwrap();
+        assert!(task.claim.is_none());
+        assert!(matches!(task.status, TaskStatus::Completed));
+    }
+
+    #[test]
+    fn test_task_claim_without_deadline_expires() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, None);
+        let plan = contract.get_project_plan(1).unwrap();
+        assert_eq!(plan.tasks().next().unwrap().claim.as_ref().unwrap().deadline, 1_000);
+
+        call_at(&accounts(4), 1_001);
+        contract.release_task(1, 1);
+        assert!(transfers().is_empty());
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Project plan is not approved")]
+    fn test_claim_task_of_unapproved_plan() {
+        let mut contract = setup_plan_vote();
+        call_as(&accounts(3));
+        contract.claim_task(1, 1, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Task ids must be unique across the plan")]
+    fn test_submit_plan_with_duplicate_task_ids() {
+        let mut contract = setup_contract();
+        let mut plan = project_plan(1, &[(accounts(1), 1_000), (accounts(2), 1_000)]);
+        let developer = plan.developers.get_mut(&accounts(2)).unwrap();
+        developer.sub_projects[0].epics[0].tasks[0].id = 1;
+        call_as(&accounts(1));
+        contract.submit_project_plan(plan);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadline is too long")]
+    fn test_reassign_task_deadline_too_long() {
+        let mut contract = setup_task_claims();
+        call_as(&owner());
+        contract.reassign_task(1, 1, accounts(4), Some(1_001));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the claimer can release the task before its deadline")]
+    fn test_release_task_before_deadline() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, Some(100));
+        call_at(&accounts(4), 100);
+        contract.release_task(1, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadline is too long")]
+    fn test_claim_task_deadline_too_long() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, Some(1_001));
+    }
+
+    #[test]
+    #[should_panic(expected = "Task is already claimed")]
+    fn test_claim_task_twice() {
+        let mut contract = setup_task_claims();
+        call_with_deposit(&accounts(3), 50);
+        contract.claim_task(1, 1, None);
+        call_with_deposit(&accounts(4), 50);
+        contract.claim_task(1, 1, None);
+    }
+}
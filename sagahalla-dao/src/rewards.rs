@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Balance, Promise};
+
+use crate::curation::{CurationRoundStatus, WorkStatus};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RewardEpochStatus {
+    Funded,      // Waiting for its curation rounds to close
+    Distributed, // Pools were credited to creators and curators
+}
+
+// Reward pools for the works curated in a set of curation rounds
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardEpoch {
+    pub id: u64,
+    pub round_ids: Vec<u64>,
+    pub creator_pool: U128, // Split between creators of accepted works, pro-rata to the votes in favour of their works
+    pub curator_pool: U128, // Split between curators, pro-rata to their votes that matched the outcome
+    pub status: RewardEpochStatus,
+    pub created_at: u64,
+    pub distributed_at: Option<u64>,
+}
+
+// Reward epochs and the rewards each account can claim
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RewardsModule {
+    pub epochs: UnorderedMap<u64, RewardEpoch>,
+    pub round_epochs: LookupMap<u64, u64>, // Epoch rewarding each curation round
+    pub balances: LookupMap<AccountId, Balance>, // Distributed rewards not claimed yet
+    pub last_epoch_id: u64,
+}
+
+impl Default for RewardsModule {
+    fn default() -> Self {
+        Self {
+            epochs: UnorderedMap::new(b"o"),
+            round_epochs: LookupMap::new(b"d"),
+            balances: LookupMap::new(b"b"),
+            last_epoch_id: 0,
+        }
+    }
+}
+
+impl RewardsModule {
+    fn internal_credit(&mut self, account_id: &AccountId, amount: Balance) {
+        if amount > 0 {
+            let balance = self.balances.get(account_id).unwrap_or(0);
+            self.balances.insert(account_id, &(balance + amount));
+        }
+    }
+
+    // Credits `pool` pro-rata to `weights`, returns the amount actually credited
+    fn internal_distribute(&mut self, pool: Balance, weights: &HashMap<AccountId, u128>) -> Balance {
+        let total: u128 = weights.values().sum();
+        if total == 0 {
+            return 0;
+        }
+        let mut distributed = 0;
+        for (account_id, weight) in weights.iter() {
+            let amount = pool * weight / total;
+            self.internal_credit(account_id, amount);
+            distributed += amount;
+        }
+        distributed
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Funds reward pools for the given curation rounds from the treasury. The pools are locked until
+    // distributed, so they must be covered by the contract balance not already locked.
+    pub fn create_reward_epoch(&mut self, round_ids: Vec<u64>, creator_pool: U128, curator_pool: U128) -> u64 {
+        self.assert_owner();
+        require!(!round_ids.is_empty(), "Reward epoch needs at least one curation round");
+        let id = self.rewards.last_epoch_id;
+        for round_id in round_ids.iter() {
+            require!(self.curation.rounds.get(round_id).is_some(), "No curation round found with this id");
+            require!(
+                self.rewards.round_epochs.insert(round_id, &id).is_none(),
+                "Curation round is already rewarded by another epoch"
+            );
+        }
+        let amount = creator_pool.0 + curator_pool.0;
+        require!(
+            env::account_balance().saturating_sub(self.locked_amount) >= amount,
+            "Not enough treasury funds for the reward pools"
+        );
+        self.locked_amount += amount;
+        self.rewards.epochs.insert(
+            &id,
+            &RewardEpoch {
+                id,
+                round_ids,
+                creator_pool,
+                curator_pool,
+                status: RewardEpochStatus::Funded,
+                created_at: env::block_timestamp(),
+                distributed_at: None,
+            },
+        );
+        self.rewards.last_epoch_id += 1;
+        id
+    }
+
+    // Credits the pools of an epoch once all of its rounds are closed. Anyone can call this.
+    // Pools without anyone to reward, and rounding leftovers, return to the treasury.
+    pub fn distribute_reward_epoch(&mut self, epoch_id: u64) {
+        let mut epoch = self
+            .rewards
+            .epochs
+            .get(&epoch_id)
+            .expect("No reward epoch found with this id");
+        require!(epoch.status == RewardEpochStatus::Funded, "Reward epoch is already distributed");
+
+        let mut creator_weights: HashMap<AccountId, u128> = HashMap::new();
+        let mut curator_weights: HashMap<AccountId, u128> = HashMap::new();
+        for round_id in epoch.round_ids.iter() {
+            let round = self.curation.rounds.get(round_id).unwrap();
+            require!(round.status == CurationRoundStatus::Closed, "Curation round is not closed");
+            for work_id in round.work_ids.iter() {
+                let work = self.curation.internal_get_work(*work_id);
+                let accepted = work.status == WorkStatus::Accepted;
+                if accepted {
+                    *creator_weights.entry(work.creator_id.clone()).or_insert(0) += work.votes_for as u128;
+                }
+                for (curator_id, accept) in work.votes.iter() {
+                    if *accept == accepted {
+                        *curator_weights.entry(curator_id.clone()).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let distributed = self.rewards.internal_distribute(epoch.creator_pool.0, &creator_weights)
+            + self.rewards.internal_distribute(epoch.curator_pool.0, &curator_weights);
+        self.locked_amount -= epoch.creator_pool.0 + epoch.curator_pool.0 - distributed;
+        epoch.status = RewardEpochStatus::Distributed;
+        epoch.distributed_at = Some(env::block_timestamp());
+        self.rewards.epochs.insert(&epoch_id, &epoch);
+
+        env::log_str(&format!("Reward epoch {} distributed {} yoctoNEAR", epoch_id, distributed));
+    }
+
+    // Transfers all distributed rewards of the caller
+    pub fn claim_rewards(&mut self) -> U128 {
+        let account_id = env::predecessor_account_id();
+        let amount = self.rewards.balances.remove(&account_id).unwrap_or(0);
+        require!(amount > 0, "No rewards to claim");
+        self.locked_amount -= amount;
+        Promise::new(account_id).transfer(amount);
+        U128(amount)
+    }
+
+    pub fn get_pending_rewards(&self, account_id: AccountId) -> U128 {
+        U128(self.rewards.balances.get(&account_id).unwrap_or(0))
+    }
+
+    pub fn get_reward_epoch(&self, epoch_id: u64) -> Option<RewardEpoch> {
+        self.rewards.epochs.get(&epoch_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::curation::{WorkKind, WorkMetadata};
+    use crate::test_utils::*;
+
+    use super::*;
+
+    // Closed round 0 where bob's work was accepted by charlie (3) over danny (1)
+    fn setup_closed_round() -> Contract {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(2), 3);
+        set_voting_power(&mut contract, &accounts(3), 1);
+        call_as(&owner());
+        let round_id = contract.open_curation_round("Round 1".to_string(), 100, None);
+
+        call_as(&accounts(1));
+        let metadata = WorkMetadata {
+            title: "Saga".to_string(),
+            description: None,
+            kind: WorkKind::Story,
+            media: None,
+        };
+        let work_id = contract.submit_work(round_id, [1; 32].into(), metadata);
+        call_as(&accounts(2));
+        contract.vote_work(work_id, true, None);
+        call_as(&accounts(3));
+        contract.vote_work(work_id, false, None);
+        call_at(&accounts(3), 101);
+        contract.close_curation_round(round_id);
+        contract
+    }
+
+    #[test]
+    fn test_curation_rewards_payout() {
+        let mut contract = setup_closed_round();
+        call_as(&owner());
+        let epoch_id = contract.create_reward_epoch(vec![0], U128(600), U128(400));
+        assert_eq!(contract.locked_amount, 1_000);
+        contract.distribute_reward_epoch(epoch_id);
+        // The only accepted work's creator gets the creator pool, the only curator voting with the outcome
+        // gets the curator pool.
+        assert_eq!(contract.get_pending_rewards(accounts(1)), U128(600));
+        assert_eq!(contract.get_pending_rewards(accounts(2)), U128(400));
+        assert_eq!(contract.get_pending_rewards(accounts(3)), U128(0));
+
+        call_as(&accounts(1));
+        assert_eq!(contract.claim_rewards(), U128(600));
+        assert_eq!(transfers(), vec![(accounts(1), 600)]);
+        assert_eq!(contract.get_pending_rewards(accounts(1)), U128(0));
+        assert_eq!(contract.locked_amount, 400);
+    }
+
+    #[test]
+    #[should_panic(expected = "No rewards to claim")]
+    fn test_claim_without_rewards() {
+        let mut contract = setup_contract();
+        call_as(&accounts(1));
+        contract.claim_rewards();
+    }
+
+    #[test]
+    #[should_panic(expected = "Curation round is already rewarded by another epoch")]
+    fn test_reward_round_twice() {
+        let mut contract = setup_closed_round();
+        call_as(&owner());
+        contract.create_reward_epoch(vec![0], U128(600), U128(400));
+        contract.create_reward_epoch(vec![0], U128(600), U128(400));
+    }
+
+    #[test]
+    #[should_panic(expected = "Curation round is not closed")]
+    fn test_distribute_open_round() {
+        let mut contract = setup_contract();
+        call_as(&owner());
+        contract.open_curation_round("Round 1".to_string(), 100, None);
+        let epoch_id = contract.create_reward_epoch(vec![0], U128(600), U128(400));
+        contract.distribute_reward_epoch(epoch_id);
+    }
+}
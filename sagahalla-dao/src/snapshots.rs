@@ -0,0 +1,351 @@
+use std::collections::HashSet;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, CryptoHash};
+
+use crate::permissions::SNAPSHOT;
+use crate::voting::GovernanceData;
+use crate::*;
+
+// Balances of one holder in a snapshot. The leaf hash is sha256 of the borsh encoding of this struct.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SnapshotLeaf {
+    pub account_id: AccountId,
+    pub mana_balance: U128,
+    pub collateral_mana_balance: U128,
+    pub voting_power: u64,
+}
+
+impl SnapshotLeaf {
+    pub fn hash(&self) -> CryptoHash {
+        env::sha256_array(&self.try_to_vec().unwrap())
+    }
+}
+
+// Parent node of two nodes. Pairs are hashed in sorted order so proofs need no directions.
+pub fn hash_pair(a: &CryptoHash, b: &CryptoHash) -> CryptoHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    env::sha256_array(&[left.as_ref(), right.as_ref()].concat())
+}
+
+// Root reached by folding the leaf with its proof, sibling by sibling from the bottom of the tree
+pub fn merkle_root(leaf: CryptoHash, proof: &[Base58CryptoHash]) -> CryptoHash {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, &(*sibling).into()))
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SnapshotStatus {
+    Proposed, // Waiting for governance approval
+    Approved, // Holders can activate their balances
+    Rejected,
+}
+
+// Approval vote on a snapshot epoch, with the plan quorum
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SnapshotVote {
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub voters: HashSet<AccountId>,
+    pub quorum: u64, // Voting power needed on either side to resolve the vote
+}
+
+// Merkle root over the balances of all holders at one snapshot epoch
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SnapshotEpoch {
+    pub id: u64,
+    pub root: Base58CryptoHash,
+    pub description: String,
+    pub status: SnapshotStatus,
+    pub vote: SnapshotVote,
+    pub created_at: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SnapshotModule {
+    pub epochs: UnorderedMap<u64, SnapshotEpoch>,
+    pub activations: LookupMap<AccountId, u64>, // Latest epoch each holder activated
+    pub last_epoch_id: u64,
+}
+
+impl Default for SnapshotModule {
+    fn default() -> Self {
+        Self {
+            epochs: UnorderedMap::new(b"n"),
+            activations: LookupMap::new(b"t"),
+            last_epoch_id: 0,
+        }
+    }
+}
+
+impl SnapshotModule {
+    fn internal_get_epoch(&self, epoch_id: u64) -> SnapshotEpoch {
+        self.epochs.get(&epoch_id).expect("No snapshot epoch found with this id")
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Proposes the Merkle root of a balance snapshot built off-chain. It applies once approved by vote.
+    pub fn propose_snapshot(&mut self, root: Base58CryptoHash, description: String) -> u64 {
+        self.assert_owner();
+        let id = self.snapshots.last_epoch_id;
+        let config = &self.voting_module.config;
+        let epoch = SnapshotEpoch {
+            id,
+            root,
+            description,
+            status: SnapshotStatus::Proposed,
+            vote: SnapshotVote {
+                votes_for: 0,
+                votes_against: 0,
+                voters: HashSet::new(),
+                quorum: self
+                    .voting_module
+                    .internal_quorum(config.plan_quorum, config.plan_quorum_bps),
+            },
+            created_at: env::block_timestamp(),
+        };
+        self.snapshots.epochs.insert(&id, &epoch);
+        self.snapshots.last_epoch_id += 1;
+        id
+    }
+
+    pub fn vote_snapshot(&mut self, epoch_id: u64, approve: bool) {
+        self.assert_permission(SNAPSHOT, "Vote");
+        let mut epoch = self.snapshots.internal_get_epoch(epoch_id);
+        require!(epoch.status == SnapshotStatus::Proposed, "Snapshot is not open for voting");
+        let vote = &mut epoch.vote;
+        let outcome = self.voting_module.internal_tally(
+            &mut vote.voters,
+            &mut vote.votes_for,
+            &mut vote.votes_against,
+            vote.quorum,
+            &env::predecessor_account_id(),
+            approve,
+        );
+        match outcome {
+            Some(true) => {
+                epoch.status = SnapshotStatus::Approved;
+                // Epochs may be approved out of order; the newest one wins.
+                self.voting_module.latest_snapshot_epoch =
+                    self.voting_module.latest_snapshot_epoch.max(Some(epoch_id));
+            }
+            Some(false) => epoch.status = SnapshotStatus::Rejected,
+            None => {}
+        }
+        self.snapshots.epochs.insert(&epoch_id, &epoch);
+    }
+
+    // Proves the caller's balances are in an approved snapshot and makes them the caller's governance data.
+    // Only the latest approved epoch can be activated, and balances from older epochs stop counting once a
+    // newer one is approved.
+    pub fn activate_snapshot_balances(
+        &mut self,
+        epoch_id: u64,
+        mana_balance: U128,
+        collateral_mana_balance: U128,
+        voting_power: u64,
+        proof: Vec<Base58CryptoHash>,
+    ) {
+        let epoch = self.snapshots.internal_get_epoch(epoch_id);
+        require!(epoch.status == SnapshotStatus::Approved, "Snapshot is not approved");
+        require!(
+            self.voting_module.latest_snapshot_epoch == Some(epoch_id),
+            "A newer snapshot has been approved"
+        );
+        let account_id = env::predecessor_account_id();
+        if let Some(activated_epoch_id) = self.snapshots.activations.get(&account_id) {
+            require!(epoch_id > activated_epoch_id, "Balances were already activated from this or a newer snapshot");
+        }
+
+        let leaf = SnapshotLeaf {
+            account_id: account_id.clone(),
+            mana_balance,
+            collateral_mana_balance,
+            voting_power,
+        };
+        let root: CryptoHash = epoch.root.into();
+        require!(merkle_root(leaf.hash(), &proof) == root, "Invalid snapshot proof");
+
+        self.voting_module.governance_data.insert(
+            &account_id,
+            &GovernanceData::from_snapshot(mana_balance, collateral_mana_balance, voting_power, epoch_id),
+        );
+        self.snapshots.activations.insert(&account_id, &epoch_id);
+
+        env::log_str(&format!(
+            "Activated snapshot {} balances for {}: voting_power = {}",
+            epoch_id, account_id, voting_power
+        ));
+    }
+
+    pub fn get_snapshot(&self, epoch_id: u64) -> Option<SnapshotEpoch> {
+        self.snapshots.epochs.get(&epoch_id)
+    }
+
+    // Latest snapshot epoch the account activated its balances from
+    pub fn get_snapshot_activation(&self, account_id: AccountId) -> Option<u64> {
+        self.snapshots.activations.get(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn snapshot_leaf(account_index: usize, voting_power: u64) -> SnapshotLeaf {
+        SnapshotLeaf {
+            account_id: accounts(account_index),
+            mana_balance: U128(voting_power as u128 * 100),
+            collateral_mana_balance: U128(0),
+            voting_power,
+        }
+    }
+
+    // Snapshot 0 over two holders, bob (7) and charlie (3), approved by danny if `approve`
+    fn setup_snapshot(approve: bool) -> (Contract, SnapshotLeaf, SnapshotLeaf) {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(3), 1);
+        let (bob, charlie) = (snapshot_leaf(1, 7), snapshot_leaf(2, 3));
+        let root: Base58CryptoHash = hash_pair(&bob.hash(), &charlie.hash()).into();
+
+        call_as(&owner());
+        let epoch_id = contract.propose_snapshot(root, "Balances at block 100".to_string());
+        assert_eq!(contract.get_snapshot(epoch_id).unwrap().status, SnapshotStatus::Proposed);
+        call_as(&accounts(3));
+        contract.vote_snapshot(epoch_id, approve);
+        (contract, bob, charlie)
+    }
+
+    fn activate(contract: &mut Contract, leaf: &SnapshotLeaf, proof: Vec<Base58CryptoHash>) {
+        activate_epoch(contract, 0, leaf, proof);
+    }
+
+    fn activate_epoch(
+        contract: &mut Contract,
+        epoch_id: u64,
+        leaf: &SnapshotLeaf,
+        proof: Vec<Base58CryptoHash>,
+    ) {
+        call_as(&leaf.account_id);
+        contract.activate_snapshot_balances(
+            epoch_id,
+            leaf.mana_balance,
+            leaf.collateral_mana_balance,
+            leaf.voting_power,
+            proof,
+        );
+    }
+
+    #[test]
+    fn test_activate_snapshot_balances() {
+        let (mut contract, bob, charlie) = setup_snapshot(true);
+        assert_eq!(contract.get_snapshot(0).unwrap().status, SnapshotStatus::Approved);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+        activate(&mut contract, &charlie, vec![bob.hash().into()]);
+        assert_eq!(contract.get_snapshot_activation(accounts(1)), Some(0));
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(1)), 7);
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(2)), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid snapshot proof")]
+    fn test_activate_snapshot_forged_balances() {
+        let (mut contract, _, charlie) = setup_snapshot(true);
+        activate(&mut contract, &snapshot_leaf(1, 70), vec![charlie.hash().into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid snapshot proof")]
+    fn test_activate_snapshot_of_other_account() {
+        // Danny replays bob's leaf values, but the leaf hash covers the account id.
+        let (mut contract, bob, charlie) = setup_snapshot(true);
+        let danny = SnapshotLeaf {
+            account_id: accounts(3),
+            ..bob
+        };
+        activate(&mut contract, &danny, vec![charlie.hash().into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Balances were already activated from this or a newer snapshot")]
+    fn test_activate_snapshot_twice() {
+        let (mut contract, bob, charlie) = setup_snapshot(true);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+    }
+
+    // Snapshot 1 over bob (2) and charlie (3), approved by danny
+    fn approve_second_snapshot(contract: &mut Contract) -> (SnapshotLeaf, SnapshotLeaf) {
+        let (bob, charlie) = (snapshot_leaf(1, 2), snapshot_leaf(2, 3));
+        let root: Base58CryptoHash = hash_pair(&bob.hash(), &charlie.hash()).into();
+        call_as(&owner());
+        let epoch_id = contract.propose_snapshot(root, "Balances at block 200".to_string());
+        call_as(&accounts(3));
+        contract.vote_snapshot(epoch_id, true);
+        (bob, charlie)
+    }
+
+    #[test]
+    fn test_older_snapshot_balances_go_stale() {
+        let (mut contract, bob, charlie) = setup_snapshot(true);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+        let (bob, charlie) = approve_second_snapshot(&mut contract);
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(1)), 0);
+        // Relayed governance data is not tied to a snapshot and keeps counting.
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(3)), 1);
+
+        activate_epoch(&mut contract, 1, &bob, vec![charlie.hash().into()]);
+        assert_eq!(contract.get_snapshot_activation(accounts(1)), Some(1));
+        assert_eq!(contract.voting_module.internal_voting_power(&accounts(1)), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "A newer snapshot has been approved")]
+    fn test_activate_older_snapshot() {
+        let (mut contract, bob, charlie) = setup_snapshot(true);
+        approve_second_snapshot(&mut contract);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot is not open for voting")]
+    fn test_vote_on_resolved_snapshot() {
+        let (mut contract, _, _) = setup_snapshot(true);
+        set_voting_power(&mut contract, &accounts(4), 1);
+        call_as(&accounts(4));
+        contract.vote_snapshot(0, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller has no role allowing this action")]
+    fn test_vote_snapshot_without_role() {
+        let mut contract = setup_contract();
+        set_voting_power(&mut contract, &accounts(3), 1);
+        contract.propose_snapshot([1; 32].into(), "Balances at block 100".to_string());
+        contract.remove_role("all".to_string());
+        call_as(&accounts(3));
+        contract.vote_snapshot(0, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot is not approved")]
+    fn test_activate_rejected_snapshot() {
+        let (mut contract, bob, charlie) = setup_snapshot(false);
+        assert_eq!(contract.get_snapshot(0).unwrap().status, SnapshotStatus::Rejected);
+        activate(&mut contract, &bob, vec![charlie.hash().into()]);
+    }
+}
@@ -0,0 +1,119 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId};
+
+use crate::voting::CirculatingSupply;
+use crate::*;
+
+// Quorums new votes would get with the current supply
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Quorums {
+    pub plan_quorum: u64,
+    pub execution_quorum: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Records the circulating MANA and SHLD supply. Callable by the owner or the supply relayer,
+    // which reports the supply read from Aurora.
+    pub fn update_circulating_supply(&mut self, mana_supply: U128, shld_supply: U128) {
+        let reporter = env::predecessor_account_id();
+        require!(
+            reporter == self.owner_id || Some(&reporter) == self.supply_relayer_id.as_ref(),
+            "Only the owner or the supply relayer can update the circulating supply"
+        );
+        self.voting_module.circulating_supply = Some(CirculatingSupply {
+            mana_supply,
+            shld_supply,
+            updated_at: env::block_timestamp(),
+            reported_by: reporter,
+        });
+
+        env::log_str(&format!(
+            "Circulating supply updated: mana_supply = {}, shld_supply = {}",
+            mana_supply.0, shld_supply.0
+        ));
+    }
+
+    pub fn set_supply_relayer(&mut self, relayer_id: Option<AccountId>) {
+        self.assert_owner();
+        self.supply_relayer_id = relayer_id;
+    }
+
+    pub fn get_supply_relayer(&self) -> Option<AccountId> {
+        self.supply_relayer_id.clone()
+    }
+
+    pub fn get_circulating_supply(&self) -> Option<CirculatingSupply> {
+        self.voting_module.circulating_supply.clone()
+    }
+
+    pub fn get_quorums(&self) -> Quorums {
+        let config = &self.voting_module.config;
+        Quorums {
+            plan_quorum: self
+                .voting_module
+                .internal_quorum(config.plan_quorum, config.plan_quorum_bps),
+            execution_quorum: self
+                .voting_module
+                .internal_quorum(config.execution_quorum, config.execution_quorum_bps),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+    use crate::voting::SupplyToken;
+
+    use super::*;
+
+    // Plan quorum of 2, or 10% of the supply counted in units of 100 tokens
+    fn setup_supply_quorum(quorum_supply_token: SupplyToken) -> Contract {
+        let mut contract = setup_contract();
+        let mut config = contract.get_voting_config();
+        config.plan_quorum = 2;
+        config.plan_quorum_bps = 1_000;
+        config.quorum_supply_token = quorum_supply_token;
+        config.voting_power_unit = U128(100);
+        contract.update_voting_config(config);
+        contract
+    }
+
+    #[test]
+    fn test_circulating_supply_scales_quorum() {
+        let mut contract = setup_supply_quorum(SupplyToken::Mana);
+        assert_eq!(contract.get_quorums().plan_quorum, 2);
+
+        contract.update_circulating_supply(U128(50_000), U128(0));
+        assert_eq!(contract.get_quorums().plan_quorum, 50);
+        // The fixed quorum still applies to small supplies.
+        contract.update_circulating_supply(U128(1_000), U128(0));
+        assert_eq!(contract.get_quorums().plan_quorum, 2);
+        contract.update_circulating_supply(U128(50_000), U128(0));
+        contract.submit_project_plan(project_plan(1, &[(accounts(1), 100)]));
+        assert_eq!(contract.get_project_plan_vote(1).unwrap().quorum, 50);
+    }
+
+    #[test]
+    fn test_supply_relayer() {
+        let mut contract = setup_supply_quorum(SupplyToken::Shld);
+        contract.set_supply_relayer(Some(accounts(1)));
+        call_as(&accounts(1));
+        contract.update_circulating_supply(U128(0), U128(30_000));
+        let supply = contract.get_circulating_supply().unwrap();
+        assert_eq!(supply.reported_by, accounts(1));
+        assert_eq!(contract.get_quorums().plan_quorum, 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or the supply relayer can update the circulating supply")]
+    fn test_circulating_supply_reporter() {
+        let mut contract = setup_contract();
+        call_as(&accounts(1));
+        contract.update_circulating_supply(U128(50_000), U128(0));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use near_sdk::json_types::U128;
+use near_sdk::mock::{Receipt, VmAction};
+use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+use near_sdk::{testing_env, AccountId, Balance, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+use crate::mana_structs::{
+    DeveloperProjectPlan, EpicPlan, ProjectExecution, ProjectPlan, SubProjectPlan, TaskExecution, TaskPlan,
+    TaskRoleManaHours, TaskStatus,
+};
+use crate::Contract;
+
+pub fn dao() -> AccountId {
+    "sagahalla.near".parse().unwrap()
+}
+
+pub fn owner() -> AccountId {
+    accounts(0)
+}
+
+pub fn context(predecessor: &AccountId) -> VMContextBuilder {
+    let mut context = VMContextBuilder::new();
+    context
+        .current_account_id(dao())
+        .predecessor_account_id(predecessor.clone());
+    context
+}
+
+// Next calls are made by `predecessor`
+pub fn call_as(predecessor: &AccountId) {
+    testing_env!(context(predecessor).build());
+}
+
+// Next calls are made by `predecessor` at `block_timestamp`
+pub fn call_at(predecessor: &AccountId, block_timestamp: u64) {
+    testing_env!(context(predecessor).block_timestamp(block_timestamp).build());
+}
+
+pub fn call_with_deposit(predecessor: &AccountId, attached_deposit: Balance) {
+    testing_env!(context(predecessor).attached_deposit(attached_deposit).build());
+}
+
+// Next call is a callback of the contract on itself, receiving `result`
+pub fn callback_with(result: PromiseResult) {
+    testing_env!(
+        context(&dao()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![result],
+    );
+}
+
+pub fn setup_contract() -> Contract {
+    call_as(&owner());
+    Contract::new(owner())
+}
+
+// Records governance data giving `account_id` the voting power, as the governance relayer would.
+// Leaves the owner as the caller.
+pub fn set_voting_power(contract: &mut Contract, account_id: &AccountId, voting_power: u64) {
+    call_as(&owner());
+    contract.update_governance_data(
+        account_id.clone(),
+        U128(voting_power as u128 * 100),
+        U128(0),
+        voting_power,
+        1,
+    );
+}
+
+// Plan with one task for each developer, allocating them the given MANA. Task ids start at 1.
+pub fn project_plan(id: u64, allocations: &[(AccountId, u128)]) -> ProjectPlan {
+    let mut developers = HashMap::new();
+    for (index, (developer_id, allocated)) in allocations.iter().enumerate() {
+        let task_id = index as u64 + 1;
+        developers.insert(
+            developer_id.clone(),
+            DeveloperProjectPlan {
+                developer_id: developer_id.clone(),
+                mana_hours_budgeted: 10,
+                mana_token_allocated: U128(*allocated),
+                sub_projects: vec![SubProjectPlan {
+                    id: task_id,
+                    project_plan_id: id,
+                    sub_project_name: "Contracts".to_string(),
+                    epics: vec![EpicPlan {
+                        id: task_id,
+                        sub_project_plan_id: task_id,
+                        epic_name: "Voting".to_string(),
+                        tasks: vec![TaskPlan {
+                            id: task_id,
+                            epic_plan_id: task_id,
+                            task_name: format!("Task {}", task_id),
+                            estimated_mana_hours: 10,
+                            roles_mana_hours: vec![TaskRoleManaHours {
+                                id: task_id,
+                                task_id,
+                                role_name: "developer".to_string(),
+                                mana_hours: 10,
+                            }],
+                            status: TaskStatus::Planned,
+                            claim: None,
+                        }],
+                    }],
+                }],
+            },
+        );
+    }
+    ProjectPlan {
+        id,
+        proposal_id: None,
+        project_name: format!("Project {}", id),
+        total_mana_hours: 10 * allocations.len() as u64,
+        voting_power: None,
+        created_at: "2024-01-01".to_string(),
+        updated_at: None,
+        developers,
+        proposal: None,
+        version: 0,
+        treasury_proposal: None,
+    }
+}
+
+// Submits the plan as bob and approves it with charlie's vote, giving charlie voting power if needed
+pub fn approve_plan(contract: &mut Contract, project_plan_id: u64, allocations: &[(AccountId, u128)]) {
+    if contract.voting_module.internal_voting_power(&accounts(2)) == 0 {
+        set_voting_power(contract, &accounts(2), 1);
+    }
+    call_as(&accounts(1));
+    contract.submit_project_plan(project_plan(project_plan_id, allocations));
+    call_as(&accounts(2));
+    contract.vote_project_plan(project_plan_id, true);
+}
+
+// Execution of a plan reporting the given actual mana hours for each task
+pub fn project_execution(id: u64, project_plan_id: u64, actuals: &[(u64, u64)]) -> ProjectExecution {
+    ProjectExecution {
+        id,
+        project_plan_id,
+        actual_mana_hours: actuals.iter().map(|(_, hours)| hours).sum(),
+        tasks: actuals
+            .iter()
+            .map(|(task_plan_id, hours)| TaskExecution {
+                id: *task_plan_id,
+                project_execution_id: id,
+                task_plan_id: *task_plan_id,
+                actual_mana_hours: *hours,
+                status: TaskStatus::Completed,
+            })
+            .collect(),
+        peer_votes: vec![],
+    }
+}
+
+// NEAR transfers created by the last call, by receiver
+pub fn transfers() -> Vec<(AccountId, Balance)> {
+    get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt: Receipt| {
+            let receiver_id = receipt.receiver_id;
+            receipt.actions.into_iter().filter_map(move |action| match action {
+                VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+// Function calls created by the last call as (receiver, method, args, deposit)
+pub fn function_calls() -> Vec<(AccountId, String, Vec<u8>, Balance)> {
+    get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt: Receipt| {
+            let receiver_id = receipt.receiver_id;
+            receipt.actions.into_iter().filter_map(move |action| match action {
+                VmAction::FunctionCall {
+                    function_name,
+                    args,
+                    deposit,
+                    ..
+                } => Some((receiver_id.clone(), function_name, args, deposit)),
+                _ => None,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{near_bindgen, AccountId};
+
+use crate::mana_structs::{ProjectExecution, ProjectPlan};
+use crate::*;
+
+// Estimated vs actual mana hours for one level of a project
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ManaHoursVariance {
+    pub estimated_mana_hours: u64,
+    pub actual_mana_hours: u64,
+    pub variance: i64, // actual - estimated, positive when over the estimate
+    pub variance_bps: i64, // variance relative to the estimate
+    pub over_budget: bool, // variance is above the configured tolerance
+}
+
+impl ManaHoursVariance {
+    pub fn new(estimated_mana_hours: u64, actual_mana_hours: u64, tolerance_bps: u64) -> Self {
+        let variance = actual_mana_hours as i64 - estimated_mana_hours as i64;
+        let variance_bps = if estimated_mana_hours == 0 {
+            if actual_mana_hours == 0 { 0 } else { 10_000 }
+        } else {
+            variance * 10_000 / estimated_mana_hours as i64
+        };
+        Self {
+            estimated_mana_hours,
+            actual_mana_hours,
+            variance,
+            variance_bps,
+            over_budget: variance_bps > tolerance_bps as i64,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TaskVariance {
+    pub task_plan_id: u64,
+    pub task_name: String,
+    pub variance: ManaHoursVariance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpicVariance {
+    pub epic_plan_id: u64,
+    pub epic_name: String,
+    pub variance: ManaHoursVariance,
+}
+
+// Developer estimates are their budgeted mana hours, actuals are the sum over their tasks
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeveloperVariance {
+    pub developer_id: AccountId,
+    pub variance: ManaHoursVariance,
+}
+
+// Variance report of a project execution against its plan, at every level of the plan
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProjectVariance {
+    pub project_execution_id: u64,
+    pub project_plan_id: u64,
+    pub project: ManaHoursVariance,
+    pub developers: Vec<DeveloperVariance>,
+    pub epics: Vec<EpicVariance>,
+    pub tasks: Vec<TaskVariance>,
+}
+
+impl ProjectVariance {
+    pub fn new(plan: &ProjectPlan, execution: &ProjectExecution, tolerance_bps: u64) -> Self {
+        let mut actual_hours: HashMap<u64, u64> = HashMap::new();
+        for task in execution.tasks.iter() {
+            *actual_hours.entry(task.task_plan_id).or_insert(0) += task.actual_mana_hours;
+        }
+
+        let mut developers = vec![];
+        let mut epics = vec![];
+        let mut tasks = vec![];
+        let mut developer_ids: Vec<&AccountId> = plan.developers.keys().collect();
+        developer_ids.sort();
+        for developer_id in developer_ids {
+            let developer = &plan.developers[developer_id];
+            let mut developer_actual = 0;
+            for epic in developer.sub_projects.iter().flat_map(|sub_project| sub_project.epics.iter()) {
+                let (mut epic_estimated, mut epic_actual) = (0, 0);
+                for task in epic.tasks.iter() {
+                    let actual = actual_hours.get(&task.id).copied().unwrap_or(0);
+                    epic_estimated += task.estimated_mana_hours;
+                    epic_actual += actual;
+                    tasks.push(TaskVariance {
+                        task_plan_id: task.id,
+                        task_name: task.task_name.clone(),
+                        variance: ManaHoursVariance::new(task.estimated_mana_hours, actual, tolerance_bps),
+                    });
+                }
+                developer_actual += epic_actual;
+                epics.push(EpicVariance {
+                    epic_plan_id: epic.id,
+                    epic_name: epic.epic_name.clone(),
+                    variance: ManaHoursVariance::new(epic_estimated, epic_actual, tolerance_bps),
+                });
+            }
+            developers.push(DeveloperVariance {
+                developer_id: developer_id.clone(),
+                variance: ManaHoursVariance::new(developer.mana_hours_budgeted, developer_actual, tolerance_bps),
+            });
+        }
+
+        // Project actuals come from the task executions rather than the execution's own total
+        let project_actual = execution.tasks.iter().map(|task| task.actual_mana_hours).sum();
+        Self {
+            project_execution_id: execution.id,
+            project_plan_id: plan.id,
+            project: ManaHoursVariance::new(plan.total_mana_hours, project_actual, tolerance_bps),
+            developers,
+            epics,
+            tasks,
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_execution_variance(&self, execution: &ProjectExecution) -> ProjectVariance {
+        let plan = self.internal_get_plan(execution.project_plan_id);
+        ProjectVariance::new(&plan, execution, self.plan_config.over_budget_tolerance_bps)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Per-project, per-developer, per-epic and per-task variance of an execution against its plan
+    pub fn get_execution_variance(&self, project_execution_id: u64) -> ProjectVariance {
+        let execution = self
+            .project_executions
+            .get(&project_execution_id)
+            .expect("No project execution found with this id");
+        self.internal_execution_variance(&execution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+    use crate::voting::{ProjectExecutionStatus, ProjectPlanStatus};
+
+    use super::*;
+
+    // Bob's and danny's approved plan 1, with 10 hours estimated for each of their tasks
+    fn setup_variance() -> Contract {
+        let mut contract = setup_contract();
+        approve_plan(&mut contract, 1, &[(accounts(1), 1_000), (accounts(3), 1_000)]);
+        contract
+    }
+
+    #[test]
+    fn test_execution_variance() {
+        let mut contract = setup_variance();
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 15), (2, 5)]));
+        let variance = contract.get_execution_variance(1);
+        assert_eq!((variance.project.variance, variance.project.over_budget), (0, false));
+        let tasks: Vec<(u64, i64, i64, bool)> = variance
+            .tasks
+            .iter()
+            .map(|task| {
+                let variance = &task.variance;
+                (task.task_plan_id, variance.variance, variance.variance_bps, variance.over_budget)
+            })
+            .collect();
+        assert_eq!(tasks, vec![(1, 5, 5_000, true), (2, -5, -5_000, false)]);
+        assert_eq!(variance.developers[0].developer_id, accounts(1));
+        assert_eq!(variance.developers[0].variance.actual_mana_hours, 15);
+        assert!(contract.get_project_execution_vote(1).unwrap().budget_override.is_none());
+    }
+
+    #[test]
+    fn test_over_budget_execution_approved_with_override() {
+        let mut contract = setup_variance();
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 15), (2, 10)]));
+        let vote = contract.get_project_execution_vote(1).unwrap();
+        assert!(vote.over_budget);
+        assert_eq!(vote.budget_override.unwrap().status, ProjectPlanStatus::Active);
+
+        // Reaching the execution quorum is not enough while the override is pending.
+        call_as(&accounts(2));
+        contract.vote_project_execution(1, true);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Active
+        );
+        contract.vote_budget_override(1, true);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_over_budget_execution_rejected_by_override() {
+        let mut contract = setup_variance();
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 15), (2, 10)]));
+        call_as(&accounts(2));
+        contract.vote_budget_override(1, false);
+        assert_eq!(
+            contract.get_project_execution_vote(1).unwrap().status,
+            ProjectExecutionStatus::Rejected
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Project execution mana hours must equal the sum of its task executions")]
+    fn test_execution_understating_mana_hours() {
+        let mut contract = setup_variance();
+        let mut execution = project_execution(1, 1, &[(1, 15), (2, 10)]);
+        execution.actual_mana_hours = 20;
+        call_as(&accounts(1));
+        contract.submit_project_execution(execution);
+    }
+
+    #[test]
+    #[should_panic(expected = "Project execution is not over budget")]
+    fn test_budget_override_within_budget() {
+        let mut contract = setup_variance();
+        call_as(&accounts(1));
+        contract.submit_project_execution(project_execution(1, 1, &[(1, 10), (2, 10)]));
+        call_as(&accounts(2));
+        contract.vote_budget_override(1, true);
+    }
+}
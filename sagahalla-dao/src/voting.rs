@@ -4,12 +4,14 @@ use near_sdk::{env, AccountId, require, near_bindgen};
 use near_sdk::json_types::U128;
 use async_trait::async_trait;
 use near_sdk::serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::error::Error;
+use crate::disputes::{Dispute, DisputeMethod, DisputeStatus};
 use crate::mana_structs::ManaBalancesProof; // Import ManaBalancesProof here
 //use aurora_engine_sdk::proof::verify_proof;
 
 // Enums for Project Plan and Project Execution statuses
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ProjectPlanStatus {
     Active,
@@ -17,22 +19,25 @@ pub enum ProjectPlanStatus {
     Rejected,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ProjectExecutionStatus {
     Active,
     Approved,
     Rejected,
+    Disputed, // Rejection was appealed and is waiting for a ruling
 }
 
 // Structures to track votes for Project Plans and Executions
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ProjectPlanVote {
     pub project_plan_id: u64,
     pub votes_for: u64,
     pub votes_against: u64,
     pub status: ProjectPlanStatus,
+    pub voters: HashSet<AccountId>,
+    pub quorum: u64, // Voting power needed on either side to resolve the vote
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -42,6 +47,87 @@ pub struct ProjectExecutionVote {
     pub votes_for: u64,
     pub votes_against: u64,
     pub status: ProjectExecutionStatus,
+    pub developer_id: AccountId, // Account that submitted the execution and may appeal a rejection
+    pub voters: HashSet<AccountId>,
+    pub quorum: u64, // Voting power needed on either side to resolve the vote
+    pub resolved_at: Option<u64>, // Block timestamp of the last approval or rejection
+    pub dispute: Option<Dispute>, // Appeal record, if the rejection was disputed
+    pub over_budget: bool, // Actual mana hours exceed the plan beyond the tolerance
+    pub budget_override: Option<ProjectPlanVote>, // Vote to approve despite going over budget
+}
+
+impl ProjectExecutionVote {
+    // Over-budget executions can only be approved once the budget override vote passed
+    pub fn budget_cleared(&self) -> bool {
+        !self.over_budget
+            || matches!(&self.budget_override, Some(vote) if vote.status == ProjectPlanStatus::Approved)
+    }
+}
+
+// How voting power turns into vote weight
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VotingMode {
+    #[default]
+    Standard,  // Each vote weighs the voter's full voting power
+    Quadratic, // Voters spend credits from a budget derived from voting power, each vote weighs sqrt(credits)
+}
+
+// Weight of a quadratic vote: integer square root of the credits spent
+pub fn quadratic_weight(credits: u64) -> u64 {
+    if credits < 2 {
+        return credits;
+    }
+    let mut x = credits;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + credits / x) / 2;
+    }
+    x
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SupplyToken {
+    Mana,
+    Shld,
+}
+
+// Circulating supply as reported by governance or a relayer, e.g. from `AuroraIntegration::get_circulating_supply`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CirculatingSupply {
+    pub mana_supply: U128,
+    pub shld_supply: U128,
+    pub updated_at: u64,
+    pub reported_by: AccountId,
+}
+
+// Thresholds used when opening new votes. A vote's quorum is the larger of the fixed quorum and the
+// basis points of the circulating supply, so quorums scale with supply.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VotingConfig {
+    pub plan_quorum: u64,
+    pub execution_quorum: u64,
+    pub plan_quorum_bps: u64, // Zero only uses the fixed quorum
+    pub execution_quorum_bps: u64,
+    pub quorum_supply_token: SupplyToken, // Supply the quorum percentages apply to
+    pub voting_power_unit: U128, // Token amount worth one unit of voting power
+}
+
+impl Default for VotingConfig {
+    fn default() -> Self {
+        Self {
+            plan_quorum: 1,
+            execution_quorum: 1,
+            plan_quorum_bps: 0,
+            execution_quorum_bps: 0,
+            quorum_supply_token: SupplyToken::Mana,
+            voting_power_unit: U128(1),
+        }
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -65,6 +151,36 @@ pub struct GovernanceData {
     mana_collateral_balance: U128,
     voting_power: u64,
     transaction_id: Option<u64>, // New field for unique transaction identification
+    snapshot_epoch: Option<u64>, // Snapshot epoch the balances were activated from, if any
+}
+
+impl GovernanceData {
+    pub fn new(
+        mana_balance: U128,
+        mana_collateral_balance: U128,
+        voting_power: u64,
+        transaction_id: Option<u64>,
+    ) -> Self {
+        Self {
+            mana_balance,
+            mana_collateral_balance,
+            voting_power,
+            transaction_id,
+            snapshot_epoch: None,
+        }
+    }
+
+    pub fn from_snapshot(
+        mana_balance: U128,
+        mana_collateral_balance: U128,
+        voting_power: u64,
+        epoch_id: u64,
+    ) -> Self {
+        Self {
+            snapshot_epoch: Some(epoch_id),
+            ..Self::new(mana_balance, mana_collateral_balance, voting_power, None)
+        }
+    }
 }
 
 #[near_bindgen]
@@ -74,7 +190,6 @@ pub struct GovernanceDataContract {
 }
 
 // VotingModule definition
-#[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct VotingModule {
     pub shld_holders: UnorderedMap<AccountId, u64>,
@@ -84,6 +199,9 @@ pub struct VotingModule {
     pub proposals: UnorderedMap<String, Vec<u8>>,
     // GovernanceDataContract fields
     pub governance_data: UnorderedMap<AccountId, GovernanceData>,
+    pub config: VotingConfig,
+    pub circulating_supply: Option<CirculatingSupply>,
+    pub latest_snapshot_epoch: Option<u64>, // Newest approved snapshot; balances from older ones no longer count
 }
 
 impl Default for VotingModule {
@@ -95,13 +213,14 @@ impl Default for VotingModule {
             project_contributions: UnorderedMap::new(b"p"),   // 'c' for contributions
             proposals: UnorderedMap::new(b"r"),              // 'r' for proposals
             governance_data: UnorderedMap::new(b"g"),
+            config: VotingConfig::default(),
+            circulating_supply: None,
+            latest_snapshot_epoch: None,
         }
     }
 }
 
-#[near_bindgen]
 impl VotingModule {
-    #[init]
     pub fn init_voting() -> Self {
         Self {
             shld_holders: UnorderedMap::new(b"s"),
@@ -110,6 +229,9 @@ impl VotingModule {
             project_contributions: UnorderedMap::new(b"p"),
             proposals: UnorderedMap::new(b"r"),
             governance_data: UnorderedMap::new(b"g"),
+            config: VotingConfig::default(),
+            circulating_supply: None,
+            latest_snapshot_epoch: None,
         }
     }
 
@@ -221,6 +343,7 @@ impl VotingModule {
             mana_collateral_balance,
             voting_power,
             transaction_id: Some(transaction_id),
+            snapshot_epoch: None,
         };
         self.governance_data.insert(&account_id, &data);
 
@@ -280,6 +403,166 @@ impl VotingModule {
     }
 }
 
+impl VotingModule {
+    // Voting power from the account's latest governance data, zero if none was recorded
+    // or if it was activated from a snapshot older than the latest approved one
+    pub(crate) fn internal_voting_power(&self, account_id: &AccountId) -> u64 {
+        self.governance_data
+            .get(account_id)
+            .filter(|data| data.snapshot_epoch.is_none() || data.snapshot_epoch == self.latest_snapshot_epoch)
+            .map(|data| data.voting_power)
+            .unwrap_or(0)
+    }
+
+    // Circulating supply of the quorum token in units of voting power, if it was reported
+    pub(crate) fn internal_supply_voting_power(&self) -> Option<u128> {
+        self.circulating_supply.as_ref().map(|supply| {
+            let amount = match self.config.quorum_supply_token {
+                SupplyToken::Mana => supply.mana_supply.0,
+                SupplyToken::Shld => supply.shld_supply.0,
+            };
+            amount / self.config.voting_power_unit.0
+        })
+    }
+
+    // Larger of the fixed quorum and `quorum_bps` of the circulating supply, in voting power
+    pub(crate) fn internal_quorum(&self, fixed_quorum: u64, quorum_bps: u64) -> u64 {
+        let supply = match self.internal_supply_voting_power() {
+            Some(supply) if quorum_bps > 0 => supply,
+            _ => return fixed_quorum,
+        };
+        let supply_quorum = supply * quorum_bps as u128 / 10_000;
+        std::cmp::max(fixed_quorum, supply_quorum.min(u64::MAX as u128) as u64)
+    }
+
+    // Opens a new vote on a project execution submitted by `developer_id`.
+    // Over-budget executions also get a budget override vote on their plan.
+    pub(crate) fn internal_open_execution_vote(
+        &mut self,
+        project_execution_id: u64,
+        project_plan_id: u64,
+        developer_id: AccountId,
+        over_budget: bool,
+    ) {
+        require!(
+            self.project_execution_votes.get(&project_execution_id).is_none(),
+            "Project execution has already been submitted"
+        );
+        let budget_override = if over_budget {
+            Some(self.internal_new_plan_vote(project_plan_id))
+        } else {
+            None
+        };
+        let vote = ProjectExecutionVote {
+            project_execution_id,
+            votes_for: 0,
+            votes_against: 0,
+            status: ProjectExecutionStatus::Active,
+            developer_id,
+            voters: HashSet::new(),
+            quorum: self.internal_quorum(self.config.execution_quorum, self.config.execution_quorum_bps),
+            resolved_at: None,
+            dispute: None,
+            over_budget,
+            budget_override,
+        };
+        self.project_execution_votes.insert(&project_execution_id, &vote);
+    }
+
+    // Adds the voter's power to an execution vote. Returns the outcome once either side reaches the quorum.
+    pub(crate) fn internal_tally_execution_vote(
+        &self,
+        vote: &mut ProjectExecutionVote,
+        voter: &AccountId,
+        approve: bool,
+    ) -> Option<bool> {
+        let revote_open = match &vote.dispute {
+            Some(dispute) => {
+                dispute.status == DisputeStatus::Open
+                    && matches!(dispute.method, DisputeMethod::ReVote { .. })
+            }
+            None => false,
+        };
+        require!(
+            vote.status == ProjectExecutionStatus::Active
+                || (vote.status == ProjectExecutionStatus::Disputed && revote_open),
+            "Project execution is not open for voting"
+        );
+        self.internal_tally(
+            &mut vote.voters,
+            &mut vote.votes_for,
+            &mut vote.votes_against,
+            vote.quorum,
+            voter,
+            approve,
+        )
+    }
+
+    // Creates an open vote for a project plan, used both for new plans and plan amendments
+    pub(crate) fn internal_new_plan_vote(&self, project_plan_id: u64) -> ProjectPlanVote {
+        ProjectPlanVote {
+            project_plan_id,
+            votes_for: 0,
+            votes_against: 0,
+            status: ProjectPlanStatus::Active,
+            voters: HashSet::new(),
+            quorum: self.internal_quorum(self.config.plan_quorum, self.config.plan_quorum_bps),
+        }
+    }
+
+    // Adds the voter's power to a plan vote and updates its status once the quorum is reached
+    pub(crate) fn internal_tally_plan_vote(
+        &self,
+        vote: &mut ProjectPlanVote,
+        voter: &AccountId,
+        approve: bool,
+    ) {
+        require!(vote.status == ProjectPlanStatus::Active, "Project plan is not open for voting");
+        let outcome = self.internal_tally(
+            &mut vote.voters,
+            &mut vote.votes_for,
+            &mut vote.votes_against,
+            vote.quorum,
+            voter,
+            approve,
+        );
+        match outcome {
+            Some(true) => vote.status = ProjectPlanStatus::Approved,
+            Some(false) => vote.status = ProjectPlanStatus::Rejected,
+            None => {}
+        }
+    }
+
+    // Records a weighted vote. Returns the outcome once either side reaches the quorum.
+    pub(crate) fn internal_tally(
+        &self,
+        voters: &mut HashSet<AccountId>,
+        votes_for: &mut u64,
+        votes_against: &mut u64,
+        quorum: u64,
+        voter: &AccountId,
+        approve: bool,
+    ) -> Option<bool> {
+        let voting_power = self.internal_voting_power(voter);
+        require!(voting_power > 0, "Voter has no voting power");
+        require!(voters.insert(voter.clone()), "Voter has already voted");
+
+        if approve {
+            *votes_for += voting_power;
+        } else {
+            *votes_against += voting_power;
+        }
+
+        if *votes_for >= quorum {
+            Some(true)
+        } else if *votes_against >= quorum {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
 /*
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -384,4 +667,67 @@ impl GovernanceDataContract {
         }
     }
 }
-*/
\ No newline at end of file
+*/
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::test_utils::accounts;
+
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn proof(
+        mana_balance: u128,
+        collateral_mana_balance: u128,
+        voting_power: u64,
+        signature: Vec<u8>,
+    ) -> ManaBalancesProof {
+        ManaBalancesProof::new(
+            U128(mana_balance),
+            U128(collateral_mana_balance),
+            voting_power,
+            signature,
+            "aurora".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_verify_aurora_proof() {
+        let contract = setup_contract();
+        let voting = &contract.voting_module;
+        assert!(voting.verify_aurora_proof(proof(100, 50, 5, vec![1; 65]), accounts(1)));
+        assert!(voting.verify_aurora_proof(proof(0, 50, 5, vec![1; 65]), accounts(1)));
+        assert!(!voting.verify_aurora_proof(proof(100, 50, 5, vec![]), accounts(1)));
+        assert!(!voting.verify_aurora_proof(proof(0, 0, 5, vec![1; 65]), accounts(1)));
+        assert!(!voting.verify_aurora_proof(proof(100, 50, 0, vec![1; 65]), accounts(1)));
+    }
+
+    #[test]
+    fn test_governance_data_updates() {
+        let mut contract = setup_contract();
+        let voting = &mut contract.voting_module;
+        assert!(!voting.verify_cross_chain_data(accounts(1), U128(100), U128(0), 5, 1));
+
+        voting.update_governance_data(accounts(1), U128(100), U128(0), 5, 1);
+        assert!(voting.verify_cross_chain_data(accounts(1), U128(100), U128(0), 5, 1));
+        assert!(!voting.verify_cross_chain_data(accounts(1), U128(100), U128(0), 5, 2));
+        assert!(!voting.verify_cross_chain_data(accounts(1), U128(100), U128(0), 6, 1));
+
+        // A newer update replaces the previous data.
+        voting.update_governance_data(accounts(1), U128(300), U128(20), 9, 2);
+        assert!(!voting.verify_cross_chain_data(accounts(1), U128(100), U128(0), 5, 1));
+        assert!(voting.verify_cross_chain_data(accounts(1), U128(300), U128(20), 9, 2));
+        let data = serde_json::to_value(voting.get_governance_data(accounts(1)).unwrap()).unwrap();
+        assert_eq!(data["voting_power"], json!(9));
+        assert_eq!(voting.internal_voting_power(&accounts(1)), 9);
+        assert_eq!(voting.internal_voting_power(&accounts(2)), 0);
+    }
+
+    #[test]
+    fn test_quadratic_weight() {
+        let weights: Vec<u64> = vec![0, 1, 2, 3, 4, 8, 9, 10, 99, 100].into_iter().map(quadratic_weight).collect();
+        assert_eq!(weights, vec![0, 1, 1, 1, 2, 2, 3, 3, 9, 10]);
+        assert_eq!(quadratic_weight(u64::MAX), u32::MAX as u64);
+    }
+}
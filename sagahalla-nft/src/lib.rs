@@ -0,0 +1,379 @@
+//! NFT collection for works curated by sagahalla-dao.
+//! Implements NEP-171 (core), NEP-177 (metadata), NEP-181 (enumeration) and NEP-199 (royalties).
+//! Only the DAO can mint, which it does for works accepted by a curation vote.
+
+use std::collections::HashMap;
+
+use near_contract_standards::non_fungible_token::events::NftMint;
+use near_contract_standards::non_fungible_token::metadata::{
+    NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata,
+};
+use near_contract_standards::non_fungible_token::{NonFungibleToken, Token, TokenId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{
+    assert_one_yocto, env, near_bindgen, require, AccountId, Balance, BorshStorageKey,
+    PanicOnDefault, Promise, PromiseOrValue,
+};
+
+/// Royalties are expressed in basis points of the sale price.
+pub const ROYALTY_DENOMINATOR: u32 = 10_000;
+/// Most accounts a single token can pay royalties to.
+pub const MAX_ROYALTY_ACCOUNTS: usize = 10;
+
+#[derive(BorshStorageKey, BorshSerialize)]
+pub enum StorageKeys {
+    NonFungibleToken,
+    Metadata,
+    TokenMetadata,
+    Enumeration,
+    Approval,
+    Royalties,
+}
+
+/// Payout for a token sale (NEP-199): amount per account, owner included.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    tokens: NonFungibleToken,
+    metadata: LazyOption<NFTContractMetadata>,
+    /// sagahalla-dao account. The only account allowed to mint.
+    dao_id: AccountId,
+    /// Royalty shares per token, in basis points.
+    royalties: LookupMap<TokenId, HashMap<AccountId, u32>>,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(dao_id: AccountId, metadata: NFTContractMetadata) -> Self {
+        metadata.assert_valid();
+        Self {
+            tokens: NonFungibleToken::new(
+                StorageKeys::NonFungibleToken,
+                dao_id.clone(),
+                Some(StorageKeys::TokenMetadata),
+                Some(StorageKeys::Enumeration),
+                Some(StorageKeys::Approval),
+            ),
+            metadata: LazyOption::new(StorageKeys::Metadata, Some(&metadata)),
+            dao_id,
+            royalties: LookupMap::new(StorageKeys::Royalties),
+        }
+    }
+
+    /// Mints a curated work to `receiver_id` with the given royalty split.
+    /// Only the DAO can call this. Attached deposit pays for the storage of the token and its royalties,
+    /// the rest is refunded to `refund_id`, or to the DAO if not given.
+    #[payable]
+    pub fn nft_mint(
+        &mut self,
+        token_id: TokenId,
+        receiver_id: AccountId,
+        token_metadata: TokenMetadata,
+        royalty: HashMap<AccountId, u32>,
+        refund_id: Option<AccountId>,
+    ) -> Token {
+        require!(
+            env::predecessor_account_id() == self.dao_id,
+            "ERR_ONLY_DAO_CAN_MINT"
+        );
+        require!(
+            royalty.len() <= MAX_ROYALTY_ACCOUNTS,
+            "ERR_TOO_MANY_ROYALTY_ACCOUNTS"
+        );
+        require!(
+            royalty.values().sum::<u32>() <= ROYALTY_DENOMINATOR,
+            "ERR_ROYALTY_TOO_HIGH"
+        );
+        let initial_storage_usage = env::storage_usage();
+        self.royalties.insert(&token_id, &royalty);
+        let token = self.tokens.internal_mint_with_refund(
+            token_id,
+            receiver_id,
+            Some(token_metadata),
+            None,
+        );
+        NftMint {
+            owner_id: &token.owner_id,
+            token_ids: &[&token.token_id],
+            memo: None,
+        }
+        .emit();
+
+        let storage_cost =
+            env::storage_byte_cost() * (env::storage_usage() - initial_storage_usage) as Balance;
+        let deposit = env::attached_deposit();
+        require!(deposit >= storage_cost, "ERR_NOT_ENOUGH_DEPOSIT");
+        if deposit > storage_cost {
+            Promise::new(refund_id.unwrap_or_else(env::predecessor_account_id))
+                .transfer(deposit - storage_cost);
+        }
+        token
+    }
+
+    /// Returns the DAO account allowed to mint.
+    pub fn get_dao_id(&self) -> AccountId {
+        self.dao_id.clone()
+    }
+
+    /// Returns royalty split of the token in basis points.
+    pub fn get_royalty(&self, token_id: TokenId) -> HashMap<AccountId, u32> {
+        self.royalties.get(&token_id).unwrap_or_default()
+    }
+
+    /// Splits `balance` between royalty accounts and the current owner (NEP-199).
+    pub fn nft_payout(
+        &self,
+        token_id: TokenId,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(&token_id)
+            .expect("ERR_TOKEN_NOT_FOUND");
+        self.internal_payout(&token_id, &owner_id, balance.0, max_len_payout)
+    }
+
+    /// Transfers the token and returns the payout for the previous owner (NEP-199).
+    #[payable]
+    pub fn nft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        assert_one_yocto();
+        let (previous_owner_id, _) = self.tokens.internal_transfer(
+            &env::predecessor_account_id(),
+            &receiver_id,
+            &token_id,
+            approval_id,
+            memo,
+        );
+        self.internal_payout(&token_id, &previous_owner_id, balance.0, max_len_payout)
+    }
+}
+
+impl Contract {
+    fn internal_payout(
+        &self,
+        token_id: &TokenId,
+        owner_id: &AccountId,
+        balance: u128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        let royalty = self.royalties.get(token_id).unwrap_or_default();
+        if let Some(max_len_payout) = max_len_payout {
+            require!(
+                royalty.len() < max_len_payout as usize,
+                "ERR_PAYOUT_TOO_LONG"
+            );
+        }
+        let mut payout = HashMap::new();
+        let mut royalties_total = 0;
+        for (account_id, bps) in royalty.iter() {
+            if account_id == owner_id {
+                continue;
+            }
+            let amount = balance * *bps as u128 / ROYALTY_DENOMINATOR as u128;
+            royalties_total += amount;
+            payout.insert(account_id.clone(), U128(amount));
+        }
+        payout.insert(owner_id.clone(), U128(balance - royalties_total));
+        Payout { payout }
+    }
+}
+
+near_contract_standards::impl_non_fungible_token_core!(Contract, tokens);
+near_contract_standards::impl_non_fungible_token_approval!(Contract, tokens);
+near_contract_standards::impl_non_fungible_token_enumeration!(Contract, tokens);
+
+#[near_bindgen]
+impl NonFungibleTokenMetadataProvider for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_contract_standards::non_fungible_token::metadata::NFT_METADATA_SPEC;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn contract_metadata() -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "SagaHalla".to_string(),
+            symbol: "SAGA".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn token_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("Yggdrasil".to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: Some(1),
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn mint(context: &mut VMContextBuilder, contract: &mut Contract) {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(10u128.pow(23))
+            .build());
+        let royalty = vec![(accounts(1), 1_000), (accounts(0), 500)]
+            .into_iter()
+            .collect();
+        contract.nft_mint(
+            "0".to_string(),
+            accounts(1),
+            token_metadata(),
+            royalty,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_mint_and_payout() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), contract_metadata());
+        mint(&mut context, &mut contract);
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(1)
+        );
+
+        // Creator owns the token: creator gets everything except the DAO share.
+        let payout = contract
+            .nft_payout("0".to_string(), U128(10_000), None)
+            .payout;
+        assert_eq!(payout.len(), 2);
+        assert_eq!(payout[&accounts(1)], U128(9_500));
+        assert_eq!(payout[&accounts(0)], U128(500));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        let payout = contract
+            .nft_transfer_payout(
+                accounts(2),
+                "0".to_string(),
+                None,
+                None,
+                U128(10_000),
+                Some(3),
+            )
+            .payout;
+        assert_eq!(payout[&accounts(1)], U128(9_500));
+        assert_eq!(
+            contract
+                .nft_payout("0".to_string(), U128(10_000), None)
+                .payout[&accounts(2)],
+            U128(8_500)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ONLY_DAO_CAN_MINT")]
+    fn test_mint_not_dao() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), contract_metadata());
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(10u128.pow(23))
+            .build());
+        contract.nft_mint(
+            "0".to_string(),
+            accounts(1),
+            token_metadata(),
+            HashMap::new(),
+            None,
+        );
+    }
+
+    /// Deposit left after paying for the token and its royalties goes back to the refund account.
+    #[test]
+    fn test_mint_refunds_payer() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), contract_metadata());
+        let deposit = 10u128.pow(23);
+        testing_env!(context.attached_deposit(deposit).build());
+        let storage_usage = env::storage_usage();
+        let royalty = vec![(accounts(1), 1_000)].into_iter().collect();
+        contract.nft_mint(
+            "0".to_string(),
+            accounts(1),
+            token_metadata(),
+            royalty,
+            Some(accounts(2)),
+        );
+
+        let storage_cost =
+            env::storage_byte_cost() * (env::storage_usage() - storage_usage) as Balance;
+        let refunds: Vec<(AccountId, Balance)> = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                        _ => None,
+                    })
+            })
+            .collect();
+        assert_eq!(refunds, vec![(accounts(2), deposit - storage_cost)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_DEPOSIT")]
+    fn test_mint_not_enough_deposit() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), contract_metadata());
+        testing_env!(context.attached_deposit(1).build());
+        contract.nft_mint(
+            "0".to_string(),
+            accounts(1),
+            token_metadata(),
+            HashMap::new(),
+            None,
+        );
+    }
+}
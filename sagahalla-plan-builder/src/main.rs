@@ -0,0 +1,387 @@
+//! Builds the `ProjectPlan` JSON for sagahalla-dao's `submit_project_plan` from a project spreadsheet.
+//!
+//! Each row of the spreadsheet is the mana hours of one role on one task:
+//! `developer_id,mana_token_allocated,sub_project,epic,task,role,mana_hours`.
+//! CSV files need this header row; YAML files are a list of rows with these keys.
+//! Sub-projects, epics and tasks are grouped by name in order of first appearance and get
+//! consecutive ids. Task estimates, developer budgets and the plan total are summed from the rows.
+//!
+//! Usage: sagahalla-plan-builder <plan.csv|plan.yaml> --id <plan_id> --name <project_name> [--created-at <date>]
+
+use std::collections::{HashMap, HashSet};
+use std::{env, fs, process};
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::Deserialize;
+use near_sdk::serde_json::{self, json};
+use near_sdk::AccountId;
+use sagahalla_dao::mana_structs::{
+    DeveloperProjectPlan, EpicPlan, ProjectPlan, SubProjectPlan, TaskPlan, TaskRoleManaHours,
+    TaskStatus,
+};
+
+const COLUMNS: [&str; 7] = [
+    "developer_id",
+    "mana_token_allocated",
+    "sub_project",
+    "epic",
+    "task",
+    "role",
+    "mana_hours",
+];
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+struct PlanRow {
+    developer_id: AccountId,
+    mana_token_allocated: U128,
+    sub_project: String,
+    epic: String,
+    task: String,
+    role: String,
+    mana_hours: u64,
+}
+
+struct PlanArgs {
+    path: String,
+    id: u64,
+    name: String,
+    created_at: String,
+}
+
+fn parse_args(args: &[String]) -> Result<PlanArgs, String> {
+    let mut path = None;
+    let mut options: HashMap<&str, &str> = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--id" | "--name" | "--created-at" => {
+                let value = iter.next().ok_or(format!("missing value for {}", arg))?;
+                options.insert(arg.as_str(), value.as_str());
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    Ok(PlanArgs {
+        path: path.ok_or("missing spreadsheet path")?,
+        id: options
+            .get("--id")
+            .ok_or("missing --id")?
+            .parse()
+            .map_err(|_| "invalid --id".to_string())?,
+        name: options.get("--name").ok_or("missing --name")?.to_string(),
+        created_at: options
+            .get("--created-at")
+            .copied()
+            .unwrap_or("")
+            .to_string(),
+    })
+}
+
+fn parse_csv(input: &str) -> Result<Vec<PlanRow>, String> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<&str> = match lines.next() {
+        Some((_, line)) => line.split(',').map(str::trim).collect(),
+        None => return Err("spreadsheet is empty".to_string()),
+    };
+    if header != COLUMNS {
+        return Err(format!("header must be {}", COLUMNS.join(",")));
+    }
+    lines
+        .map(|(index, line)| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != COLUMNS.len() {
+                return Err(format!(
+                    "line {}: expected {} fields",
+                    index + 1,
+                    COLUMNS.len()
+                ));
+            }
+            let invalid = |column: &str, value: &str| {
+                format!("line {}: invalid {} {}", index + 1, column, value)
+            };
+            Ok(PlanRow {
+                developer_id: fields[0]
+                    .parse()
+                    .map_err(|_| invalid(COLUMNS[0], fields[0]))?,
+                mana_token_allocated: U128(
+                    fields[1]
+                        .parse()
+                        .map_err(|_| invalid(COLUMNS[1], fields[1]))?,
+                ),
+                sub_project: fields[2].to_string(),
+                epic: fields[3].to_string(),
+                task: fields[4].to_string(),
+                role: fields[5].to_string(),
+                mana_hours: fields[6]
+                    .parse()
+                    .map_err(|_| invalid(COLUMNS[6], fields[6]))?,
+            })
+        })
+        .collect()
+}
+
+fn parse_yaml(input: &str) -> Result<Vec<PlanRow>, String> {
+    serde_yaml::from_str(input).map_err(|err| err.to_string())
+}
+
+// Index of the item named `name` in `items`, pushing a new one built by `new` if none exists yet
+fn find_or_push<T>(
+    items: &mut Vec<T>,
+    name: &str,
+    item_name: impl Fn(&T) -> &str,
+    new: impl FnOnce() -> T,
+) -> usize {
+    match items.iter().position(|item| item_name(item) == name) {
+        Some(index) => index,
+        None => {
+            items.push(new());
+            items.len() - 1
+        }
+    }
+}
+
+fn build_plan(
+    rows: Vec<PlanRow>,
+    id: u64,
+    name: String,
+    created_at: String,
+) -> Result<ProjectPlan, String> {
+    if rows.is_empty() {
+        return Err("spreadsheet has no rows".to_string());
+    }
+    let mut developer_order: Vec<AccountId> = vec![];
+    let mut developers: HashMap<AccountId, DeveloperProjectPlan> = HashMap::new();
+    let (mut last_sub_project_id, mut last_epic_id, mut last_task_id, mut last_role_id) =
+        (0, 0, 0, 0);
+    let mut task_roles: HashSet<(u64, String)> = HashSet::new();
+
+    for (index, row) in rows.into_iter().enumerate() {
+        let row_number = index + 1;
+        for (field, value) in [
+            ("sub_project", &row.sub_project),
+            ("epic", &row.epic),
+            ("task", &row.task),
+            ("role", &row.role),
+        ] {
+            if value.trim().is_empty() {
+                return Err(format!("row {}: {} is empty", row_number, field));
+            }
+        }
+        if row.mana_hours == 0 {
+            return Err(format!("row {}: mana_hours must be positive", row_number));
+        }
+
+        let developer = developers
+            .entry(row.developer_id.clone())
+            .or_insert_with(|| {
+                developer_order.push(row.developer_id.clone());
+                DeveloperProjectPlan {
+                    developer_id: row.developer_id.clone(),
+                    mana_hours_budgeted: 0,
+                    mana_token_allocated: row.mana_token_allocated,
+                    sub_projects: vec![],
+                }
+            });
+        if developer.mana_token_allocated != row.mana_token_allocated {
+            return Err(format!(
+                "row {}: mana_token_allocated of {} differs from its earlier rows",
+                row_number, row.developer_id
+            ));
+        }
+
+        let sub_project_index = find_or_push(
+            &mut developer.sub_projects,
+            &row.sub_project,
+            |s| &s.sub_project_name,
+            || {
+                last_sub_project_id += 1;
+                SubProjectPlan {
+                    id: last_sub_project_id,
+                    project_plan_id: id,
+                    sub_project_name: row.sub_project.clone(),
+                    epics: vec![],
+                }
+            },
+        );
+        let sub_project = &mut developer.sub_projects[sub_project_index];
+        let sub_project_id = sub_project.id;
+        let epic_index = find_or_push(
+            &mut sub_project.epics,
+            &row.epic,
+            |e| &e.epic_name,
+            || {
+                last_epic_id += 1;
+                EpicPlan {
+                    id: last_epic_id,
+                    sub_project_plan_id: sub_project_id,
+                    epic_name: row.epic.clone(),
+                    tasks: vec![],
+                }
+            },
+        );
+        let epic = &mut sub_project.epics[epic_index];
+        let epic_id = epic.id;
+        let task_index = find_or_push(
+            &mut epic.tasks,
+            &row.task,
+            |t| &t.task_name,
+            || {
+                last_task_id += 1;
+                TaskPlan {
+                    id: last_task_id,
+                    epic_plan_id: epic_id,
+                    task_name: row.task.clone(),
+                    estimated_mana_hours: 0,
+                    roles_mana_hours: vec![],
+                    status: TaskStatus::Planned,
+                    claim: None,
+                }
+            },
+        );
+        let task = &mut epic.tasks[task_index];
+        if !task_roles.insert((task.id, row.role.clone())) {
+            return Err(format!(
+                "row {}: role {} is listed twice for task {}",
+                row_number, row.role, row.task
+            ));
+        }
+        last_role_id += 1;
+        task.roles_mana_hours.push(TaskRoleManaHours {
+            id: last_role_id,
+            task_id: task.id,
+            role_name: row.role,
+            mana_hours: row.mana_hours,
+        });
+        task.estimated_mana_hours += row.mana_hours;
+        developer.mana_hours_budgeted += row.mana_hours;
+    }
+
+    let total_mana_hours = developer_order
+        .iter()
+        .map(|developer_id| developers[developer_id].mana_hours_budgeted)
+        .sum();
+    Ok(ProjectPlan {
+        id,
+        proposal_id: None,
+        project_name: name,
+        total_mana_hours,
+        voting_power: None,
+        created_at,
+        updated_at: None,
+        developers,
+        proposal: None,
+        version: 0,
+        treasury_proposal: None,
+    })
+}
+
+fn run(args: &[String]) -> Result<String, String> {
+    let args = parse_args(args)?;
+    let input = fs::read_to_string(&args.path)
+        .map_err(|err| format!("failed to read {}: {}", args.path, err))?;
+    let rows = if args.path.ends_with(".yaml") || args.path.ends_with(".yml") {
+        parse_yaml(&input)?
+    } else {
+        parse_csv(&input)?
+    };
+    let plan = build_plan(rows, args.id, args.name, args.created_at)?;
+    Ok(serde_json::to_string_pretty(&json!({ "project_plan": plan })).unwrap())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(call_args) => println!("{}", call_args),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            eprintln!("Usage: sagahalla-plan-builder <plan.csv|plan.yaml> --id <plan_id> --name <project_name> [--created-at <date>]");
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "developer_id,mana_token_allocated,sub_project,epic,task,role,mana_hours
+alice.near,1000,Contracts,Voting,Tally votes,developer,8
+alice.near,1000,Contracts,Voting,Tally votes,reviewer,2
+alice.near,1000,Contracts,Curation,Rounds,developer,5
+bob.near,500,Frontend,Voting,Vote page,designer,3
+";
+
+    #[test]
+    fn test_build_plan_from_csv() {
+        let plan = build_plan(
+            parse_csv(CSV).unwrap(),
+            7,
+            "Voting".to_string(),
+            String::new(),
+        )
+        .unwrap();
+        assert_eq!(plan.total_mana_hours, 18);
+        let alice = &plan.developers[&"alice.near".parse::<AccountId>().unwrap()];
+        assert_eq!(alice.mana_hours_budgeted, 15);
+        assert_eq!(alice.sub_projects.len(), 1);
+        assert_eq!(alice.sub_projects[0].epics.len(), 2);
+        let task = &alice.sub_projects[0].epics[0].tasks[0];
+        assert_eq!(
+            (
+                task.id,
+                task.estimated_mana_hours,
+                task.roles_mana_hours.len()
+            ),
+            (1, 10, 2)
+        );
+        assert!(task
+            .roles_mana_hours
+            .iter()
+            .all(|role| role.task_id == task.id));
+
+        let mut task_ids: Vec<u64> = plan.tasks().map(|task| task.id).collect();
+        task_ids.sort_unstable();
+        assert_eq!(task_ids, vec![1, 2, 3]);
+        // Same epic name under another developer's sub-project is a separate epic.
+        let bob = &plan.developers[&"bob.near".parse::<AccountId>().unwrap()];
+        assert_eq!(bob.sub_projects[0].epics[0].id, 3);
+    }
+
+    #[test]
+    fn test_yaml_matches_csv() {
+        let yaml = "
+- { developer_id: alice.near, mana_token_allocated: '1000', sub_project: Contracts, epic: Voting, task: Tally votes, role: developer, mana_hours: 8 }
+";
+        let plan = build_plan(
+            parse_yaml(yaml).unwrap(),
+            1,
+            "Voting".to_string(),
+            String::new(),
+        )
+        .unwrap();
+        assert_eq!(plan.total_mana_hours, 8);
+    }
+
+    #[test]
+    fn test_invalid_rows() {
+        let build = |csv: &str| build_plan(parse_csv(csv)?, 1, String::new(), String::new());
+        assert!(build("developer_id,task\n").is_err());
+        let header = COLUMNS.join(",");
+        assert!(build(&format!("{}\nalice.near,1,S,E,T,dev,0\n", header)).is_err());
+        assert!(build(&format!(
+            "{}\nalice.near,1,S,E,T,dev,1\nalice.near,2,S,E,T2,dev,1\n",
+            header
+        ))
+        .is_err());
+        assert!(build(&format!(
+            "{}\nalice.near,1,S,E,T,dev,1\nalice.near,1,S,E,T,dev,1\n",
+            header
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Builds the Merkle tree of a sagahalla-dao balance snapshot.
+//!
+//! Reads a CSV of `account_id,mana_balance,collateral_mana_balance,voting_power` rows (with a header)
+//! and prints JSON with the root to pass to `propose_snapshot` and, for each holder, the arguments
+//! of their `activate_snapshot_balances` call.
+//!
+//! Usage: sagahalla-snapshot <balances.csv>
+
+use std::collections::HashSet;
+use std::{env, fs, process};
+
+use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{AccountId, CryptoHash};
+use sagahalla_dao::snapshots::{hash_pair, SnapshotLeaf};
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct HolderProof {
+    account_id: AccountId,
+    mana_balance: U128,
+    collateral_mana_balance: U128,
+    voting_power: u64,
+    proof: Vec<Base58CryptoHash>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct Snapshot {
+    root: Base58CryptoHash,
+    holders: Vec<HolderProof>,
+}
+
+fn parse_csv(input: &str) -> Result<Vec<SnapshotLeaf>, String> {
+    let mut leaves = vec![];
+    let mut accounts = HashSet::new();
+    for (index, line) in input.lines().enumerate().skip(1) {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 4 {
+            return Err(format!("line {}: expected 4 fields, got {}", line_number, fields.len()));
+        }
+        let account_id: AccountId = fields[0]
+            .parse()
+            .map_err(|_| format!("line {}: invalid account id {}", line_number, fields[0]))?;
+        if !accounts.insert(account_id.clone()) {
+            return Err(format!("line {}: duplicate account {}", line_number, account_id));
+        }
+        let number = |field: &str, name: &str| {
+            field
+                .parse::<u128>()
+                .map_err(|_| format!("line {}: invalid {} {}", line_number, name, field))
+        };
+        leaves.push(SnapshotLeaf {
+            account_id,
+            mana_balance: U128(number(fields[1], "mana_balance")?),
+            collateral_mana_balance: U128(number(fields[2], "collateral_mana_balance")?),
+            voting_power: fields[3]
+                .parse()
+                .map_err(|_| format!("line {}: invalid voting_power {}", line_number, fields[3]))?,
+        });
+    }
+    if leaves.is_empty() {
+        return Err("no holders in snapshot".to_string());
+    }
+    Ok(leaves)
+}
+
+/// Levels of the tree from the leaves up to the root. A node without a sibling moves up unchanged.
+fn build_tree(leaves: Vec<CryptoHash>) -> Vec<Vec<CryptoHash>> {
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let level = levels.last().unwrap();
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_pair(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+fn proof(levels: &[Vec<CryptoHash>], mut index: usize) -> Vec<Base58CryptoHash> {
+    let mut proof = vec![];
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push((*sibling).into());
+        }
+        index /= 2;
+    }
+    proof
+}
+
+fn build_snapshot(leaves: Vec<SnapshotLeaf>) -> Snapshot {
+    let levels = build_tree(leaves.iter().map(SnapshotLeaf::hash).collect());
+    let root = levels.last().unwrap()[0];
+    let holders = leaves
+        .into_iter()
+        .enumerate()
+        .map(|(index, leaf)| HolderProof {
+            account_id: leaf.account_id,
+            mana_balance: leaf.mana_balance,
+            collateral_mana_balance: leaf.collateral_mana_balance,
+            voting_power: leaf.voting_power,
+            proof: proof(&levels, index),
+        })
+        .collect();
+    Snapshot {
+        root: root.into(),
+        holders,
+    }
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: sagahalla-snapshot <balances.csv>");
+            process::exit(1);
+        }
+    };
+    let input = fs::read_to_string(&path).unwrap_or_else(|err| {
+        eprintln!("Failed to read {}: {}", path, err);
+        process::exit(1);
+    });
+    let leaves = parse_csv(&input).unwrap_or_else(|err| {
+        eprintln!("Invalid snapshot CSV: {}", err);
+        process::exit(1);
+    });
+    let snapshot = build_snapshot(leaves);
+    println!("{}", serde_json::to_string_pretty(&snapshot).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use sagahalla_dao::snapshots::merkle_root;
+
+    use super::*;
+
+    const CSV: &str = "account_id,mana_balance,collateral_mana_balance,voting_power
+alice.near,100,10,5
+bob.near,200,0,7
+carol.near,0,50,3
+";
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        let leaves = parse_csv(CSV).unwrap();
+        let hashes: Vec<CryptoHash> = leaves.iter().map(SnapshotLeaf::hash).collect();
+        let snapshot = build_snapshot(leaves);
+        let root: CryptoHash = snapshot.root.into();
+        assert_eq!(snapshot.holders.len(), 3);
+        for (holder, leaf) in snapshot.holders.iter().zip(hashes) {
+            assert_eq!(merkle_root(leaf, &holder.proof), root);
+        }
+        // Tampered balances don't verify.
+        let forged = SnapshotLeaf {
+            account_id: "alice.near".parse().unwrap(),
+            mana_balance: U128(1_000),
+            collateral_mana_balance: U128(10),
+            voting_power: 5,
+        };
+        assert_ne!(merkle_root(forged.hash(), &snapshot.holders[0].proof), root);
+    }
+
+    #[test]
+    fn test_invalid_csv() {
+        assert!(parse_csv("account_id,mana\n").is_err());
+        assert!(parse_csv("header\nalice.near,1,2\n").is_err());
+        assert!(parse_csv("header\nalice.near,1,2,3\nalice.near,1,2,3\n").is_err());
+    }
+}
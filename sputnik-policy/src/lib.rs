@@ -0,0 +1,195 @@
+//! Role based permission engine of sputnikdao2, shared with other contracts.
+//! Permissions are `<kind>:<action>` strings, where either side can be `*`.
+
+use std::collections::{HashMap, HashSet};
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, Balance};
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub enum RoleKind {
+    /// Matches everyone, who is not matched by other roles.
+    Everyone,
+    /// Member greater or equal than given balance. Can use `1` as non-zero balance.
+    Member(U128),
+    /// Set of accounts.
+    Group(HashSet<AccountId>),
+}
+
+impl RoleKind {
+    /// Checks if user matches given role.
+    pub fn match_user(&self, user: &UserInfo) -> bool {
+        match self {
+            RoleKind::Everyone => true,
+            RoleKind::Member(amount) => user.amount >= amount.0,
+            RoleKind::Group(accounts) => accounts.contains(&user.account_id),
+        }
+    }
+
+    /// Returns the number of people in the this role or None if not supported role kind.
+    pub fn get_role_size(&self) -> Option<usize> {
+        match self {
+            RoleKind::Group(accounts) => Some(accounts.len()),
+            _ => None,
+        }
+    }
+
+    /// Adds member to the group. Returns false if this role is not a group.
+    pub fn add_member_to_group(&mut self, member_id: &AccountId) -> bool {
+        match self {
+            RoleKind::Group(accounts) => {
+                accounts.insert(member_id.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes member from the group. Returns false if this role is not a group.
+    pub fn remove_member_from_group(&mut self, member_id: &AccountId) -> bool {
+        match self {
+            RoleKind::Group(accounts) => {
+                accounts.remove(member_id);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Role granting `<kind>:<action>` permissions to the users matching its kind.
+/// Contracts can attach their own settings to roles by implementing this on their role type.
+pub trait Role {
+    /// Name of the role to display to the user.
+    fn name(&self) -> &str;
+    /// Kind of the role: defines which users this permissions apply.
+    fn kind(&self) -> &RoleKind;
+    /// Set of actions on which kinds that this role is allowed to execute.
+    fn permissions(&self) -> &HashSet<String>;
+
+    /// Checks if this role allows `action` on `kind`, directly or through a wildcard.
+    fn allows(&self, kind: &str, action: &str) -> bool {
+        let permissions = self.permissions();
+        permissions.contains(&format!("{}:{}", kind, action))
+            || permissions.contains(&format!("{}:*", kind))
+            || permissions.contains(&format!("*:{}", action))
+            || permissions.contains("*:*")
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct RolePermission {
+    /// Name of the role to display to the user.
+    pub name: String,
+    /// Kind of the role: defines which users this permissions apply.
+    pub kind: RoleKind,
+    /// Set of actions on which kinds that this role is allowed to execute.
+    /// <kind>:<action>
+    pub permissions: HashSet<String>,
+}
+
+impl Role for RolePermission {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &RoleKind {
+        &self.kind
+    }
+
+    fn permissions(&self) -> &HashSet<String> {
+        &self.permissions
+    }
+}
+
+pub struct UserInfo {
+    pub account_id: AccountId,
+    pub amount: Balance,
+}
+
+/// Returns set of roles that this user is member of permissions for given user across all the roles it's member of.
+pub fn get_user_roles<'a, R: Role>(roles: &'a [R], user: &UserInfo) -> HashMap<String, &'a R> {
+    roles
+        .iter()
+        .filter(|role| role.kind().match_user(user))
+        .map(|role| (role.name().to_string(), role))
+        .collect()
+}
+
+/// Can given user execute given action on given kind.
+/// Returns all roles that allow this action.
+pub fn can_execute_action<R: Role>(
+    roles: &[R],
+    user: UserInfo,
+    kind: &str,
+    action: &str,
+) -> (Vec<String>, bool) {
+    let allowed_roles: Vec<String> = get_user_roles(roles, &user)
+        .into_iter()
+        .filter(|(_, role)| role.allows(kind, action))
+        .map(|(name, _)| name)
+        .collect();
+    let allowed = !allowed_roles.is_empty();
+    (allowed_roles, allowed)
+}
+
+/// Returns role with given name.
+pub fn get_role<'a, R: Role>(roles: &'a [R], name: &str) -> Option<&'a R> {
+    roles.iter().find(|role| role.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+
+    use super::*;
+
+    fn role(name: &str, kind: RoleKind, permissions: &[&str]) -> RolePermission {
+        RolePermission {
+            name: name.to_string(),
+            kind,
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_group_members() {
+        let mut group = RoleKind::Group(HashSet::default());
+        assert!(group.add_member_to_group(&accounts(0)));
+        assert_eq!(group.get_role_size(), Some(1));
+        assert!(group.remove_member_from_group(&accounts(0)));
+        assert_eq!(group.get_role_size(), Some(0));
+        assert!(!RoleKind::Everyone.add_member_to_group(&accounts(0)));
+        assert!(!RoleKind::Member(U128(1)).remove_member_from_group(&accounts(0)));
+    }
+
+    #[test]
+    fn test_can_execute_action() {
+        let roles = vec![
+            role("all", RoleKind::Everyone, &["plan:Submit"]),
+            role(
+                "council",
+                RoleKind::Group(vec![accounts(0)].into_iter().collect()),
+                &["*:Vote", "execution:*"],
+            ),
+            role("holders", RoleKind::Member(U128(10)), &["*:*"]),
+        ];
+        let user = |account_id, amount| UserInfo { account_id, amount };
+
+        assert_eq!(
+            can_execute_action(&roles, user(accounts(1), 0), "plan", "Submit"),
+            (vec!["all".to_string()], true)
+        );
+        assert!(!can_execute_action(&roles, user(accounts(1), 0), "plan", "Vote").1);
+        assert!(can_execute_action(&roles, user(accounts(0), 0), "plan", "Vote").1);
+        assert!(can_execute_action(&roles, user(accounts(0), 0), "execution", "Review").1);
+        assert!(!can_execute_action(&roles, user(accounts(0), 0), "feedback", "Record").1);
+        assert!(can_execute_action(&roles, user(accounts(1), 10), "feedback", "Record").1);
+    }
+}
@@ -157,6 +157,7 @@ impl Contract {
     }
 
     /// Report that bounty is done. Creates a proposal to vote for paying out the bounty.
+    /// If the proposal bond is paid in a token, the proposal has no bond besides the bounty bond.
     /// Only creator of the claim can call `done` on bounty that is still in progress.
     /// On expired, anyone can call it to free up the claim slot.
     #[payable]
@@ -174,13 +175,26 @@ impl Contract {
                 env::predecessor_account_id(),
                 "ERR_BOUNTY_DONE_MUST_BE_SELF"
             );
-            self.add_proposal(ProposalInput {
+            let proposal = ProposalInput {
                 description,
                 kind: ProposalKind::BountyDone {
                     bounty_id: id,
                     receiver_id: sender_id.clone(),
                 },
-            });
+            };
+            if self
+                .policy
+                .get()
+                .unwrap()
+                .to_policy()
+                .proposal_bond_token
+                .is_some()
+            {
+                // Bond in a token can't be attached here. The bounty bond already backs the claim.
+                self.internal_add_proposal(sender_id.clone(), proposal, None, 0);
+            } else {
+                self.add_proposal(proposal);
+            }
             claims[claim_idx].completed = true;
             self.bounty_claimers.insert(&sender_id, &claims);
         }
@@ -209,6 +223,7 @@ impl Contract {
 
 #[cfg(test)]
 mod tests {
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
     use near_sdk::test_utils::{accounts, VMContextBuilder};
     use near_sdk::testing_env;
     use near_sdk_sim::to_yocto;
@@ -300,6 +315,45 @@ mod tests {
         assert_eq!(contract.get_bounty(0).bounty.times, 0);
     }
 
+    /// Bounty is completed in a DAO that takes proposal bonds in a token.
+    #[test]
+    fn test_bounty_done_with_token_bond() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        policy.to_policy_mut().proposal_bond = U128(10);
+        policy.to_policy_mut().proposal_bond_token = Some(accounts(3));
+        let mut contract = Contract::new(Config::test_config(), policy);
+        let msg = near_sdk::serde_json::to_string(&ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::AddBounty {
+                bounty: Bounty {
+                    description: "test bounty".to_string(),
+                    token: String::from(OLD_BASE_TOKEN),
+                    amount: U128(to_yocto("10")),
+                    times: 1,
+                    max_deadline: U64::from(1_000),
+                },
+            },
+        })
+        .unwrap();
+        contract.ft_on_transfer(accounts(1), U128(10), msg);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.act_proposal(0, Action::VoteApprove, None);
+        contract.bounty_claim(0, U64::from(500));
+        testing_env!(context.attached_deposit(0).build());
+        contract.bounty_done(0, None, "Bounty is done".to_string());
+
+        let proposal = contract.get_proposal(1).proposal;
+        assert_eq!(proposal.kind.to_policy_label(), "bounty_done");
+        assert_eq!(proposal.bond, Some(U128(0)));
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+    }
+
     #[test]
     #[should_panic(expected = "ERR_BOUNTY_ALL_CLAIMED")]
     fn test_bounty_claim_not_allowed() {
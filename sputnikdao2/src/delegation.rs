@@ -1,9 +1,109 @@
+use near_sdk::collections::Vector;
+
 use crate::*;
 
+/// Delegated amount of an account, or total delegated amount, from `timestamp` on.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct Checkpoint {
+    pub timestamp: u64,
+    pub amount: Balance,
+}
+
+/// Checkpoints ordered by timestamp, in a `Vector` per account and for the total.
+trait Checkpoints {
+    fn amount_before(&self, timestamp: u64) -> Option<Balance>;
+    fn record(&mut self, prev_amount: Balance, amount: Balance);
+}
+
+impl Checkpoints for Vector<Checkpoint> {
+    /// Amount of the last checkpoint written strictly before `timestamp`, found by binary search.
+    /// None if there are no checkpoints.
+    fn amount_before(&self, timestamp: u64) -> Option<Balance> {
+        if self.is_empty() {
+            return None;
+        }
+        let checkpoint = |index| self.get(index).expect("ERR_NO_CHECKPOINT");
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if checkpoint(mid).timestamp < timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Some(match low.checked_sub(1) {
+            Some(index) => checkpoint(index).amount,
+            None => 0,
+        })
+    }
+
+    /// Records `amount` from the current block on, overwriting a checkpoint of the same block.
+    /// The first checkpoint is preceded by `prev_amount` from timestamp 0, for delegations made
+    /// before checkpoints were recorded.
+    fn record(&mut self, prev_amount: Balance, amount: Balance) {
+        let timestamp = env::block_timestamp();
+        let checkpoint = Checkpoint { timestamp, amount };
+        match self.len().checked_sub(1) {
+            Some(last) if self.get(last).expect("ERR_NO_CHECKPOINT").timestamp == timestamp => {
+                self.replace(last, &checkpoint);
+            }
+            Some(_) => self.push(&checkpoint),
+            None => {
+                self.push(&Checkpoint {
+                    timestamp: 0,
+                    amount: prev_amount,
+                });
+                self.push(&checkpoint);
+            }
+        }
+    }
+}
+
 impl Contract {
     pub fn get_user_weight(&self, account_id: &AccountId) -> Balance {
         self.delegations.get(account_id).unwrap_or_default()
     }
+
+    /// Delegated weight of the account before the block at `timestamp`.
+    /// Votes are weighed as of the proposal submission, so delegating mid-vote has no effect.
+    pub fn get_user_weight_at(&self, account_id: &AccountId, timestamp: u64) -> Balance {
+        self.delegation_checkpoints
+            .get(account_id)
+            .and_then(|checkpoints| checkpoints.amount_before(timestamp))
+            // No checkpoints: delegation didn't change since they are recorded.
+            .unwrap_or_else(|| self.get_user_weight(account_id))
+    }
+
+    /// Total delegated amount before the block at `timestamp`.
+    pub fn get_total_delegation_at(&self, timestamp: u64) -> Balance {
+        self.total_delegation_checkpoints
+            .amount_before(timestamp)
+            .unwrap_or(self.total_delegation_amount)
+    }
+
+    /// Records checkpoints of the account's new delegated amount and of the new total.
+    fn internal_checkpoint_delegation(
+        &mut self,
+        account_id: &AccountId,
+        prev_amount: Balance,
+        prev_total: Balance,
+    ) {
+        let mut checkpoints = self
+            .delegation_checkpoints
+            .get(account_id)
+            .unwrap_or_else(|| {
+                Vector::new(StorageKeys::AccountDelegationCheckpoints {
+                    account_id: account_id.clone(),
+                })
+            });
+        checkpoints.record(prev_amount, self.get_user_weight(account_id));
+        // Stored again, as the `Vector` keeps its length in the map.
+        self.delegation_checkpoints.insert(account_id, &checkpoints);
+        self.total_delegation_checkpoints
+            .record(prev_total, self.total_delegation_amount);
+    }
 }
 
 #[near_bindgen]
@@ -34,8 +134,10 @@ impl Contract {
             .get(account_id)
             .expect("ERR_NOT_REGISTERED");
         let new_amount = prev_amount + amount.0;
+        let prev_total = self.total_delegation_amount;
         self.delegations.insert(account_id, &new_amount);
         self.total_delegation_amount += amount.0;
+        self.internal_checkpoint_delegation(account_id, prev_amount, prev_total);
         (
             U128(prev_amount),
             U128(new_amount),
@@ -55,8 +157,10 @@ impl Contract {
         let prev_amount = self.delegations.get(account_id).unwrap_or_default();
         assert!(prev_amount >= amount.0, "ERR_INVALID_STAKING_CONTRACT");
         let new_amount = prev_amount - amount.0;
+        let prev_total = self.total_delegation_amount;
         self.delegations.insert(account_id, &new_amount);
         self.total_delegation_amount -= amount.0;
+        self.internal_checkpoint_delegation(account_id, prev_amount, prev_total);
         (
             U128(prev_amount),
             U128(new_amount),
@@ -64,3 +168,123 @@ impl Contract {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use near_sdk_sim::to_yocto;
+
+    use crate::policy::WeightKind;
+    use crate::proposals::ProposalStatus;
+    use crate::{Action, Config, ProposalInput, ProposalKind, VersionedPolicy};
+
+    use super::*;
+
+    /// DAO with bob and charlie as token weighted council and accounts(0) as staking contract.
+    fn setup_contract(context: &mut VMContextBuilder) -> Contract {
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1), accounts(2)]).upgrade();
+        policy.to_policy_mut().default_vote_policy.weight_kind = WeightKind::TokenWeight;
+        let mut contract = Contract::new(Config::test_config(), policy);
+        contract.staking_id = Some(accounts(0));
+        testing_env!(context
+            .attached_deposit(16 * env::storage_byte_cost())
+            .build());
+        for account_id in [accounts(1), accounts(2)].iter() {
+            contract.register_delegation(account_id);
+        }
+        contract
+    }
+
+    fn delegate_at(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        timestamp: u64,
+        account_id: AccountId,
+        amount: Balance,
+    ) {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(timestamp)
+            .build());
+        contract.delegate(&account_id, U128(amount));
+    }
+
+    #[test]
+    fn test_delegation_checkpoints() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        delegate_at(&mut context, &mut contract, 10, accounts(1), 100);
+        delegate_at(&mut context, &mut contract, 10, accounts(1), 20);
+        delegate_at(&mut context, &mut contract, 20, accounts(2), 50);
+        testing_env!(context.block_timestamp(30).build());
+        contract.undelegate(&accounts(1), U128(90));
+
+        let weights: Vec<Balance> = [10, 11, 30, 31]
+            .iter()
+            .map(|timestamp| contract.get_user_weight_at(&accounts(1), *timestamp))
+            .collect();
+        assert_eq!(weights, vec![0, 120, 120, 30]);
+        let totals: Vec<Balance> = [10, 11, 21, 31]
+            .iter()
+            .map(|timestamp| contract.get_total_delegation_at(*timestamp))
+            .collect();
+        assert_eq!(totals, vec![0, 120, 170, 80]);
+        // Same block delegations share one checkpoint, after the initial one.
+        assert_eq!(
+            contract
+                .delegation_checkpoints
+                .get(&accounts(1))
+                .unwrap()
+                .to_vec(),
+            vec![
+                Checkpoint {
+                    timestamp: 0,
+                    amount: 0
+                },
+                Checkpoint {
+                    timestamp: 10,
+                    amount: 120
+                },
+                Checkpoint {
+                    timestamp: 30,
+                    amount: 30
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vote_weighed_at_submission() {
+        let mut context = VMContextBuilder::new();
+        let mut contract = setup_contract(&mut context);
+        delegate_at(&mut context, &mut contract, 10, accounts(1), 100);
+        delegate_at(&mut context, &mut contract, 10, accounts(2), 100);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(20)
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let id = contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::Vote,
+        });
+
+        // Stake delegated after submission neither counts as votes nor as supply.
+        delegate_at(&mut context, &mut contract, 30, accounts(2), 1_000);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.act_proposal(id, Action::VoteApprove, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::InProgress);
+        assert_eq!(proposal.vote_counts["council"][0], 100);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::VoteApprove, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+}
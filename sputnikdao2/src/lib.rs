@@ -1,5 +1,5 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, Vector};
 use near_sdk::json_types::{Base58CryptoHash, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
@@ -8,19 +8,28 @@ use near_sdk::{
 };
 
 pub use crate::bounties::{Bounty, BountyClaim, VersionedBounty};
+pub use crate::delegation::Checkpoint;
+use crate::policy::OldVersionedPolicy;
 pub use crate::policy::{
-    default_policy, Policy, RoleKind, RolePermission, VersionedPolicy, VotePolicy,
+    default_policy, BondSlashing, Policy, RoleKind, RolePermission, VersionedPolicy, VotePolicy,
 };
 use crate::proposals::VersionedProposal;
-pub use crate::proposals::{Proposal, ProposalInput, ProposalKind, ProposalStatus};
+pub use crate::proposals::{BatchKinds, Proposal, ProposalInput, ProposalKind, ProposalStatus};
+pub use crate::recurring_payments::{RecurringPayment, VersionedRecurringPayment};
+pub use crate::streams::{Stream, VersionedStream};
 pub use crate::types::{Action, Config, OldAccountId, OLD_BASE_TOKEN};
 use crate::upgrade::{internal_get_factory_info, internal_set_factory_info, FactoryInfo};
-pub use crate::views::{BountyOutput, ProposalOutput};
+pub use crate::views::{
+    BountyOutput, ProposalOutput, RecurringPaymentOutput, StreamOutput, TreasuryBalance,
+};
 
 mod bounties;
 mod delegation;
 mod policy;
 mod proposals;
+mod recurring_payments;
+mod streams;
+mod treasury;
 mod types;
 mod upgrade;
 pub mod views;
@@ -35,6 +44,13 @@ pub enum StorageKeys {
     BountyClaimers,
     BountyClaimCounts,
     Blobs,
+    DelegationCheckpoints,
+    TotalDelegationCheckpoints,
+    Streams,
+    RecurringPayments,
+    TokenBalances,
+    ReservedAmounts,
+    AccountDelegationCheckpoints { account_id: AccountId },
 }
 
 /// After payouts, allows a callback
@@ -42,6 +58,14 @@ pub enum StorageKeys {
 pub trait ExtSelf {
     /// Callback after proposal execution.
     fn on_proposal_callback(&mut self, proposal_id: u64) -> PromiseOrValue<()>;
+    /// Callback after an asynchronous step of a batch proposal.
+    fn on_batch_callback(&mut self, proposal_id: u64, next_step: u64) -> PromiseOrValue<()>;
+    /// Callback with the balance of the token held by the DAO.
+    fn on_token_balance(&mut self, token_id: AccountId);
+    /// Callback after the stream claim was paid out.
+    fn on_claim_stream(&mut self, id: u64, amount: U128);
+    /// Callback after the recurring payment was paid out.
+    fn on_recurring_payment(&mut self, id: u64);
 }
 
 #[near_bindgen]
@@ -61,6 +85,10 @@ pub struct Contract {
     pub total_delegation_amount: Balance,
     /// Delegations per user.
     pub delegations: LookupMap<AccountId, Balance>,
+    /// History of delegations per user, to weigh votes as of the proposal submission.
+    pub delegation_checkpoints: LookupMap<AccountId, Vector<Checkpoint>>,
+    /// History of the delegated token total amount.
+    pub total_delegation_checkpoints: Vector<Checkpoint>,
 
     /// Last available id for the proposals.
     pub last_proposal_id: u64,
@@ -76,26 +104,69 @@ pub struct Contract {
     /// Count of claims per bounty.
     pub bounty_claims_count: LookupMap<u64, u32>,
 
+    /// Last available id for the streams.
+    pub last_stream_id: u64,
+    /// Streams map from ID to stream information.
+    pub streams: LookupMap<u64, VersionedStream>,
+
+    /// Last available id for the recurring payments.
+    pub last_recurring_payment_id: u64,
+    /// Recurring payments map from ID to payment schedule.
+    pub recurring_payments: LookupMap<u64, VersionedRecurringPayment>,
+
+    /// Balances of tokens held by the DAO.
+    pub token_balances: UnorderedMap<OldAccountId, Balance>,
+    /// Amounts per token reserved by pending proposals. "" is used for $NEAR.
+    pub reserved_amounts: LookupMap<OldAccountId, Balance>,
+
     /// Large blob storage.
     pub blobs: LookupMap<CryptoHash, AccountId>,
 }
 
+/// State layout before delegation checkpoints, read by `migrate`.
+#[derive(BorshDeserialize)]
+struct OldContract {
+    config: LazyOption<Config>,
+    policy: LazyOption<OldVersionedPolicy>,
+    locked_amount: Balance,
+    staking_id: Option<AccountId>,
+    total_delegation_amount: Balance,
+    delegations: LookupMap<AccountId, Balance>,
+    last_proposal_id: u64,
+    proposals: LookupMap<u64, VersionedProposal>,
+    last_bounty_id: u64,
+    bounties: LookupMap<u64, VersionedBounty>,
+    bounty_claimers: LookupMap<AccountId, Vec<BountyClaim>>,
+    bounty_claims_count: LookupMap<u64, u32>,
+    blobs: LookupMap<CryptoHash, AccountId>,
+}
+
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn new(config: Config, policy: VersionedPolicy) -> Self {
+        let policy = policy.upgrade();
+        policy.clone().to_policy().assert_valid();
         let this = Self {
             config: LazyOption::new(StorageKeys::Config, Some(&config)),
-            policy: LazyOption::new(StorageKeys::Policy, Some(&policy.upgrade())),
+            policy: LazyOption::new(StorageKeys::Policy, Some(&policy)),
             staking_id: None,
             total_delegation_amount: 0,
             delegations: LookupMap::new(StorageKeys::Delegations),
+            delegation_checkpoints: LookupMap::new(StorageKeys::DelegationCheckpoints),
+            total_delegation_checkpoints: Vector::new(StorageKeys::TotalDelegationCheckpoints),
             last_proposal_id: 0,
             proposals: LookupMap::new(StorageKeys::Proposals),
             last_bounty_id: 0,
             bounties: LookupMap::new(StorageKeys::Bounties),
             bounty_claimers: LookupMap::new(StorageKeys::BountyClaimers),
             bounty_claims_count: LookupMap::new(StorageKeys::BountyClaimCounts),
+            last_stream_id: 0,
+            streams: LookupMap::new(StorageKeys::Streams),
+            last_recurring_payment_id: 0,
+            recurring_payments: LookupMap::new(StorageKeys::RecurringPayments),
+            token_balances: UnorderedMap::new(StorageKeys::TokenBalances),
+            reserved_amounts: LookupMap::new(StorageKeys::ReservedAmounts),
             blobs: LookupMap::new(StorageKeys::Blobs),
             locked_amount: 0,
         };
@@ -107,14 +178,39 @@ impl Contract {
     }
 
     /// Should only be called by this contract on migration.
-    /// This is NOOP implementation. KEEP IT if you haven't changed contract state.
-    /// If you have changed state, you need to implement migration from old state (keep the old struct with different name to deserialize it first).
-    /// After migrate goes live on MainNet, return this implementation for next updates.
+    /// Migrates from the state before delegation checkpoints. Delegations made before are recorded
+    /// as the first checkpoint of an account once its delegation changes.
+    /// Policy is rewritten with votes unlocked, no timelocks, no guardian, bonds in $NEAR and bonds of
+    /// removed proposals kept in the treasury. Roles voting on a kind of proposals can retract votes on it.
+    /// After migrate goes live on MainNet, return the NOOP implementation for next updates.
     #[private]
     #[init(ignore_state)]
     pub fn migrate() -> Self {
-        let this: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-        this
+        let old: OldContract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+        let policy: VersionedPolicy = old.policy.get().expect("ERR_NO_POLICY").into();
+        Self {
+            config: old.config,
+            policy: LazyOption::new(StorageKeys::Policy, Some(&policy)),
+            locked_amount: old.locked_amount,
+            staking_id: old.staking_id,
+            total_delegation_amount: old.total_delegation_amount,
+            delegations: old.delegations,
+            delegation_checkpoints: LookupMap::new(StorageKeys::DelegationCheckpoints),
+            total_delegation_checkpoints: Vector::new(StorageKeys::TotalDelegationCheckpoints),
+            last_proposal_id: old.last_proposal_id,
+            proposals: old.proposals,
+            last_bounty_id: old.last_bounty_id,
+            bounties: old.bounties,
+            bounty_claimers: old.bounty_claimers,
+            bounty_claims_count: old.bounty_claims_count,
+            last_stream_id: 0,
+            streams: LookupMap::new(StorageKeys::Streams),
+            last_recurring_payment_id: 0,
+            recurring_payments: LookupMap::new(StorageKeys::RecurringPayments),
+            token_balances: UnorderedMap::new(StorageKeys::TokenBalances),
+            reserved_amounts: LookupMap::new(StorageKeys::ReservedAmounts),
+            blobs: old.blobs,
+        }
     }
 
     /// Remove blob from contract storage and pay back to original storer.
@@ -173,11 +269,15 @@ pub extern "C" fn store_blob() {
 
 #[cfg(test)]
 mod tests {
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::testing_env;
+    use std::collections::{HashMap, HashSet};
+
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
     use near_sdk_sim::to_yocto;
 
-    use crate::proposals::ProposalStatus;
+    use crate::policy::WeightOrRatio;
+    use crate::proposals::{PolicyParameters, ProposalStatus};
 
     use super::*;
 
@@ -188,7 +288,7 @@ mod tests {
             kind: ProposalKind::Transfer {
                 token_id: String::from(OLD_BASE_TOKEN),
                 receiver_id: accounts(2).into(),
-                amount: U128(to_yocto("100")),
+                amount: U128(to_yocto("10")),
                 msg: None,
             },
         })
@@ -238,6 +338,47 @@ mod tests {
         });
     }
 
+    /// Decodes `ChangePolicy` proposal stored by the contract before the upgrade.
+    #[test]
+    fn test_migrate_change_policy_proposal() {
+        let bytes = vec![
+            // VersionedProposal::Default, proposer and description.
+            (0u8, accounts(1), "policy".to_string()).try_to_vec(),
+            // ProposalKind::ChangePolicy with VersionedPolicy::Current.
+            (1u8, 1u8).try_to_vec(),
+            // Single council role with everything allowed and no vote policies.
+            (1u32, "council".to_string(), 2u8, vec![accounts(1)]).try_to_vec(),
+            (vec!["*:*".to_string()], 0u32).try_to_vec(),
+            // Default vote policy: role weight, no quorum, half of the votes.
+            (1u8, 0u128, 1u8, 1u64, 2u64).try_to_vec(),
+            // Proposal bond and period, bounty bond and forgiveness period.
+            (10u128, 100u64, 1u128, 200u64).try_to_vec(),
+            // Status, vote counts, votes and submission time.
+            (0u8, 0u32, 0u32, 5u64).try_to_vec(),
+        ]
+        .into_iter()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .concat();
+
+        let proposal: Proposal = VersionedProposal::try_from_slice(&bytes).unwrap().into();
+        assert_eq!(proposal.proposer, accounts(1));
+        assert_eq!(proposal.submission_time, U64(5));
+        match proposal.kind {
+            ProposalKind::ChangePolicy {
+                policy: VersionedPolicy::Current(policy),
+            } => {
+                assert_eq!(policy.roles[0].name, "council");
+                assert_eq!(policy.proposal_bond, U128(10));
+                assert_eq!(policy.bounty_forgiveness_period, U64(200));
+                assert!(!policy.default_vote_policy.lock_votes);
+                assert!(policy.default_vote_policy.timelock.is_none());
+                assert!(policy.guardian.is_none());
+            }
+            _ => panic!("ERR_WRONG_KIND"),
+        }
+    }
+
     #[test]
     #[should_panic(expected = "ERR_PERMISSION_DENIED")]
     fn test_remove_proposal_denied() {
@@ -265,6 +406,73 @@ mod tests {
         assert_eq!(contract.get_proposal(id).proposal.description, "test");
         contract.act_proposal(id, Action::RemoveProposal, None);
         assert_eq!(contract.get_proposals(0, 10).len(), 0);
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    /// Bond paid by the proposer is refunded, even if the policy's bond changed meanwhile.
+    #[test]
+    fn test_bond_refund_after_policy_change() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        assert_eq!(
+            contract.get_proposal(id).proposal.bond,
+            Some(U128(to_yocto("1")))
+        );
+        let change_id = contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::ChangePolicyUpdateParameters {
+                parameters: PolicyParameters {
+                    proposal_bond: Some(U128(to_yocto("2"))),
+                    proposal_period: None,
+                    bounty_bond: None,
+                    bounty_forgiveness_period: None,
+                },
+            },
+        });
+        contract.act_proposal(change_id, Action::VoteApprove, None);
+        assert_eq!(contract.get_policy().proposal_bond, U128(to_yocto("2")));
+
+        contract.act_proposal(id, Action::VoteReject, None);
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    /// Removes proposal by votes of both council members, slashing the bond as configured.
+    /// Returns receivers of the slashed bond.
+    fn remove_with_slashing(bond_slashing: BondSlashing) -> HashSet<AccountId> {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1), accounts(2)]).upgrade();
+        policy.to_policy_mut().bond_slashing = bond_slashing;
+        let mut contract = Contract::new(Config::test_config(), policy);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteRemove, None);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.act_proposal(id, Action::VoteRemove, None);
+        assert_eq!(contract.locked_amount, 0);
+        get_created_receipts()
+            .into_iter()
+            .map(|receipt| receipt.receiver_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_removed_bond_slashing() {
+        assert!(remove_with_slashing(BondSlashing::Treasury).is_empty());
+        assert_eq!(
+            remove_with_slashing(BondSlashing::Burn),
+            vec![types::BURN_ACCOUNT_ID.parse().unwrap()]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            remove_with_slashing(BondSlashing::Voters),
+            vec![accounts(1), accounts(2)].into_iter().collect()
+        );
     }
 
     #[test]
@@ -296,6 +504,516 @@ mod tests {
         contract.act_proposal(id, Action::VoteApprove, None);
     }
 
+    #[test]
+    fn test_change_vote() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1), accounts(2), accounts(3)]),
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        contract.act_proposal(id, Action::VoteReject, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.vote_counts["council"], [0, 1, 0]);
+        assert_eq!(proposal.votes.len(), 1);
+
+        contract.act_proposal(id, Action::VoteRetract, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.vote_counts["council"], [0, 0, 0]);
+        assert!(proposal.votes.is_empty());
+
+        contract.act_proposal(id, Action::VoteApprove, None);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.act_proposal(id, Action::VoteApprove, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOTE_LOCKED")]
+    fn test_change_locked_vote() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1), accounts(2)]).upgrade();
+        policy.to_policy_mut().roles[1].vote_policy.insert(
+            "transfer".to_string(),
+            VotePolicy {
+                lock_votes: true,
+                ..VotePolicy::default()
+            },
+        );
+        let mut contract = Contract::new(Config::test_config(), policy);
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteReject, None);
+        contract.act_proposal(id, Action::VoteApprove, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_VOTE_TO_CHANGE")]
+    fn test_retract_without_vote() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1), accounts(2)]),
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteRetract, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOTE_CAST_BEFORE_UPGRADE")]
+    fn test_retract_vote_cast_before_upgrade() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1), accounts(2)]),
+        );
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        // Votes migrated from before the upgrade have no weights.
+        let mut proposal: Proposal = contract.proposals.get(&id).unwrap().into();
+        proposal.vote_weights.clear();
+        contract
+            .proposals
+            .insert(&id, &VersionedProposal::Current(proposal));
+        contract.act_proposal(id, Action::VoteRetract, None);
+    }
+
+    /// Contract with a council of accounts(1) and a guardian accounts(3), queueing proposals for 100ns.
+    fn setup_timelock() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        let policy_mut = policy.to_policy_mut();
+        policy_mut.default_vote_policy.timelock = Some(U64(100));
+        policy_mut.roles.push(RolePermission {
+            name: "guardian".to_string(),
+            kind: RoleKind::Group(vec![accounts(3)].into_iter().collect()),
+            permissions: HashSet::default(),
+            vote_policy: HashMap::default(),
+        });
+        policy_mut.guardian = Some("guardian".to_string());
+        let contract = Contract::new(Config::test_config(), policy);
+        (context, contract)
+    }
+
+    #[test]
+    fn test_timelock_execute() {
+        let (mut context, mut contract) = setup_timelock();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Queued);
+        assert_eq!(proposal.execution_time, Some(U64(100)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100)
+            .build());
+        contract.act_proposal(id, Action::Execute, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_TIMELOCKED")]
+    fn test_timelock_execute_early() {
+        let (mut context, mut contract) = setup_timelock();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        testing_env!(context.block_timestamp(99).build());
+        contract.act_proposal(id, Action::Execute, None);
+    }
+
+    #[test]
+    fn test_timelock_guardian_cancel() {
+        let (mut context, mut contract) = setup_timelock();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.act_proposal(id, Action::Cancel, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Cancelled
+        );
+        assert_eq!(contract.locked_amount, 0);
+    }
+
+    /// Removing approved proposal waiting for its timelock returns the bond instead of slashing it.
+    #[test]
+    fn test_timelock_remove_returns_bond() {
+        let (mut context, mut contract) = setup_timelock();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        let mut policy = contract.policy.get().unwrap();
+        policy.to_policy_mut().roles[1]
+            .permissions
+            .insert("*:RemoveProposal".to_string());
+        contract.policy.set(&policy);
+
+        testing_env!(context.attached_deposit(0).build());
+        contract.act_proposal(id, Action::RemoveProposal, None);
+        assert_eq!(contract.locked_amount, 0);
+        let receivers: Vec<AccountId> = get_created_receipts()
+            .into_iter()
+            .map(|receipt| receipt.receiver_id)
+            .collect();
+        assert_eq!(receivers, vec![accounts(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PERMISSION_DENIED")]
+    fn test_timelock_cancel_not_guardian() {
+        let (mut context, mut contract) = setup_timelock();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        contract.act_proposal(id, Action::Cancel, None);
+    }
+
+    /// Contract with a council of accounts(1), voting on transfers for at least 100ns and at most 1000ns.
+    fn setup_voting_periods() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        policy.to_policy_mut().roles[1].vote_policy.insert(
+            "transfer".to_string(),
+            VotePolicy {
+                min_voting_period: Some(U64(100)),
+                proposal_period: Some(U64(1000)),
+                ..VotePolicy::default()
+            },
+        );
+        let contract = Contract::new(Config::test_config(), policy);
+        (context, contract)
+    }
+
+    #[test]
+    fn test_min_voting_period() {
+        let (mut context, mut contract) = setup_voting_periods();
+        let id = create_proposal(&mut context, &mut contract);
+        contract.act_proposal(id, Action::VoteApprove, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::InProgress
+        );
+        testing_env!(context.block_timestamp(100).build());
+        contract.act_proposal(id, Action::Finalize, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_kind_proposal_period() {
+        let (mut context, mut contract) = setup_voting_periods();
+        let id = create_proposal(&mut context, &mut contract);
+        testing_env!(context.block_timestamp(1001).build());
+        contract.act_proposal(id, Action::Finalize, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Expired
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MIN_VOTING_PERIOD_TOO_LONG")]
+    fn test_min_voting_period_longer_than_kind_proposal_period() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(1))
+            .build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        policy.to_policy_mut().roles[1].vote_policy.insert(
+            "transfer".to_string(),
+            VotePolicy {
+                min_voting_period: Some(U64(1001)),
+                proposal_period: Some(U64(1000)),
+                ..VotePolicy::default()
+            },
+        );
+        Contract::new(Config::test_config(), policy);
+    }
+
+    /// Default vote policy can't wait longer than the policy's proposal period.
+    #[test]
+    #[should_panic(expected = "ERR_MIN_VOTING_PERIOD_TOO_LONG")]
+    fn test_update_default_min_voting_period_too_long() {
+        let (mut context, mut contract) = setup_voting_periods();
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let proposal_period = contract.get_policy().proposal_period;
+        let id = contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::ChangePolicyUpdateDefaultVotePolicy {
+                vote_policy: VotePolicy {
+                    min_voting_period: Some(U64(proposal_period.0 + 1)),
+                    ..VotePolicy::default()
+                },
+            },
+        });
+        contract.act_proposal(id, Action::VoteApprove, None);
+    }
+
+    fn create_batch_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kinds: Vec<ProposalKind>,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::Batch {
+                kinds: BatchKinds(kinds),
+            },
+        })
+    }
+
+    fn transfer_kind() -> ProposalKind {
+        ProposalKind::Transfer {
+            token_id: String::from(OLD_BASE_TOKEN),
+            receiver_id: accounts(2),
+            amount: U128(to_yocto("1")),
+            msg: None,
+        }
+    }
+
+    /// Approved batch adding two council members, waiting for its transfer step.
+    fn setup_batch() -> (VMContextBuilder, Contract, u64) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        let add_member = |member_id| ProposalKind::AddMemberToRole {
+            member_id,
+            role: "council".to_string(),
+        };
+        let id = create_batch_proposal(
+            &mut context,
+            &mut contract,
+            vec![
+                add_member(accounts(2)),
+                add_member(accounts(3)),
+                transfer_kind(),
+            ],
+        );
+        contract.act_proposal(id, Action::VoteApprove, None);
+        (context, contract, id)
+    }
+
+    /// Next call is the callback of the batch step, with given result.
+    fn batch_callback(context: &mut VMContextBuilder, result: PromiseResult) {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    #[test]
+    fn test_batch_proposal() {
+        let (mut context, mut contract, id) = setup_batch();
+        assert_eq!(
+            contract.get_policy().roles[1].kind,
+            RoleKind::Group(
+                vec![accounts(1), accounts(2), accounts(3)]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        // Bond is returned once the transfer step succeeded.
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+        batch_callback(&mut context, PromiseResult::Successful(vec![]));
+        contract.on_batch_callback(id, 3);
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_GAS_FOR_BATCH")]
+    fn test_batch_not_enough_gas() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        let id = create_batch_proposal(&mut context, &mut contract, vec![transfer_kind()]);
+        testing_env!(context
+            .prepaid_gas(near_sdk::Gas(10_000_000_000_000))
+            .build());
+        contract.act_proposal(id, Action::VoteApprove, None);
+    }
+
+    /// Each asynchronous step waits for the previous one to succeed.
+    #[test]
+    fn test_batch_async_steps() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        let id = create_batch_proposal(
+            &mut context,
+            &mut contract,
+            vec![transfer_kind(), transfer_kind()],
+        );
+        contract.act_proposal(id, Action::VoteApprove, None);
+        batch_callback(&mut context, PromiseResult::Successful(vec![]));
+        contract.on_batch_callback(id, 1);
+        assert_eq!(contract.locked_amount, to_yocto("1"));
+        batch_callback(&mut context, PromiseResult::Successful(vec![]));
+        contract.on_batch_callback(id, 2);
+        assert_eq!(contract.locked_amount, 0);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    fn test_batch_step_failed() {
+        let (mut context, mut contract, id) = setup_batch();
+        batch_callback(&mut context, PromiseResult::Failed);
+        contract.on_batch_callback(id, 3);
+        let proposal = contract.get_proposal(id).proposal;
+        assert_eq!(proposal.status, ProposalStatus::Failed);
+        assert_eq!(proposal.failed_step, Some(2));
+        // Steps before the failed one stay applied.
+        assert_eq!(
+            contract.get_policy().roles[1].kind,
+            RoleKind::Group(
+                vec![accounts(1), accounts(2), accounts(3)]
+                    .into_iter()
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BATCH_FAILED")]
+    fn test_batch_step_failed_finalize() {
+        let (mut context, mut contract, id) = setup_batch();
+        batch_callback(&mut context, PromiseResult::Failed);
+        contract.on_batch_callback(id, 3);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(id, Action::Finalize, None);
+    }
+
+    /// Failed batch reserves the steps from the failed one on until it expires, as the ones before it ran.
+    #[test]
+    fn test_batch_step_failed_expires() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        let stream_kind = ProposalKind::Stream {
+            receiver_id: accounts(2),
+            token_id: String::from(OLD_BASE_TOKEN),
+            amount: U128(to_yocto("10")),
+            start: U64::from(0),
+            cliff: U64::from(0),
+            end: U64::from(1_000),
+        };
+        let id = create_batch_proposal(
+            &mut context,
+            &mut contract,
+            vec![transfer_kind(), stream_kind, transfer_kind()],
+        );
+        contract.act_proposal(id, Action::VoteApprove, None);
+        let reserved = |contract: &Contract| {
+            contract
+                .internal_treasury_balance(String::from(OLD_BASE_TOKEN))
+                .reserved
+        };
+        batch_callback(&mut context, PromiseResult::Successful(vec![]));
+        contract.on_batch_callback(id, 1);
+        assert_eq!(reserved(&contract), U128(to_yocto("10")));
+
+        batch_callback(&mut context, PromiseResult::Failed);
+        contract.on_batch_callback(id, 3);
+        assert_eq!(reserved(&contract), U128(to_yocto("11")));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_000_000_000_000_000)
+            .build());
+        contract.act_proposal(id, Action::Finalize, None);
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Expired
+        );
+        // The stream keeps its reservation.
+        assert_eq!(reserved(&contract), U128(to_yocto("10")));
+    }
+
+    #[test]
+    fn test_batch_strictest_vote_policy() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy =
+            VersionedPolicy::Default(vec![accounts(1), accounts(2), accounts(3)]).upgrade();
+        policy.to_policy_mut().roles[1].vote_policy.insert(
+            "transfer".to_string(),
+            VotePolicy {
+                threshold: WeightOrRatio::Ratio(1, 1),
+                ..VotePolicy::default()
+            },
+        );
+        let mut contract = Contract::new(Config::test_config(), policy);
+        let id = create_batch_proposal(
+            &mut context,
+            &mut contract,
+            vec![ProposalKind::Vote, transfer_kind()],
+        );
+        for voter in [accounts(1), accounts(2), accounts(3)] {
+            assert_eq!(
+                contract.get_proposal(id).proposal.status,
+                ProposalStatus::InProgress
+            );
+            testing_env!(context.predecessor_account_id(voter).build());
+            contract.act_proposal(id, Action::VoteApprove, None);
+        }
+        assert_eq!(
+            contract.get_proposal(id).proposal.status,
+            ProposalStatus::Approved
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_BATCH")]
+    fn test_nested_batch() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        create_batch_proposal(
+            &mut context,
+            &mut contract,
+            vec![ProposalKind::Batch {
+                kinds: BatchKinds(vec![ProposalKind::Vote]),
+            }],
+        );
+    }
+
     #[test]
     fn test_add_to_missing_role() {
         let mut context = VMContextBuilder::new();
@@ -9,56 +9,8 @@ use near_sdk::{env, AccountId, Balance};
 use crate::proposals::{PolicyParameters, Proposal, ProposalKind, ProposalStatus, Vote};
 use crate::types::Action;
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
-#[serde(crate = "near_sdk::serde")]
-pub enum RoleKind {
-    /// Matches everyone, who is not matched by other roles.
-    Everyone,
-    /// Member greater or equal than given balance. Can use `1` as non-zero balance.
-    Member(U128),
-    /// Set of accounts.
-    Group(HashSet<AccountId>),
-}
-
-impl RoleKind {
-    /// Checks if user matches given role.
-    pub fn match_user(&self, user: &UserInfo) -> bool {
-        match self {
-            RoleKind::Everyone => true,
-            RoleKind::Member(amount) => user.amount >= amount.0,
-            RoleKind::Group(accounts) => accounts.contains(&user.account_id),
-        }
-    }
-
-    /// Returns the number of people in the this role or None if not supported role kind.
-    pub fn get_role_size(&self) -> Option<usize> {
-        match self {
-            RoleKind::Group(accounts) => Some(accounts.len()),
-            _ => None,
-        }
-    }
-
-    pub fn add_member_to_group(&mut self, member_id: &AccountId) -> Result<(), ()> {
-        match self {
-            RoleKind::Group(accounts) => {
-                accounts.insert(member_id.clone());
-                Ok(())
-            }
-            _ => Err(()),
-        }
-    }
-
-    pub fn remove_member_from_group(&mut self, member_id: &AccountId) -> Result<(), ()> {
-        match self {
-            RoleKind::Group(accounts) => {
-                accounts.remove(member_id);
-                Ok(())
-            }
-            _ => Err(()),
-        }
-    }
-}
+use sputnik_policy::Role;
+pub use sputnik_policy::{RoleKind, UserInfo};
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -75,9 +27,18 @@ pub struct RolePermission {
     pub vote_policy: HashMap<String, VotePolicy>,
 }
 
-pub struct UserInfo {
-    pub account_id: AccountId,
-    pub amount: Balance,
+impl Role for RolePermission {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> &RoleKind {
+        &self.kind
+    }
+
+    fn permissions(&self) -> &HashSet<String> {
+        &self.permissions
+    }
 }
 
 /// Direct weight or ratio to total weight, used for the voting policy.
@@ -129,6 +90,21 @@ pub struct VotePolicy {
     pub quorum: U128,
     /// How many votes to pass this vote.
     pub threshold: WeightOrRatio,
+    /// If set, votes can't be changed or retracted while the proposal is in progress.
+    /// Used for kinds that execute as soon as the threshold is reached.
+    #[serde(default)]
+    pub lock_votes: bool,
+    /// Delay in nanoseconds between approval and execution. Approved proposals are queued
+    /// until it passes, and can be cancelled by the guardian in the meantime.
+    #[serde(default)]
+    pub timelock: Option<U64>,
+    /// Minimum time in nanoseconds between submission and approval. Reaching the threshold earlier
+    /// leaves the proposal in progress until it's finalized after this period.
+    #[serde(default)]
+    pub min_voting_period: Option<U64>,
+    /// Expiration period for proposals of this kind. Overrides the policy's `proposal_period`.
+    #[serde(default)]
+    pub proposal_period: Option<U64>,
 }
 
 impl Default for VotePolicy {
@@ -137,6 +113,10 @@ impl Default for VotePolicy {
             weight_kind: WeightKind::RoleWeight,
             quorum: U128(0),
             threshold: WeightOrRatio::Ratio(1, 2),
+            lock_votes: false,
+            timelock: None,
+            min_voting_period: None,
+            proposal_period: None,
         }
     }
 }
@@ -158,18 +138,146 @@ pub struct Policy {
     pub bounty_bond: U128,
     /// Period in which giving up on bounty is not punished.
     pub bounty_forgiveness_period: U64,
+    /// Role which members can cancel queued proposals during their timelock.
+    #[serde(default)]
+    pub guardian: Option<String>,
+    /// Token the proposal bond is paid in, via `ft_transfer_call` with the proposal as message.
+    /// If not set, the bond is paid in $NEAR.
+    #[serde(default)]
+    pub proposal_bond_token: Option<AccountId>,
+    /// What happens to the bonds of proposals removed as spam.
+    #[serde(default)]
+    pub bond_slashing: BondSlashing,
+}
+
+/// Destination of the bonds of removed proposals.
+/// Policies taking the proposal bond in a token must keep slashed bonds in the treasury.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub enum BondSlashing {
+    /// Bonds are sent to an account no one controls.
+    Burn,
+    /// Bonds are kept by the DAO and become available in the treasury.
+    #[default]
+    Treasury,
+    /// Bonds are split equally among the accounts that voted to remove the proposal.
+    /// What can't be split stays in the treasury.
+    Voters,
 }
 
 /// Versioned policy.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde", untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum VersionedPolicy {
     /// Default policy with given accounts as council.
     Default(Vec<AccountId>),
     Current(Policy),
 }
 
+/// Vote policy layout before `lock_votes`, read by `migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct OldVotePolicy {
+    weight_kind: WeightKind,
+    quorum: U128,
+    threshold: WeightOrRatio,
+}
+
+impl From<OldVotePolicy> for VotePolicy {
+    fn from(old: OldVotePolicy) -> Self {
+        Self {
+            weight_kind: old.weight_kind,
+            quorum: old.quorum,
+            threshold: old.threshold,
+            lock_votes: false,
+            timelock: None,
+            min_voting_period: None,
+            proposal_period: None,
+        }
+    }
+}
+
+/// Role layout with `OldVotePolicy`, read by `migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct OldRolePermission {
+    name: String,
+    kind: RoleKind,
+    permissions: HashSet<String>,
+    vote_policy: HashMap<String, OldVotePolicy>,
+}
+
+impl From<OldRolePermission> for RolePermission {
+    /// Voting on a kind of proposals also allows retracting votes on it, as `VoteRetract` didn't exist.
+    fn from(old: OldRolePermission) -> Self {
+        let mut permissions = old.permissions;
+        let retract_permissions: Vec<String> = permissions
+            .iter()
+            .filter_map(|permission| match permission.split_once(':') {
+                Some((label, "VoteApprove")) | Some((label, "VoteReject")) => {
+                    Some(format!("{}:VoteRetract", label))
+                }
+                _ => None,
+            })
+            .collect();
+        permissions.extend(retract_permissions);
+        Self {
+            name: old.name,
+            kind: old.kind,
+            permissions,
+            vote_policy: old
+                .vote_policy
+                .into_iter()
+                .map(|(label, vote_policy)| (label, vote_policy.into()))
+                .collect(),
+        }
+    }
+}
+
+/// Policy layout with `OldVotePolicy`, read by `migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) struct OldPolicy {
+    roles: Vec<OldRolePermission>,
+    default_vote_policy: OldVotePolicy,
+    proposal_bond: U128,
+    proposal_period: U64,
+    bounty_bond: U128,
+    bounty_forgiveness_period: U64,
+}
+
+impl From<OldPolicy> for Policy {
+    fn from(old: OldPolicy) -> Self {
+        Self {
+            roles: old.roles.into_iter().map(RolePermission::from).collect(),
+            default_vote_policy: old.default_vote_policy.into(),
+            proposal_bond: old.proposal_bond,
+            proposal_period: old.proposal_period,
+            bounty_bond: old.bounty_bond,
+            bounty_forgiveness_period: old.bounty_forgiveness_period,
+            guardian: None,
+            proposal_bond_token: None,
+            bond_slashing: BondSlashing::default(),
+        }
+    }
+}
+
+/// Versioned policy with `OldPolicy`, read by `migrate`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) enum OldVersionedPolicy {
+    Default(Vec<AccountId>),
+    Current(OldPolicy),
+}
+
+impl From<OldVersionedPolicy> for VersionedPolicy {
+    fn from(old: OldVersionedPolicy) -> Self {
+        match old {
+            OldVersionedPolicy::Default(accounts) => VersionedPolicy::Default(accounts),
+            OldVersionedPolicy::Current(policy) => VersionedPolicy::Current(policy.into()),
+        }
+    }
+}
+
 /// Defines default policy:
 ///     - everyone can add proposals
 ///     - group consisting of the call can do all actions, consists of caller.
@@ -194,6 +302,7 @@ pub fn default_policy(council: Vec<AccountId>) -> Policy {
                     "*:VoteApprove".to_string(),
                     "*:VoteReject".to_string(),
                     "*:VoteRemove".to_string(),
+                    "*:VoteRetract".to_string(),
                     "*:Finalize".to_string(),
                 ]
                 .into_iter()
@@ -206,6 +315,9 @@ pub fn default_policy(council: Vec<AccountId>) -> Policy {
         proposal_period: U64::from(1_000_000_000 * 60 * 60 * 24 * 7),
         bounty_bond: U128(10u128.pow(24)),
         bounty_forgiveness_period: U64::from(1_000_000_000 * 60 * 60 * 24),
+        guardian: None,
+        proposal_bond_token: None,
+        bond_slashing: BondSlashing::default(),
     }
 }
 
@@ -237,6 +349,50 @@ impl VersionedPolicy {
 }
 
 impl Policy {
+    /// Panics if the policy's settings contradict each other.
+    pub fn assert_valid(&self) {
+        // Token transfers to the burn account or to voters not registered with the token fail.
+        assert!(
+            self.proposal_bond_token.is_none()
+                || matches!(self.bond_slashing, BondSlashing::Treasury),
+            "ERR_TOKEN_BOND_SLASHING"
+        );
+        // Proposals must not expire before they can be approved. The empty label stands for
+        // the kinds without a vote policy of their own in any role.
+        let labels: HashSet<&str> = self
+            .roles
+            .iter()
+            .flat_map(|role| role.vote_policy.keys().map(String::as_str))
+            .chain(std::iter::once(""))
+            .collect();
+        for label in labels {
+            let vote_policies: Vec<&VotePolicy> = if self.roles.is_empty() {
+                vec![&self.default_vote_policy]
+            } else {
+                self.roles
+                    .iter()
+                    .map(|role| self.internal_get_vote_policy(&role.name, label))
+                    .collect()
+            };
+            let proposal_period = vote_policies
+                .iter()
+                .filter_map(|vote_policy| vote_policy.proposal_period)
+                .map(|period| period.0)
+                .max()
+                .unwrap_or(self.proposal_period.0);
+            let min_voting_period = vote_policies
+                .iter()
+                .filter_map(|vote_policy| vote_policy.min_voting_period)
+                .map(|period| period.0)
+                .max()
+                .unwrap_or(0);
+            assert!(
+                min_voting_period <= proposal_period,
+                "ERR_MIN_VOTING_PERIOD_TOO_LONG"
+            );
+        }
+    }
+
     pub fn add_or_update_role(&mut self, role: &RolePermission) {
         for i in 0..self.roles.len() {
             if &self.roles[i].name == &role.name {
@@ -286,12 +442,9 @@ impl Policy {
     pub fn add_member_to_role(&mut self, role: &String, member_id: &AccountId) {
         for i in 0..self.roles.len() {
             if &self.roles[i].name == role {
-                self.roles[i]
-                    .kind
-                    .add_member_to_group(member_id)
-                    .unwrap_or_else(|()| {
-                        env::log_str(&format!("ERR_ROLE_WRONG_KIND:{}", role));
-                    });
+                if !self.roles[i].kind.add_member_to_group(member_id) {
+                    env::log_str(&format!("ERR_ROLE_WRONG_KIND:{}", role));
+                }
                 return;
             }
         }
@@ -301,63 +454,36 @@ impl Policy {
     pub fn remove_member_from_role(&mut self, role: &String, member_id: &AccountId) {
         for i in 0..self.roles.len() {
             if &self.roles[i].name == role {
-                self.roles[i]
-                    .kind
-                    .remove_member_from_group(member_id)
-                    .unwrap_or_else(|()| {
-                        env::log_str(&format!("ERR_ROLE_WRONG_KIND:{}", role));
-                    });
+                if !self.roles[i].kind.remove_member_from_group(member_id) {
+                    env::log_str(&format!("ERR_ROLE_WRONG_KIND:{}", role));
+                }
                 return;
             }
         }
         env::log_str(&format!("ERR_ROLE_NOT_FOUND:{}", role));
     }
 
-    /// Returns set of roles that this user is member of permissions for given user across all the roles it's member of.
-    fn get_user_roles(&self, user: UserInfo) -> HashMap<String, &HashSet<String>> {
-        let mut roles = HashMap::default();
-        for role in self.roles.iter() {
-            if role.kind.match_user(&user) {
-                roles.insert(role.name.clone(), &role.permissions);
-            }
-        }
-        roles
-    }
-
     /// Can given user execute given action on this proposal.
-    /// Returns all roles that allow this action.
+    /// Returns all roles that allow this action. For a batch, roles must allow it on every batched kind.
     pub fn can_execute_action(
         &self,
         user: UserInfo,
         proposal_kind: &ProposalKind,
         action: &Action,
     ) -> (Vec<String>, bool) {
-        let roles = self.get_user_roles(user);
-        let mut allowed = false;
-        let allowed_roles = roles
+        let labels = proposal_kind.to_policy_labels();
+        let action = action.to_policy_label();
+        let allowed_roles: Vec<String> = sputnik_policy::get_user_roles(&self.roles, &user)
             .into_iter()
-            .filter_map(|(role, permissions)| {
-                let allowed_role = permissions.contains(&format!(
-                    "{}:{}",
-                    proposal_kind.to_policy_label(),
-                    action.to_policy_label()
-                )) || permissions
-                    .contains(&format!("{}:*", proposal_kind.to_policy_label()))
-                    || permissions.contains(&format!("*:{}", action.to_policy_label()))
-                    || permissions.contains("*:*");
-                allowed = allowed || allowed_role;
-                if allowed_role {
-                    Some(role)
-                } else {
-                    None
-                }
-            })
+            .filter(|(_, role)| labels.iter().all(|label| role.allows(label, &action)))
+            .map(|(name, _)| name)
             .collect();
+        let allowed = !allowed_roles.is_empty();
         (allowed_roles, allowed)
     }
 
     /// Returns if given proposal kind is token weighted.
-    pub fn is_token_weighted(&self, role: &String, proposal_kind_label: &String) -> bool {
+    pub fn is_token_weighted(&self, role: &str, proposal_kind_label: &String) -> bool {
         let role_info = self.internal_get_role(role).expect("ERR_ROLE_NOT_FOUND");
         match role_info
             .vote_policy
@@ -370,13 +496,89 @@ impl Policy {
         }
     }
 
-    fn internal_get_role(&self, name: &String) -> Option<&RolePermission> {
-        for role in self.roles.iter() {
-            if role.name == *name {
-                return Some(role);
-            }
-        }
-        None
+    fn internal_get_role(&self, name: &str) -> Option<&RolePermission> {
+        sputnik_policy::get_role(&self.roles, name)
+    }
+
+    /// Returns vote policy of given role for given proposal kind.
+    /// Falls back to the default vote policy if the role has none for this kind or was removed.
+    fn internal_get_vote_policy(&self, role: &str, proposal_kind_label: &str) -> &VotePolicy {
+        self.internal_get_role(role)
+            .and_then(|role_info| role_info.vote_policy.get(proposal_kind_label))
+            .unwrap_or(&self.default_vote_policy)
+    }
+
+    /// Returns vote policies of given role that apply to given proposal: one per batched kind for a batch.
+    fn internal_get_vote_policies(
+        &self,
+        role: &str,
+        proposal_kind: &ProposalKind,
+    ) -> Vec<&VotePolicy> {
+        proposal_kind
+            .to_policy_labels()
+            .into_iter()
+            .map(|label| self.internal_get_vote_policy(role, label))
+            .collect()
+    }
+
+    /// Returns vote policies of every role that apply to given proposal.
+    fn internal_get_all_vote_policies(&self, proposal_kind: &ProposalKind) -> Vec<&VotePolicy> {
+        self.roles
+            .iter()
+            .flat_map(|role| self.internal_get_vote_policies(&role.name, proposal_kind))
+            .collect()
+    }
+
+    /// Returns if each role weighs votes the same way for all the kinds in given proposal.
+    pub fn has_uniform_weight_kind(&self, proposal_kind: &ProposalKind) -> bool {
+        self.roles.iter().all(|role| {
+            let vote_policies = self.internal_get_vote_policies(&role.name, proposal_kind);
+            vote_policies
+                .iter()
+                .all(|vote_policy| vote_policy.weight_kind == vote_policies[0].weight_kind)
+        })
+    }
+
+    /// Returns if votes counted in given roles on given proposal kind can be changed or retracted.
+    pub fn can_change_vote<'a>(
+        &self,
+        roles: impl IntoIterator<Item = &'a String>,
+        proposal_kind: &ProposalKind,
+    ) -> bool {
+        roles.into_iter().all(|role| {
+            self.internal_get_vote_policies(role, proposal_kind)
+                .iter()
+                .all(|vote_policy| !vote_policy.lock_votes)
+        })
+    }
+
+    /// Returns delay between approval and execution of given proposal kind, the longest among roles.
+    pub fn timelock(&self, proposal_kind: &ProposalKind) -> u64 {
+        self.internal_get_all_vote_policies(proposal_kind)
+            .into_iter()
+            .filter_map(|vote_policy| vote_policy.timelock)
+            .map(|timelock| timelock.0)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns expiration period of given proposal kind, the longest among roles that override it.
+    /// Falls back to the policy's `proposal_period`.
+    pub fn get_proposal_period(&self, proposal_kind: &ProposalKind) -> u64 {
+        self.internal_get_all_vote_policies(proposal_kind)
+            .into_iter()
+            .filter_map(|vote_policy| vote_policy.proposal_period)
+            .map(|period| period.0)
+            .max()
+            .unwrap_or(self.proposal_period.0)
+    }
+
+    /// Returns if given user is a member of the guardian role.
+    pub fn is_guardian(&self, user: &UserInfo) -> bool {
+        self.guardian
+            .as_ref()
+            .and_then(|guardian| self.internal_get_role(guardian))
+            .is_some_and(|role| role.kind.match_user(user))
     }
 
     /// Get proposal status for given proposal.
@@ -394,35 +596,46 @@ impl Policy {
             ),
             "ERR_PROPOSAL_NOT_IN_PROGRESS"
         );
-        if proposal.submission_time.0 + self.proposal_period.0 < env::block_timestamp() {
+        if proposal.submission_time.0 + self.get_proposal_period(&proposal.kind)
+            < env::block_timestamp()
+        {
             // Proposal expired.
             return ProposalStatus::Expired;
         };
         for role in roles {
             let role_info = self.internal_get_role(&role).expect("ERR_MISSING_ROLE");
-            let vote_policy = role_info
-                .vote_policy
-                .get(&proposal.kind.to_policy_label().to_string())
-                .unwrap_or(&self.default_vote_policy);
-            let total_weight = match &role_info.kind {
-                // Skip role that covers everyone as it doesn't provide a total size.
-                RoleKind::Everyone => continue,
-                RoleKind::Group(group) => {
-                    if vote_policy.weight_kind == WeightKind::RoleWeight {
+            // Skip role that covers everyone as it doesn't provide a total size.
+            if let RoleKind::Everyone = role_info.kind {
+                continue;
+            }
+            // A batch is held to the strictest vote policy among its kinds.
+            let mut threshold = 0;
+            let mut min_voting_period = 0;
+            for vote_policy in self.internal_get_vote_policies(&role, &proposal.kind) {
+                let total_weight = match &role_info.kind {
+                    RoleKind::Group(group) if vote_policy.weight_kind == WeightKind::RoleWeight => {
                         group.len() as Balance
-                    } else {
-                        total_supply
                     }
-                }
-                RoleKind::Member(_) => total_supply,
-            };
-            let threshold = std::cmp::max(
-                vote_policy.quorum.0,
-                vote_policy.threshold.to_weight(total_weight),
-            );
+                    _ => total_supply,
+                };
+                threshold = std::cmp::max(
+                    threshold,
+                    std::cmp::max(
+                        vote_policy.quorum.0,
+                        vote_policy.threshold.to_weight(total_weight),
+                    ),
+                );
+                min_voting_period = std::cmp::max(
+                    min_voting_period,
+                    vote_policy.min_voting_period.map_or(0, |period| period.0),
+                );
+            }
+            // Approval waits for the minimum voting period, rejection and removal don't.
+            let can_approve =
+                proposal.submission_time.0 + min_voting_period <= env::block_timestamp();
             // Check if there is anything voted above the threshold specified by policy for given role.
             let vote_counts = proposal.vote_counts.get(&role).unwrap_or(&[0u128; 3]);
-            if vote_counts[Vote::Approve as usize] >= threshold {
+            if can_approve && vote_counts[Vote::Approve as usize] >= threshold {
                 return ProposalStatus::Approved;
             } else if vote_counts[Vote::Reject as usize] >= threshold {
                 return ProposalStatus::Rejected;
@@ -454,6 +667,32 @@ mod tests {
         assert_eq!(r2.to_weight(5), 5);
     }
 
+    #[test]
+    fn test_migrate_role_allows_vote_retract() {
+        let old = OldRolePermission {
+            name: "council".to_string(),
+            kind: RoleKind::Group(vec![accounts(1)].into_iter().collect()),
+            permissions: vec![
+                "*:AddProposal".to_string(),
+                "*:VoteApprove".to_string(),
+                "transfer:VoteReject".to_string(),
+            ]
+            .into_iter()
+            .collect(),
+            vote_policy: HashMap::default(),
+        };
+        let permissions: HashSet<String> = vec![
+            "*:AddProposal".to_string(),
+            "*:VoteApprove".to_string(),
+            "*:VoteRetract".to_string(),
+            "transfer:VoteReject".to_string(),
+            "transfer:VoteRetract".to_string(),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(RolePermission::from(old).permissions, permissions);
+    }
+
     #[test]
     fn test_add_role() {
         let council = vec![accounts(0), accounts(1)];
@@ -498,6 +737,7 @@ mod tests {
             "*:VoteApprove".to_string(),
             "*:VoteReject".to_string(),
             "*:VoteRemove".to_string(),
+            "*:VoteRetract".to_string(),
             "*:Finalize".to_string(),
         ]
         .into_iter()
@@ -570,6 +810,10 @@ mod tests {
             weight_kind: WeightKind::TokenWeight,
             quorum: U128(100),
             threshold: WeightOrRatio::Ratio(1, 4),
+            lock_votes: false,
+            timelock: None,
+            min_voting_period: None,
+            proposal_period: None,
         };
         policy.update_default_vote_policy(&new_default_vote_policy);
         assert_eq!(
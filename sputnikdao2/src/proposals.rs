@@ -5,10 +5,10 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::{log, AccountId, Balance, Gas, PromiseOrValue};
 
-use crate::policy::UserInfo;
+use crate::policy::{OldRolePermission, OldVersionedPolicy, OldVotePolicy, UserInfo};
 use crate::types::{
-    convert_old_to_new_token, Action, Config, OldAccountId, GAS_FOR_FT_TRANSFER, OLD_BASE_TOKEN,
-    ONE_YOCTO_NEAR,
+    convert_old_to_new_token, Action, Config, OldAccountId, BURN_ACCOUNT_ID,
+    GAS_FOR_BATCH_CALLBACK_RESERVE, GAS_FOR_FT_TRANSFER, OLD_BASE_TOKEN, ONE_YOCTO_NEAR,
 };
 use crate::upgrade::{upgrade_remote, upgrade_using_factory};
 use crate::*;
@@ -23,6 +23,7 @@ pub enum ProposalStatus {
     /// If quorum voted no, this proposal is rejected. Bond is returned.
     Rejected,
     /// If quorum voted to remove (e.g. spam), this proposal is rejected and bond is not returned.
+    /// Bond is burned, kept in the treasury or split among voters, as the policy says.
     /// Interfaces shouldn't show removed proposals.
     Removed,
     /// Expired after period of time.
@@ -31,6 +32,10 @@ pub enum ProposalStatus {
     Moved,
     /// If proposal has failed when finalizing. Allowed to re-finalize again to either expire or approved.
     Failed,
+    /// Approved proposal waiting for its timelock to pass before it can be executed.
+    Queued,
+    /// Queued proposal cancelled by the guardian. Bond is returned.
+    Cancelled,
 }
 
 /// Function call arguments.
@@ -113,6 +118,55 @@ pub enum ProposalKind {
     ChangePolicyUpdateDefaultVotePolicy { vote_policy: VotePolicy },
     /// Update the parameters from the policy. This is short cut to updating the whole policy.
     ChangePolicyUpdateParameters { parameters: PolicyParameters },
+    /// Executes given kinds in order, voted once under the strictest of their vote policies.
+    /// Execution continues past an asynchronous step (e.g. transfer or function call) once it succeeded.
+    /// If one fails, the batch stops there and fails as a whole, but the steps before it stay applied.
+    /// Can't contain batches or bounty payouts.
+    Batch { kinds: BatchKinds },
+    /// Streams given amount of `token_id` to `receiver_id`, vesting linearly from `start` to `end`.
+    /// Receiver claims what's vested with `claim_stream`, nothing before the `cliff`.
+    Stream {
+        receiver_id: AccountId,
+        /// Can be "" for $NEAR or a valid account id.
+        token_id: OldAccountId,
+        amount: U128,
+        start: U64,
+        cliff: U64,
+        end: U64,
+    },
+    /// Cancels given stream. What's vested stays claimable, the rest returns to the DAO.
+    CancelStream { stream_id: u64 },
+    /// Pays `amount` of `token_id` to `receiver_id` every `interval`, `count` times.
+    /// Anyone can make each payment with `pay_recurring_payment` once its period elapsed.
+    RecurringPayment {
+        receiver_id: AccountId,
+        /// Can be "" for $NEAR or a valid account id.
+        token_id: OldAccountId,
+        amount: U128,
+        interval: U64,
+        count: u32,
+    },
+    /// Cancels given recurring payment.
+    CancelRecurringPayment { payment_id: u64 },
+}
+
+/// Kinds of a batch proposal, in execution order.
+/// Borsh is implemented by hand, as derived bounds on the recursive `ProposalKind` don't resolve.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[serde(crate = "near_sdk::serde", transparent)]
+pub struct BatchKinds(pub Vec<ProposalKind>);
+
+impl BorshSerialize for BatchKinds {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&self.0, writer)
+    }
+}
+
+impl BorshDeserialize for BatchKinds {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self(BorshDeserialize::deserialize(buf)?))
+    }
 }
 
 impl ProposalKind {
@@ -138,12 +192,27 @@ impl ProposalKind {
                 "policy_update_default_vote_policy"
             }
             ProposalKind::ChangePolicyUpdateParameters { .. } => "policy_update_parameters",
+            ProposalKind::Batch { .. } => "batch",
+            ProposalKind::Stream { .. } => "stream",
+            ProposalKind::CancelStream { .. } => "cancel_stream",
+            ProposalKind::RecurringPayment { .. } => "recurring_payment",
+            ProposalKind::CancelRecurringPayment { .. } => "cancel_recurring_payment",
+        }
+    }
+
+    /// Returns labels of policy that apply to given proposal: the batched kinds' for a batch.
+    pub fn to_policy_labels(&self) -> Vec<&str> {
+        match self {
+            ProposalKind::Batch { kinds } => {
+                kinds.0.iter().map(|kind| kind.to_policy_label()).collect()
+            }
+            _ => vec![self.to_policy_label()],
         }
     }
 }
 
 /// Votes recorded in the proposal.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum Vote {
     Approve = 0x0,
@@ -181,24 +250,211 @@ pub struct Proposal {
     pub votes: HashMap<AccountId, Vote>,
     /// Submission time (for voting period).
     pub submission_time: U64,
+    /// Weight each vote added per role, to subtract it when the vote is changed or retracted.
+    pub vote_weights: HashMap<AccountId, HashMap<String, Balance>>,
+    /// Time after which queued proposal can be executed. Set once the proposal is queued.
+    pub execution_time: Option<U64>,
+    /// Token the bond was paid in. $NEAR if not set.
+    pub bond_token: Option<AccountId>,
+    /// Bond paid by the proposer. Not set on proposals added before the upgrade, which paid the policy's bond.
+    pub bond: Option<U128>,
+    /// Step of a batch whose asynchronous execution failed. Steps before it stay applied.
+    pub failed_step: Option<u64>,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
-#[serde(crate = "near_sdk::serde")]
+/// Kinds of proposals stored before the upgrade, with policies in their old layout.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) enum OldProposalKind {
+    ChangeConfig {
+        config: Config,
+    },
+    ChangePolicy {
+        policy: OldVersionedPolicy,
+    },
+    AddMemberToRole {
+        member_id: AccountId,
+        role: String,
+    },
+    RemoveMemberFromRole {
+        member_id: AccountId,
+        role: String,
+    },
+    FunctionCall {
+        receiver_id: AccountId,
+        actions: Vec<ActionCall>,
+    },
+    UpgradeSelf {
+        hash: Base58CryptoHash,
+    },
+    UpgradeRemote {
+        receiver_id: AccountId,
+        method_name: String,
+        hash: Base58CryptoHash,
+    },
+    Transfer {
+        token_id: OldAccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: Option<String>,
+    },
+    SetStakingContract {
+        staking_id: AccountId,
+    },
+    AddBounty {
+        bounty: Bounty,
+    },
+    BountyDone {
+        bounty_id: u64,
+        receiver_id: AccountId,
+    },
+    Vote,
+    FactoryInfoUpdate {
+        factory_info: FactoryInfo,
+    },
+    ChangePolicyAddOrUpdateRole {
+        role: OldRolePermission,
+    },
+    ChangePolicyRemoveRole {
+        role: String,
+    },
+    ChangePolicyUpdateDefaultVotePolicy {
+        vote_policy: OldVotePolicy,
+    },
+    ChangePolicyUpdateParameters {
+        parameters: PolicyParameters,
+    },
+}
+
+impl From<OldProposalKind> for ProposalKind {
+    fn from(old: OldProposalKind) -> Self {
+        match old {
+            OldProposalKind::ChangeConfig { config } => ProposalKind::ChangeConfig { config },
+            OldProposalKind::ChangePolicy { policy } => ProposalKind::ChangePolicy {
+                policy: policy.into(),
+            },
+            OldProposalKind::AddMemberToRole { member_id, role } => {
+                ProposalKind::AddMemberToRole { member_id, role }
+            }
+            OldProposalKind::RemoveMemberFromRole { member_id, role } => {
+                ProposalKind::RemoveMemberFromRole { member_id, role }
+            }
+            OldProposalKind::FunctionCall {
+                receiver_id,
+                actions,
+            } => ProposalKind::FunctionCall {
+                receiver_id,
+                actions,
+            },
+            OldProposalKind::UpgradeSelf { hash } => ProposalKind::UpgradeSelf { hash },
+            OldProposalKind::UpgradeRemote {
+                receiver_id,
+                method_name,
+                hash,
+            } => ProposalKind::UpgradeRemote {
+                receiver_id,
+                method_name,
+                hash,
+            },
+            OldProposalKind::Transfer {
+                token_id,
+                receiver_id,
+                amount,
+                msg,
+            } => ProposalKind::Transfer {
+                token_id,
+                receiver_id,
+                amount,
+                msg,
+            },
+            OldProposalKind::SetStakingContract { staking_id } => {
+                ProposalKind::SetStakingContract { staking_id }
+            }
+            OldProposalKind::AddBounty { bounty } => ProposalKind::AddBounty { bounty },
+            OldProposalKind::BountyDone {
+                bounty_id,
+                receiver_id,
+            } => ProposalKind::BountyDone {
+                bounty_id,
+                receiver_id,
+            },
+            OldProposalKind::Vote => ProposalKind::Vote,
+            OldProposalKind::FactoryInfoUpdate { factory_info } => {
+                ProposalKind::FactoryInfoUpdate { factory_info }
+            }
+            OldProposalKind::ChangePolicyAddOrUpdateRole { role } => {
+                ProposalKind::ChangePolicyAddOrUpdateRole { role: role.into() }
+            }
+            OldProposalKind::ChangePolicyRemoveRole { role } => {
+                ProposalKind::ChangePolicyRemoveRole { role }
+            }
+            OldProposalKind::ChangePolicyUpdateDefaultVotePolicy { vote_policy } => {
+                ProposalKind::ChangePolicyUpdateDefaultVotePolicy {
+                    vote_policy: vote_policy.into(),
+                }
+            }
+            OldProposalKind::ChangePolicyUpdateParameters { parameters } => {
+                ProposalKind::ChangePolicyUpdateParameters { parameters }
+            }
+        }
+    }
+}
+
+/// Proposal stored before vote weights were recorded.
+/// Votes cast on it before the upgrade can't be changed or retracted.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ProposalV0 {
+    pub proposer: AccountId,
+    pub description: String,
+    pub(crate) kind: OldProposalKind,
+    pub status: ProposalStatus,
+    pub vote_counts: HashMap<String, [Balance; 3]>,
+    pub votes: HashMap<AccountId, Vote>,
+    pub submission_time: U64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
 pub enum VersionedProposal {
-    Default(Proposal),
+    Default(ProposalV0),
+    Current(Proposal),
 }
 
 impl From<VersionedProposal> for Proposal {
     fn from(v: VersionedProposal) -> Self {
         match v {
-            VersionedProposal::Default(p) => p,
+            VersionedProposal::Default(p) => Proposal {
+                proposer: p.proposer,
+                description: p.description,
+                kind: p.kind.into(),
+                status: p.status,
+                vote_counts: p.vote_counts,
+                votes: p.votes,
+                submission_time: p.submission_time,
+                vote_weights: HashMap::default(),
+                execution_time: None,
+                bond_token: None,
+                bond: None,
+                failed_step: None,
+            },
+            VersionedProposal::Current(p) => p,
         }
     }
 }
 
 impl Proposal {
+    /// Returns amount of the bond paid by the proposer.
+    pub fn bond_amount(&self, policy: &Policy) -> Balance {
+        self.bond.unwrap_or(policy.proposal_bond).0
+    }
+
+    /// Returns kinds whose payouts the proposal still reserves.
+    /// For a failed batch, these are the failed step and the ones after it, which didn't run.
+    pub fn reserved_kinds(&self) -> &[ProposalKind] {
+        match (&self.kind, self.failed_step) {
+            (ProposalKind::Batch { kinds }, Some(step)) => &kinds.0[step as usize..],
+            (kind, _) => std::slice::from_ref(kind),
+        }
+    }
+
     /// Adds vote of the given user with given `amount` of weight. If user already voted, fails.
     pub fn update_votes(
         &mut self,
@@ -208,20 +464,50 @@ impl Proposal {
         policy: &Policy,
         user_weight: Balance,
     ) {
+        let mut weights = HashMap::new();
+        // Kinds in a batch share the weight kind, so the first one is representative.
+        let proposal_kind_label = self.kind.to_policy_labels()[0].to_string();
         for role in roles {
-            let amount = if policy.is_token_weighted(role, &self.kind.to_policy_label().to_string())
-            {
+            let amount = if policy.is_token_weighted(role, &proposal_kind_label) {
                 user_weight
             } else {
                 1
             };
             self.vote_counts.entry(role.clone()).or_insert([0u128; 3])[vote.clone() as usize] +=
                 amount;
+            weights.insert(role.clone(), amount);
         }
         assert!(
             self.votes.insert(account_id.clone(), vote).is_none(),
             "ERR_ALREADY_VOTED"
         );
+        self.vote_weights.insert(account_id.clone(), weights);
+    }
+
+    /// Removes vote of the given user, subtracting the weight it added to every role.
+    /// Fails if user hasn't voted, voted before the upgrade or if votes on this kind are locked for any of
+    /// the roles.
+    pub fn retract_vote(&mut self, account_id: &AccountId, policy: &Policy) -> Vote {
+        assert!(self.votes.contains_key(account_id), "ERR_NO_VOTE_TO_CHANGE");
+        // Weights aren't known for votes cast before the upgrade.
+        let weights = self
+            .vote_weights
+            .remove(account_id)
+            .expect("ERR_VOTE_CAST_BEFORE_UPGRADE");
+        assert!(
+            policy.can_change_vote(weights.keys(), &self.kind),
+            "ERR_VOTE_LOCKED"
+        );
+        let vote = self
+            .votes
+            .remove(account_id)
+            .expect("ERR_NO_VOTE_TO_CHANGE");
+        for (role, amount) in weights {
+            if let Some(vote_counts) = self.vote_counts.get_mut(&role) {
+                vote_counts[vote.clone() as usize] -= amount;
+            }
+        }
+        vote
     }
 }
 
@@ -244,6 +530,11 @@ impl From<ProposalInput> for Proposal {
             vote_counts: HashMap::default(),
             votes: HashMap::default(),
             submission_time: U64::from(env::block_timestamp()),
+            vote_weights: HashMap::default(),
+            execution_time: None,
+            bond_token: None,
+            bond: None,
+            failed_step: None,
         }
     }
 }
@@ -261,6 +552,7 @@ impl Contract {
         if token_id.is_none() {
             Promise::new(receiver_id.clone()).transfer(amount).into()
         } else {
+            self.internal_withdraw_token(&token_id.as_ref().unwrap().to_string(), amount);
             if let Some(msg) = msg {
                 ext_fungible_token::ft_transfer_call(
                     receiver_id.clone(),
@@ -285,7 +577,11 @@ impl Contract {
         }
     }
 
-    fn internal_return_bonds(&mut self, policy: &Policy, proposal: &Proposal) -> Promise {
+    fn internal_return_bonds(
+        &mut self,
+        policy: &Policy,
+        proposal: &Proposal,
+    ) -> PromiseOrValue<()> {
         match &proposal.kind {
             ProposalKind::BountyDone { .. } => {
                 self.locked_amount -= policy.bounty_bond.0;
@@ -294,8 +590,25 @@ impl Contract {
             _ => {}
         }
 
-        self.locked_amount -= policy.proposal_bond.0;
-        Promise::new(proposal.proposer.clone()).transfer(policy.proposal_bond.0)
+        let bond = proposal.bond_amount(policy);
+        match &proposal.bond_token {
+            None => {
+                self.locked_amount -= bond;
+                Promise::new(proposal.proposer.clone())
+                    .transfer(bond)
+                    .into()
+            }
+            Some(token_id) => {
+                self.internal_release(&token_id.to_string(), bond);
+                self.internal_payout(
+                    &proposal.bond_token,
+                    &proposal.proposer,
+                    bond,
+                    "Proposal bond refund".to_string(),
+                    None,
+                )
+            }
+        }
     }
 
     /// Executes given proposal and updates the contract's state.
@@ -305,7 +618,68 @@ impl Contract {
         proposal: &Proposal,
         proposal_id: u64,
     ) -> PromiseOrValue<()> {
-        let result = match &proposal.kind {
+        self.internal_release_payouts(&proposal.kind);
+        if let ProposalKind::Batch { .. } = proposal.kind {
+            return self.internal_execute_batch(policy, proposal, proposal_id, 0);
+        }
+        match self.internal_execute_proposal_kind(policy, proposal, &proposal.kind) {
+            PromiseOrValue::Promise(promise) => promise
+                .then(ext_self::on_proposal_callback(
+                    proposal_id,
+                    env::current_account_id(),
+                    0,
+                    GAS_FOR_FT_TRANSFER,
+                ))
+                .into(),
+            PromiseOrValue::Value(()) => self.internal_return_bonds(policy, proposal),
+        }
+    }
+
+    /// Executes steps of given batch proposal in order, starting from `from_step`.
+    /// Stops at the first asynchronous step and continues in `on_batch_callback` if it succeeded.
+    fn internal_execute_batch(
+        &mut self,
+        policy: &Policy,
+        proposal: &Proposal,
+        proposal_id: u64,
+        from_step: u64,
+    ) -> PromiseOrValue<()> {
+        let kinds = match &proposal.kind {
+            ProposalKind::Batch { kinds } => &kinds.0,
+            _ => unreachable!(),
+        };
+        for (step, kind) in kinds.iter().enumerate().skip(from_step as usize) {
+            // Each step sees the policy changed by the previous ones.
+            let current_policy = self.policy.get().unwrap().to_policy();
+            if let PromiseOrValue::Promise(promise) =
+                self.internal_execute_proposal_kind(&current_policy, proposal, kind)
+            {
+                let callback_gas = env::prepaid_gas()
+                    .0
+                    .checked_sub(env::used_gas().0 + GAS_FOR_BATCH_CALLBACK_RESERVE.0)
+                    .expect("ERR_NOT_ENOUGH_GAS_FOR_BATCH");
+                return promise
+                    .then(ext_self::on_batch_callback(
+                        proposal_id,
+                        step as u64 + 1,
+                        env::current_account_id(),
+                        0,
+                        Gas(callback_gas),
+                    ))
+                    .into();
+            }
+        }
+        self.internal_return_bonds(policy, proposal)
+    }
+
+    /// Executes given kind of the proposal. Returns the promise if the execution is asynchronous.
+    fn internal_execute_proposal_kind(
+        &mut self,
+        policy: &Policy,
+        proposal: &Proposal,
+        proposal_kind: &ProposalKind,
+    ) -> PromiseOrValue<()> {
+        match proposal_kind {
             ProposalKind::ChangeConfig { config } => {
                 self.config.set(config);
                 PromiseOrValue::Value(())
@@ -386,38 +760,84 @@ impl Contract {
             ProposalKind::ChangePolicyAddOrUpdateRole { role } => {
                 let mut new_policy = policy.clone();
                 new_policy.add_or_update_role(role);
+                new_policy.assert_valid();
                 self.policy.set(&VersionedPolicy::Current(new_policy));
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyRemoveRole { role } => {
                 let mut new_policy = policy.clone();
                 new_policy.remove_role(role);
+                new_policy.assert_valid();
                 self.policy.set(&VersionedPolicy::Current(new_policy));
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyUpdateDefaultVotePolicy { vote_policy } => {
                 let mut new_policy = policy.clone();
                 new_policy.update_default_vote_policy(vote_policy);
+                new_policy.assert_valid();
                 self.policy.set(&VersionedPolicy::Current(new_policy));
                 PromiseOrValue::Value(())
             }
             ProposalKind::ChangePolicyUpdateParameters { parameters } => {
                 let mut new_policy = policy.clone();
                 new_policy.update_parameters(parameters);
+                new_policy.assert_valid();
                 self.policy.set(&VersionedPolicy::Current(new_policy));
                 PromiseOrValue::Value(())
             }
-        };
-        match result {
-            PromiseOrValue::Promise(promise) => promise
-                .then(ext_self::on_proposal_callback(
-                    proposal_id,
-                    env::current_account_id(),
-                    0,
-                    GAS_FOR_FT_TRANSFER,
-                ))
-                .into(),
-            PromiseOrValue::Value(()) => self.internal_return_bonds(&policy, &proposal).into(),
+            ProposalKind::Batch { .. } => env::panic_str("ERR_INVALID_BATCH"),
+            ProposalKind::Stream {
+                receiver_id,
+                token_id,
+                amount,
+                start,
+                cliff,
+                end,
+            } => {
+                self.internal_add_stream(receiver_id, token_id, *amount, *start, *cliff, *end);
+                PromiseOrValue::Value(())
+            }
+            ProposalKind::CancelStream { stream_id } => {
+                self.internal_cancel_stream(*stream_id);
+                PromiseOrValue::Value(())
+            }
+            ProposalKind::RecurringPayment {
+                receiver_id,
+                token_id,
+                amount,
+                interval,
+                count,
+            } => {
+                self.internal_add_recurring_payment(
+                    receiver_id,
+                    token_id,
+                    *amount,
+                    *interval,
+                    *count,
+                );
+                PromiseOrValue::Value(())
+            }
+            ProposalKind::CancelRecurringPayment { payment_id } => {
+                self.internal_cancel_recurring_payment(*payment_id);
+                PromiseOrValue::Value(())
+            }
+        }
+    }
+
+    /// Executes approved proposal, or queues it if its kind has a timelock.
+    /// Proposal that was already queued (e.g. re-finalized after failing) is executed right away.
+    fn internal_approve_proposal(
+        &mut self,
+        policy: &Policy,
+        proposal: &mut Proposal,
+        proposal_id: u64,
+    ) {
+        let timelock = policy.timelock(&proposal.kind);
+        if timelock > 0 && proposal.execution_time.is_none() {
+            proposal.status = ProposalStatus::Queued;
+            proposal.execution_time = Some(U64::from(env::block_timestamp() + timelock));
+        } else {
+            self.internal_execute_proposal(policy, proposal, proposal_id);
         }
     }
 
@@ -437,14 +857,27 @@ impl Contract {
             }
         }
         proposal.status = ProposalStatus::Approved;
-        self.internal_return_bonds(&policy, &proposal).into()
+        self.internal_return_bonds(&policy, &proposal)
     }
 
+    /// Marks proposal as failed, with its funds reserved again until it's re-executed or expires.
     pub(crate) fn internal_callback_proposal_fail(
         &mut self,
         proposal: &mut Proposal,
+        failed_step: Option<u64>,
     ) -> PromiseOrValue<()> {
+        let failed_kind = match (&proposal.kind, failed_step) {
+            (ProposalKind::Batch { kinds }, Some(step)) => &kinds.0[step as usize],
+            (kind, _) => kind,
+        };
+        self.internal_restore_payouts(failed_kind);
         proposal.status = ProposalStatus::Failed;
+        if let ProposalKind::Batch { .. } = proposal.kind {
+            proposal.failed_step = failed_step;
+        }
+        for kind in proposal.reserved_kinds() {
+            self.internal_reserve_payouts(kind, false);
+        }
         PromiseOrValue::Value(())
     }
 
@@ -455,9 +888,14 @@ impl Contract {
         proposal: &Proposal,
         return_bonds: bool,
     ) -> PromiseOrValue<()> {
+        for kind in proposal.reserved_kinds() {
+            self.internal_release_payouts(kind);
+        }
         if return_bonds {
             // Return bond to the proposer.
             self.internal_return_bonds(policy, proposal);
+        } else {
+            self.internal_slash_bonds(policy, proposal);
         }
         match &proposal.kind {
             ProposalKind::BountyDone {
@@ -470,34 +908,62 @@ impl Contract {
         }
     }
 
-    pub(crate) fn internal_user_info(&self) -> UserInfo {
-        let account_id = env::predecessor_account_id();
-        UserInfo {
-            amount: self.get_user_weight(&account_id),
-            account_id,
+    /// Unlocks bonds of the removed proposal and sends $NEAR bonds where the policy says.
+    /// Token bonds are kept in the treasury, the only destination policies with a bond token can set.
+    fn internal_slash_bonds(&mut self, policy: &Policy, proposal: &Proposal) {
+        let mut bonds = vec![(proposal.bond_token.clone(), proposal.bond_amount(policy))];
+        if let ProposalKind::BountyDone { .. } = proposal.kind {
+            bonds.push((None, policy.bounty_bond.0));
+        }
+        let voters: Vec<&AccountId> = proposal
+            .votes
+            .iter()
+            .filter(|(_, vote)| **vote == Vote::Remove)
+            .map(|(account_id, _)| account_id)
+            .collect();
+        for (token_id, amount) in bonds {
+            match &token_id {
+                None => self.locked_amount -= amount,
+                Some(token_id) => {
+                    self.internal_release(&token_id.to_string(), amount);
+                    continue;
+                }
+            }
+            match policy.bond_slashing {
+                BondSlashing::Burn => {
+                    self.internal_payout(
+                        &token_id,
+                        &BURN_ACCOUNT_ID.parse().unwrap(),
+                        amount,
+                        "Bond burn".to_string(),
+                        None,
+                    );
+                }
+                BondSlashing::Treasury => {}
+                BondSlashing::Voters => {
+                    let share = amount.checked_div(voters.len() as u128).unwrap_or_default();
+                    if share == 0 {
+                        continue;
+                    }
+                    for voter in &voters {
+                        self.internal_payout(
+                            &token_id,
+                            voter,
+                            share,
+                            "Bond share for removing the proposal".to_string(),
+                            None,
+                        );
+                    }
+                }
+            }
         }
     }
-}
 
-#[near_bindgen]
-impl Contract {
-    /// Add proposal to this DAO.
-    #[payable]
-    pub fn add_proposal(&mut self, proposal: ProposalInput) -> u64 {
-        // 0. validate bond attached.
-        // TODO: consider bond in the token of this DAO.
-        let policy = self.policy.get().unwrap().to_policy();
-
-        assert_eq!(
-            env::attached_deposit(),
-            policy.proposal_bond.0,
-            "ERR_MIN_BOND"
-        );
-
-        // 1. Validate proposal.
-        match &proposal.kind {
+    /// Validates given kind of the proposal being added, and each batched kind of a batch.
+    fn internal_validate_proposal_kind(&self, policy: &Policy, proposal_kind: &ProposalKind) {
+        match proposal_kind {
             ProposalKind::ChangePolicy { policy } => match policy {
-                VersionedPolicy::Current(_) => {}
+                VersionedPolicy::Current(policy) => policy.assert_valid(),
                 _ => panic!("ERR_INVALID_POLICY"),
             },
             ProposalKind::Transfer { token_id, msg, .. } => {
@@ -510,30 +976,139 @@ impl Contract {
                 self.staking_id.is_none(),
                 "ERR_STAKING_CONTRACT_CANT_CHANGE"
             ),
+            ProposalKind::Batch { kinds } => {
+                assert!(
+                    !kinds.0.is_empty()
+                        && kinds.0.iter().all(|kind| !matches!(
+                            kind,
+                            ProposalKind::Batch { .. } | ProposalKind::BountyDone { .. }
+                        )),
+                    "ERR_INVALID_BATCH"
+                );
+                assert!(
+                    policy.has_uniform_weight_kind(proposal_kind),
+                    "ERR_BATCH_MIXED_WEIGHT_KIND"
+                );
+                for kind in &kinds.0 {
+                    self.internal_validate_proposal_kind(policy, kind);
+                }
+            }
+            ProposalKind::Stream {
+                amount,
+                start,
+                cliff,
+                end,
+                ..
+            } => assert!(
+                amount.0 > 0 && start.0 <= cliff.0 && cliff.0 <= end.0 && start.0 < end.0,
+                "ERR_INVALID_STREAM"
+            ),
+            ProposalKind::CancelStream { stream_id } => {
+                let stream: Stream = self.streams.get(stream_id).expect("ERR_NO_STREAM").into();
+                assert!(stream.cancel_time.is_none(), "ERR_STREAM_CANCELLED");
+            }
+            ProposalKind::RecurringPayment {
+                amount,
+                interval,
+                count,
+                ..
+            } => assert!(
+                amount.0 > 0
+                    && interval.0 > 0
+                    && *count > 0
+                    && amount.0.checked_mul(*count as u128).is_some(),
+                "ERR_INVALID_RECURRING_PAYMENT"
+            ),
+            ProposalKind::CancelRecurringPayment { payment_id } => {
+                let payment: RecurringPayment = self
+                    .recurring_payments
+                    .get(payment_id)
+                    .expect("ERR_NO_RECURRING_PAYMENT")
+                    .into();
+                assert!(!payment.cancelled, "ERR_RECURRING_PAYMENT_CANCELLED");
+            }
             // TODO: add more verifications.
             _ => {}
         };
+    }
+
+    pub(crate) fn internal_user_info(&self) -> UserInfo {
+        let account_id = env::predecessor_account_id();
+        UserInfo {
+            amount: self.get_user_weight(&account_id),
+            account_id,
+        }
+    }
+
+    /// Adds proposal by given proposer, with the `bond` already paid in `bond_token` or $NEAR.
+    pub(crate) fn internal_add_proposal(
+        &mut self,
+        proposer: AccountId,
+        proposal: ProposalInput,
+        bond_token: Option<AccountId>,
+        bond: Balance,
+    ) -> u64 {
+        let policy = self.policy.get().unwrap().to_policy();
 
-        // 2. Check permission of caller to add this type of proposal.
+        // 1. Validate proposal.
+        self.internal_validate_proposal_kind(&policy, &proposal.kind);
+
+        // 2. Check permission of proposer to add this type of proposal.
+        let user_info = UserInfo {
+            amount: self.get_user_weight(&proposer),
+            account_id: proposer.clone(),
+        };
         assert!(
             policy
-                .can_execute_action(
-                    self.internal_user_info(),
-                    &proposal.kind,
-                    &Action::AddProposal
-                )
+                .can_execute_action(user_info, &proposal.kind, &Action::AddProposal)
                 .1,
             "ERR_PERMISSION_DENIED"
         );
 
-        // 3. Actually add proposal to the current list of proposals.
+        // 3. Lock the bond and reserve funds paid out by the proposal, if the treasury can cover them.
+        match &bond_token {
+            None => self.locked_amount += bond,
+            Some(token_id) => self.internal_reserve(&token_id.to_string(), bond),
+        }
+        self.internal_reserve_payouts(&proposal.kind, true);
+
+        // 4. Actually add proposal to the current list of proposals.
         let id = self.last_proposal_id;
+        let mut proposal: Proposal = proposal.into();
+        proposal.proposer = proposer;
+        proposal.bond_token = bond_token;
+        proposal.bond = Some(U128(bond));
         self.proposals
-            .insert(&id, &VersionedProposal::Default(proposal.into()));
+            .insert(&id, &VersionedProposal::Current(proposal));
         self.last_proposal_id += 1;
-        self.locked_amount += env::attached_deposit();
         id
     }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add proposal to this DAO.
+    #[payable]
+    pub fn add_proposal(&mut self, proposal: ProposalInput) -> u64 {
+        // 0. validate bond attached.
+        // Bond in a token is paid by adding the proposal via `ft_transfer_call` to this DAO.
+        let policy = self.policy.get().unwrap().to_policy();
+        assert!(
+            policy.proposal_bond_token.is_none() || policy.proposal_bond.0 == 0,
+            "ERR_BOND_IN_TOKEN"
+        );
+        assert_eq!(
+            env::attached_deposit(),
+            policy.proposal_bond.0,
+            "ERR_MIN_BOND"
+        );
+        self.internal_add_proposal(
+            env::predecessor_account_id(),
+            proposal,
+            None,
+            policy.proposal_bond.0,
+        )
+    }
 
     /// Act on given proposal by id, if permissions allow.
     /// Memo is logged but not stored in the state. Can be used to leave notes or explain the action.
@@ -543,32 +1118,66 @@ impl Contract {
         // Check permissions for the given action.
         let (roles, allowed) =
             policy.can_execute_action(self.internal_user_info(), &proposal.kind, &action);
-        assert!(allowed, "ERR_PERMISSION_DENIED");
+        // Queued proposals are executed by anyone and cancelled by the guardian.
+        assert!(
+            allowed || matches!(action, Action::Execute | Action::Cancel),
+            "ERR_PERMISSION_DENIED"
+        );
         let sender_id = env::predecessor_account_id();
         // Update proposal given action. Returns true if should be updated in storage.
         let update = match action {
             Action::AddProposal => env::panic_str("ERR_WRONG_ACTION"),
             Action::RemoveProposal => {
+                // Bonds and funds of a pending proposal are still locked.
+                // Bond is slashed while the proposal is voted on, and returned once it was approved.
+                match proposal.status {
+                    ProposalStatus::InProgress => {
+                        self.internal_reject_proposal(&policy, &proposal, false);
+                    }
+                    ProposalStatus::Queued | ProposalStatus::Failed => {
+                        self.internal_reject_proposal(&policy, &proposal, true);
+                    }
+                    _ => {}
+                }
                 self.proposals.remove(&id);
                 false
             }
+            Action::VoteRetract => {
+                assert!(
+                    matches!(proposal.status, ProposalStatus::InProgress),
+                    "ERR_PROPOSAL_NOT_READY_FOR_VOTE"
+                );
+                proposal.retract_vote(&sender_id, &policy);
+                true
+            }
             Action::VoteApprove | Action::VoteReject | Action::VoteRemove => {
                 assert!(
                     matches!(proposal.status, ProposalStatus::InProgress),
                     "ERR_PROPOSAL_NOT_READY_FOR_VOTE"
                 );
+                let vote = Vote::from(action);
+                // A voter changing their mind replaces the previous vote.
+                if let Some(previous_vote) = proposal.votes.get(&sender_id) {
+                    assert!(*previous_vote != vote, "ERR_ALREADY_VOTED");
+                    proposal.retract_vote(&sender_id, &policy);
+                }
+                // Votes and the total supply are weighed as of the proposal submission.
+                let submission_time = proposal.submission_time.0;
                 proposal.update_votes(
                     &sender_id,
                     &roles,
-                    Vote::from(action),
+                    vote,
                     &policy,
-                    self.get_user_weight(&sender_id),
+                    self.get_user_weight_at(&sender_id, submission_time),
                 );
                 // Updates proposal status with new votes using the policy.
-                proposal.status =
-                    policy.proposal_status(&proposal, roles, self.total_delegation_amount);
+                proposal.status = policy.proposal_status(
+                    &proposal,
+                    roles,
+                    self.get_total_delegation_at(submission_time),
+                );
                 if proposal.status == ProposalStatus::Approved {
-                    self.internal_execute_proposal(&policy, &proposal, id);
+                    self.internal_approve_proposal(&policy, &mut proposal, id);
                     true
                 } else if proposal.status == ProposalStatus::Removed {
                     self.internal_reject_proposal(&policy, &proposal, false);
@@ -582,22 +1191,31 @@ impl Contract {
                     true
                 }
             }
-            // There are two cases when proposal must be finalized manually: expired or failed.
+            // There are three cases when proposal must be finalized manually: expired, failed or
+            // approved before the minimum voting period passed.
             // In case of failed, we just recompute the status and if it still approved, we re-execute the proposal.
             // In case of expired, we reject the proposal and return the bond.
+            // In case of approved early, we recompute the status once the period passed and execute it.
             // Corner cases:
             //  - if proposal expired during the failed state - it will be marked as expired.
             //  - if the number of votes in the group has changed (new members has been added) -
             //      the proposal can loose it's approved state. In this case new proposal needs to be made, this one can only expire.
             Action::Finalize => {
+                // Steps of a failed batch that succeeded can't be undone, so it can only expire.
+                let retry_batch = proposal.status == ProposalStatus::Failed
+                    && matches!(proposal.kind, ProposalKind::Batch { .. });
                 proposal.status = policy.proposal_status(
                     &proposal,
                     policy.roles.iter().map(|r| r.name.clone()).collect(),
-                    self.total_delegation_amount,
+                    self.get_total_delegation_at(proposal.submission_time.0),
+                );
+                assert!(
+                    !retry_batch || proposal.status == ProposalStatus::Expired,
+                    "ERR_BATCH_FAILED"
                 );
                 match proposal.status {
                     ProposalStatus::Approved => {
-                        self.internal_execute_proposal(&policy, &proposal, id);
+                        self.internal_approve_proposal(&policy, &mut proposal, id);
                     }
                     ProposalStatus::Expired => {
                         self.internal_reject_proposal(&policy, &proposal, true);
@@ -609,10 +1227,40 @@ impl Contract {
                 true
             }
             Action::MoveToHub => false,
+            Action::Execute => {
+                assert!(
+                    matches!(proposal.status, ProposalStatus::Queued),
+                    "ERR_PROPOSAL_NOT_QUEUED"
+                );
+                assert!(
+                    env::block_timestamp() >= proposal.execution_time.unwrap().0,
+                    "ERR_PROPOSAL_TIMELOCKED"
+                );
+                proposal.status = ProposalStatus::Approved;
+                self.internal_execute_proposal(&policy, &proposal, id);
+                true
+            }
+            Action::Cancel => {
+                assert!(
+                    policy.is_guardian(&self.internal_user_info()),
+                    "ERR_PERMISSION_DENIED"
+                );
+                assert!(
+                    matches!(proposal.status, ProposalStatus::Queued),
+                    "ERR_PROPOSAL_NOT_QUEUED"
+                );
+                assert!(
+                    env::block_timestamp() < proposal.execution_time.unwrap().0,
+                    "ERR_TIMELOCK_PASSED"
+                );
+                proposal.status = ProposalStatus::Cancelled;
+                self.internal_reject_proposal(&policy, &proposal, true);
+                true
+            }
         };
         if update {
             self.proposals
-                .insert(&id, &VersionedProposal::Default(proposal));
+                .insert(&id, &VersionedProposal::Current(proposal));
         }
         if let Some(memo) = memo {
             log!("Memo: {}", memo);
@@ -638,10 +1286,40 @@ impl Contract {
         let result = match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_) => self.internal_callback_proposal_success(&mut proposal),
-            PromiseResult::Failed => self.internal_callback_proposal_fail(&mut proposal),
+            PromiseResult::Failed => self.internal_callback_proposal_fail(&mut proposal, None),
+        };
+        self.proposals
+            .insert(&proposal_id, &VersionedProposal::Current(proposal));
+        result
+    }
+
+    /// Receiving callback after an asynchronous step of a batch proposal.
+    /// If successful, executes the remaining steps and returns the bond once all are done.
+    /// If the step failed, move the whole batch to "Failed" state.
+    #[private]
+    pub fn on_batch_callback(&mut self, proposal_id: u64, next_step: u64) -> PromiseOrValue<()> {
+        let mut proposal: Proposal = self
+            .proposals
+            .get(&proposal_id)
+            .expect("ERR_NO_PROPOSAL")
+            .into();
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        let result = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                let policy = self.policy.get().unwrap().to_policy();
+                self.internal_execute_batch(&policy, &proposal, proposal_id, next_step)
+            }
+            PromiseResult::Failed => {
+                self.internal_callback_proposal_fail(&mut proposal, Some(next_step - 1))
+            }
         };
         self.proposals
-            .insert(&proposal_id, &VersionedProposal::Default(proposal.into()));
+            .insert(&proposal_id, &VersionedProposal::Current(proposal));
         result
     }
 }
@@ -0,0 +1,328 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, PromiseOrValue};
+
+use crate::types::{convert_old_to_new_token, OldAccountId, GAS_FOR_FT_TRANSFER};
+use crate::*;
+
+/// Payment of the same amount to the receiver every `interval`, `count` times.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecurringPayment {
+    /// Account receiving the payments.
+    pub receiver_id: AccountId,
+    /// Token the payments are made in.
+    /// Can be "" for $NEAR or a valid account id.
+    pub token_id: OldAccountId,
+    /// Amount paid each period.
+    pub amount: U128,
+    /// Length of a period.
+    pub interval: U64,
+    /// Number of payments to make.
+    pub count: u32,
+    /// Time the schedule was created at. First payment is due one interval after.
+    pub start_time: U64,
+    /// Number of payments already made.
+    pub paid_count: u32,
+    /// If cancelled, no more payments are made.
+    pub cancelled: bool,
+}
+
+impl RecurringPayment {
+    /// Returns number of payments due by given time, made or not.
+    pub fn due_count(&self, timestamp: u64) -> u32 {
+        let periods = timestamp.saturating_sub(self.start_time.0) / self.interval.0;
+        std::cmp::min(periods, self.count as u64) as u32
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum VersionedRecurringPayment {
+    Default(RecurringPayment),
+}
+
+impl From<VersionedRecurringPayment> for RecurringPayment {
+    fn from(v: VersionedRecurringPayment) -> Self {
+        match v {
+            VersionedRecurringPayment::Default(p) => p,
+        }
+    }
+}
+
+impl Contract {
+    /// Adds recurring payment to the storage and returns it's id.
+    /// All of its payments are reserved in the treasury until made or cancelled.
+    pub(crate) fn internal_add_recurring_payment(
+        &mut self,
+        receiver_id: &AccountId,
+        token_id: &OldAccountId,
+        amount: U128,
+        interval: U64,
+        count: u32,
+    ) -> u64 {
+        let id = self.last_recurring_payment_id;
+        let payment = RecurringPayment {
+            receiver_id: receiver_id.clone(),
+            token_id: token_id.clone(),
+            amount,
+            interval,
+            count,
+            start_time: U64::from(env::block_timestamp()),
+            paid_count: 0,
+            cancelled: false,
+        };
+        self.internal_reserve(token_id, amount.0 * count as u128);
+        self.recurring_payments
+            .insert(&id, &VersionedRecurringPayment::Default(payment));
+        self.last_recurring_payment_id += 1;
+        id
+    }
+
+    /// Stops given recurring payment. Payments due but not made yet are dropped.
+    pub(crate) fn internal_cancel_recurring_payment(&mut self, id: u64) {
+        let mut payment: RecurringPayment = self
+            .recurring_payments
+            .get(&id)
+            .expect("ERR_NO_RECURRING_PAYMENT")
+            .into();
+        assert!(!payment.cancelled, "ERR_RECURRING_PAYMENT_CANCELLED");
+        payment.cancelled = true;
+        self.internal_release(
+            &payment.token_id,
+            payment.amount.0 * (payment.count - payment.paid_count) as u128,
+        );
+        self.recurring_payments
+            .insert(&id, &VersionedRecurringPayment::Default(payment));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Makes the next due payment of given recurring payment. Can be called by anyone.
+    /// If the payout fails, the payment stays due and can be made again.
+    pub fn pay_recurring_payment(&mut self, id: u64) -> PromiseOrValue<()> {
+        let mut payment: RecurringPayment = self
+            .recurring_payments
+            .get(&id)
+            .expect("ERR_NO_RECURRING_PAYMENT")
+            .into();
+        assert!(!payment.cancelled, "ERR_RECURRING_PAYMENT_CANCELLED");
+        assert!(
+            payment.paid_count < payment.due_count(env::block_timestamp()),
+            "ERR_PAYMENT_NOT_DUE"
+        );
+        self.internal_release(&payment.token_id, payment.amount.0);
+        assert!(
+            self.internal_has_funds(&payment.token_id, payment.amount.0),
+            "ERR_NOT_ENOUGH_FUNDS"
+        );
+        payment.paid_count += 1;
+        self.recurring_payments
+            .insert(&id, &VersionedRecurringPayment::Default(payment.clone()));
+        match self.internal_payout(
+            &convert_old_to_new_token(&payment.token_id),
+            &payment.receiver_id,
+            payment.amount.0,
+            format!("Recurring payment {} #{}", id, payment.paid_count),
+            None,
+        ) {
+            PromiseOrValue::Promise(promise) => promise
+                .then(ext_self::on_recurring_payment(
+                    id,
+                    env::current_account_id(),
+                    0,
+                    GAS_FOR_FT_TRANSFER,
+                ))
+                .into(),
+            PromiseOrValue::Value(()) => PromiseOrValue::Value(()),
+        }
+    }
+
+    /// Receiving callback after the recurring payment was paid out.
+    /// If the payout failed, the payment is due again and its funds are reserved back.
+    #[private]
+    pub fn on_recurring_payment(&mut self, id: u64) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            let mut payment: RecurringPayment = self
+                .recurring_payments
+                .get(&id)
+                .expect("ERR_NO_RECURRING_PAYMENT")
+                .into();
+            payment.paid_count -= 1;
+            if payment.token_id != OLD_BASE_TOKEN {
+                self.internal_deposit_token(&payment.token_id, payment.amount.0);
+            }
+            if !payment.cancelled {
+                self.internal_reserve(&payment.token_id, payment.amount.0);
+            }
+            self.recurring_payments
+                .insert(&id, &VersionedRecurringPayment::Default(payment));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+    use near_sdk_sim::to_yocto;
+
+    use crate::proposals::{ProposalInput, ProposalKind};
+    use crate::{Action, Config};
+
+    use super::*;
+
+    fn add_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kind: ProposalKind,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let id = contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind,
+        });
+        contract.act_proposal(id, Action::VoteApprove, None);
+        id
+    }
+
+    fn setup_recurring_payment(amount: u128) -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .account_balance(to_yocto("1000"))
+            .build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::RecurringPayment {
+                receiver_id: accounts(2),
+                token_id: String::from(OLD_BASE_TOKEN),
+                amount: U128(amount),
+                interval: U64::from(100),
+                count: 2,
+            },
+        );
+        (context, contract)
+    }
+
+    /// Pays 1N every 100ns twice, with anyone triggering the payments.
+    #[test]
+    fn test_recurring_payment_lifecycle() {
+        let (mut context, mut contract) = setup_recurring_payment(to_yocto("1"));
+        assert_eq!(contract.get_last_recurring_payment_id(), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(100)
+            .attached_deposit(0)
+            .build());
+        contract.pay_recurring_payment(0);
+        assert_eq!(
+            contract
+                .get_recurring_payment(0)
+                .recurring_payment
+                .paid_count,
+            1
+        );
+
+        testing_env!(context.block_timestamp(250).build());
+        contract.pay_recurring_payment(0);
+        assert_eq!(
+            contract
+                .get_recurring_payment(0)
+                .recurring_payment
+                .paid_count,
+            2
+        );
+    }
+
+    /// Payment that failed to pay out is due again, with its funds reserved until cancelled.
+    #[test]
+    fn test_recurring_payment_failed() {
+        let (mut context, mut contract) = setup_recurring_payment(to_yocto("1"));
+        let reserved = |contract: &Contract| {
+            contract
+                .internal_treasury_balance(String::from(OLD_BASE_TOKEN))
+                .reserved
+        };
+        assert_eq!(reserved(&contract), U128(to_yocto("2")));
+
+        testing_env!(context.block_timestamp(100).attached_deposit(0).build());
+        contract.pay_recurring_payment(0);
+        assert_eq!(reserved(&contract), U128(to_yocto("1")));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_recurring_payment(0);
+        assert_eq!(
+            contract
+                .get_recurring_payment(0)
+                .recurring_payment
+                .paid_count,
+            0
+        );
+        assert_eq!(reserved(&contract), U128(to_yocto("2")));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::CancelRecurringPayment { payment_id: 0 },
+        );
+        assert_eq!(reserved(&contract), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAYMENT_NOT_DUE")]
+    fn test_recurring_payment_not_due() {
+        let (mut context, mut contract) = setup_recurring_payment(to_yocto("1"));
+        testing_env!(context.block_timestamp(150).attached_deposit(0).build());
+        contract.pay_recurring_payment(0);
+        contract.pay_recurring_payment(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_FUNDS")]
+    fn test_recurring_payment_not_enough_funds() {
+        let (mut context, mut contract) = setup_recurring_payment(to_yocto("10"));
+        // The DAO account is drained below the 20N reserved for the payments.
+        testing_env!(context
+            .block_timestamp(100)
+            .attached_deposit(0)
+            .account_balance(to_yocto("5"))
+            .build());
+        contract.pay_recurring_payment(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECURRING_PAYMENT_CANCELLED")]
+    fn test_cancelled_recurring_payment() {
+        let (mut context, mut contract) = setup_recurring_payment(to_yocto("1"));
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::CancelRecurringPayment { payment_id: 0 },
+        );
+        testing_env!(context.block_timestamp(100).attached_deposit(0).build());
+        contract.pay_recurring_payment(0);
+    }
+}
@@ -0,0 +1,354 @@
+use std::cmp::min;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, Balance, PromiseOrValue};
+
+use crate::types::{convert_old_to_new_token, OldAccountId, GAS_FOR_FT_TRANSFER};
+use crate::*;
+
+/// Payment vesting linearly from `start` to `end`, held by the DAO until claimed by the receiver.
+/// Nothing can be claimed before the `cliff`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stream {
+    /// Account receiving the payment.
+    pub receiver_id: AccountId,
+    /// Token the stream is paid out in.
+    /// Can be "" for $NEAR or a valid account id.
+    pub token_id: OldAccountId,
+    /// Total amount to be paid out by the end.
+    pub amount: U128,
+    /// Time vesting starts at.
+    pub start: U64,
+    /// Time before which nothing can be claimed.
+    pub cliff: U64,
+    /// Time the full amount is vested at.
+    pub end: U64,
+    /// Amount already claimed by the receiver.
+    pub claimed: U128,
+    /// Time the stream was cancelled at. Nothing vests after it.
+    pub cancel_time: Option<U64>,
+}
+
+impl Stream {
+    /// Returns amount vested by given time.
+    pub fn vested_amount(&self, timestamp: u64) -> Balance {
+        let timestamp = self
+            .cancel_time
+            .map_or(timestamp, |cancel_time| min(timestamp, cancel_time.0));
+        if timestamp < self.cliff.0 {
+            0
+        } else if timestamp >= self.end.0 {
+            self.amount.0
+        } else {
+            // Split to avoid overflowing on large amounts over long durations.
+            let elapsed = (timestamp - self.start.0) as u128;
+            let duration = (self.end.0 - self.start.0) as u128;
+            self.amount.0 / duration * elapsed + self.amount.0 % duration * elapsed / duration
+        }
+    }
+
+    /// Returns amount that can be claimed at given time.
+    pub fn claimable_amount(&self, timestamp: u64) -> Balance {
+        self.vested_amount(timestamp) - self.claimed.0
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum VersionedStream {
+    Default(Stream),
+}
+
+impl From<VersionedStream> for Stream {
+    fn from(v: VersionedStream) -> Self {
+        match v {
+            VersionedStream::Default(s) => s,
+        }
+    }
+}
+
+impl Contract {
+    /// Adds stream to the storage and returns it's id.
+    /// Streamed amount is reserved in the treasury until claimed or cancelled, same as pending payouts.
+    pub(crate) fn internal_add_stream(
+        &mut self,
+        receiver_id: &AccountId,
+        token_id: &OldAccountId,
+        amount: U128,
+        start: U64,
+        cliff: U64,
+        end: U64,
+    ) -> u64 {
+        let id = self.last_stream_id;
+        let stream = Stream {
+            receiver_id: receiver_id.clone(),
+            token_id: token_id.clone(),
+            amount,
+            start,
+            cliff,
+            end,
+            claimed: U128(0),
+            cancel_time: None,
+        };
+        self.internal_reserve(token_id, amount.0);
+        self.streams.insert(&id, &VersionedStream::Default(stream));
+        self.last_stream_id += 1;
+        id
+    }
+
+    /// Stops vesting of given stream. What's vested stays claimable, the rest returns to the DAO.
+    pub(crate) fn internal_cancel_stream(&mut self, id: u64) {
+        let mut stream: Stream = self.streams.get(&id).expect("ERR_NO_STREAM").into();
+        assert!(stream.cancel_time.is_none(), "ERR_STREAM_CANCELLED");
+        let unvested_amount = stream.amount.0 - stream.vested_amount(env::block_timestamp());
+        self.internal_release(&stream.token_id, unvested_amount);
+        stream.cancel_time = Some(U64::from(env::block_timestamp()));
+        self.streams.insert(&id, &VersionedStream::Default(stream));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pays out to the receiver what's vested from given stream since the last claim.
+    pub fn claim_stream(&mut self, id: u64) -> PromiseOrValue<()> {
+        let mut stream: Stream = self.streams.get(&id).expect("ERR_NO_STREAM").into();
+        assert_eq!(
+            stream.receiver_id,
+            env::predecessor_account_id(),
+            "ERR_STREAM_CLAIM_MUST_BE_RECEIVER"
+        );
+        let amount = stream.claimable_amount(env::block_timestamp());
+        assert!(amount > 0, "ERR_NOTHING_TO_CLAIM");
+        stream.claimed = U128(stream.claimed.0 + amount);
+        self.internal_release(&stream.token_id, amount);
+        self.streams
+            .insert(&id, &VersionedStream::Default(stream.clone()));
+        match self.internal_payout(
+            &convert_old_to_new_token(&stream.token_id),
+            &stream.receiver_id,
+            amount,
+            format!("Stream {} claim", id),
+            None,
+        ) {
+            PromiseOrValue::Promise(promise) => promise
+                .then(ext_self::on_claim_stream(
+                    id,
+                    U128(amount),
+                    env::current_account_id(),
+                    0,
+                    GAS_FOR_FT_TRANSFER,
+                ))
+                .into(),
+            PromiseOrValue::Value(()) => PromiseOrValue::Value(()),
+        }
+    }
+
+    /// Receiving callback after the stream claim was paid out.
+    /// If the payout failed, the claimed amount is held by the DAO again and can be claimed later.
+    #[private]
+    pub fn on_claim_stream(&mut self, id: u64, amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        if let PromiseResult::Failed = env::promise_result(0) {
+            let mut stream: Stream = self.streams.get(&id).expect("ERR_NO_STREAM").into();
+            stream.claimed = U128(stream.claimed.0 - amount.0);
+            if stream.token_id != OLD_BASE_TOKEN {
+                self.internal_deposit_token(&stream.token_id, amount.0);
+            }
+            self.internal_reserve(&stream.token_id, amount.0);
+            self.streams.insert(&id, &VersionedStream::Default(stream));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+    use near_sdk_sim::to_yocto;
+
+    use crate::proposals::{ProposalInput, ProposalKind};
+    use crate::{Action, Config};
+
+    use super::*;
+
+    fn add_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kind: ProposalKind,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        let id = contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind,
+        });
+        contract.act_proposal(id, Action::VoteApprove, None);
+        id
+    }
+
+    fn near_reserved(contract: &Contract) -> u128 {
+        contract
+            .internal_treasury_balance(String::from(OLD_BASE_TOKEN))
+            .reserved
+            .0
+    }
+
+    /// Streams 100N vesting from 0 to 1000 with a cliff at 100, cancels it midway and claims the rest.
+    #[test]
+    fn test_stream_lifecycle() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .account_balance(to_yocto("1000"))
+            .build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::Stream {
+                receiver_id: accounts(1),
+                token_id: String::from(OLD_BASE_TOKEN),
+                amount: U128(to_yocto("100")),
+                start: U64::from(0),
+                cliff: U64::from(100),
+                end: U64::from(1_000),
+            },
+        );
+        assert_eq!(contract.get_last_stream_id(), 1);
+        assert_eq!(near_reserved(&contract), to_yocto("100"));
+
+        testing_env!(context.block_timestamp(500).build());
+        contract.claim_stream(0);
+        assert_eq!(contract.get_stream(0).stream.claimed, U128(to_yocto("50")));
+        assert_eq!(near_reserved(&contract), to_yocto("50"));
+
+        testing_env!(context.block_timestamp(600).build());
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::CancelStream { stream_id: 0 },
+        );
+        // Unvested 40N are released, vested 10N stay claimable.
+        assert_eq!(near_reserved(&contract), to_yocto("10"));
+
+        testing_env!(context.block_timestamp(2_000).attached_deposit(0).build());
+        contract.claim_stream(0);
+        assert_eq!(contract.get_stream(0).stream.claimed, U128(to_yocto("60")));
+        assert_eq!(near_reserved(&contract), 0);
+    }
+
+    /// Claim that failed to pay out can be claimed again.
+    #[test]
+    fn test_stream_claim_failed() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .account_balance(to_yocto("1000"))
+            .build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::Stream {
+                receiver_id: accounts(1),
+                token_id: String::from(OLD_BASE_TOKEN),
+                amount: U128(to_yocto("100")),
+                start: U64::from(0),
+                cliff: U64::from(100),
+                end: U64::from(1_000),
+            },
+        );
+        testing_env!(context.block_timestamp(500).attached_deposit(0).build());
+        contract.claim_stream(0);
+        assert_eq!(near_reserved(&contract), to_yocto("50"));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_claim_stream(0, U128(to_yocto("50")));
+        assert_eq!(contract.get_stream(0).stream.claimed, U128(0));
+        assert_eq!(near_reserved(&contract), to_yocto("100"));
+    }
+
+    /// Token stream stays reserved in the treasury until claimed or cancelled.
+    #[test]
+    fn test_token_stream_reserved() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::Stream {
+                receiver_id: accounts(1),
+                token_id: accounts(3).to_string(),
+                amount: U128(100),
+                start: U64::from(0),
+                cliff: U64::from(0),
+                end: U64::from(1_000),
+            },
+        );
+        let reserved = |contract: &Contract| contract.get_treasury_balance(accounts(3)).reserved;
+        assert_eq!(reserved(&contract), U128(100));
+
+        testing_env!(context.block_timestamp(500).attached_deposit(0).build());
+        contract.claim_stream(0);
+        assert_eq!(reserved(&contract), U128(50));
+
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::CancelStream { stream_id: 0 },
+        );
+        assert_eq!(reserved(&contract), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_CLAIM")]
+    fn test_stream_claim_before_cliff() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .account_balance(to_yocto("1000"))
+            .build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        add_proposal(
+            &mut context,
+            &mut contract,
+            ProposalKind::Stream {
+                receiver_id: accounts(1),
+                token_id: String::from(OLD_BASE_TOKEN),
+                amount: U128(to_yocto("100")),
+                start: U64::from(0),
+                cliff: U64::from(100),
+                end: U64::from(1_000),
+            },
+        );
+        testing_env!(context.block_timestamp(99).build());
+        contract.claim_stream(0);
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, serde_json, AccountId, Balance, PromiseOrValue};
+
+use crate::types::{OldAccountId, GAS_FOR_FT_TRANSFER};
+use crate::*;
+
+impl Contract {
+    /// Returns amount of given token that is neither locked nor reserved by pending proposals.
+    pub(crate) fn internal_available_amount(&self, token_id: &OldAccountId) -> Balance {
+        let treasury_balance = self.internal_treasury_balance(token_id.clone());
+        treasury_balance
+            .balance
+            .0
+            .saturating_sub(treasury_balance.reserved.0)
+    }
+
+    /// Returns whether the treasury can pay out given amount of the token.
+    /// Tokens the treasury doesn't track aren't checked, their payouts fail if the DAO doesn't hold enough.
+    pub(crate) fn internal_has_funds(&self, token_id: &OldAccountId, amount: Balance) -> bool {
+        (token_id != OLD_BASE_TOKEN && self.token_balances.get(token_id).is_none())
+            || amount <= self.internal_available_amount(token_id)
+    }
+
+    /// Returns amounts per token that given kind of the proposal pays out once executed.
+    pub(crate) fn internal_proposal_payouts(
+        &self,
+        proposal_kind: &ProposalKind,
+    ) -> HashMap<OldAccountId, Balance> {
+        let kinds = match proposal_kind {
+            ProposalKind::Batch { kinds } => kinds.0.iter().collect(),
+            _ => vec![proposal_kind],
+        };
+        let mut payouts = HashMap::new();
+        for kind in kinds {
+            let (token_id, amount) = match kind {
+                ProposalKind::Transfer {
+                    token_id, amount, ..
+                }
+                | ProposalKind::Stream {
+                    token_id, amount, ..
+                } => (token_id.clone(), amount.0),
+                ProposalKind::RecurringPayment {
+                    token_id,
+                    amount,
+                    count,
+                    ..
+                } => (token_id.clone(), amount.0 * *count as u128),
+                ProposalKind::BountyDone { bounty_id, .. } => match self.bounties.get(bounty_id) {
+                    Some(bounty) => {
+                        let bounty: Bounty = bounty.into();
+                        (bounty.token, bounty.amount.0)
+                    }
+                    None => continue,
+                },
+                _ => continue,
+            };
+            *payouts.entry(token_id).or_default() += amount;
+        }
+        payouts
+    }
+
+    /// Reserves funds paid out by given kind of the proposal until it's executed or rejected.
+    /// If `check_funds`, fails when the treasury can't cover them.
+    pub(crate) fn internal_reserve_payouts(
+        &mut self,
+        proposal_kind: &ProposalKind,
+        check_funds: bool,
+    ) {
+        for (token_id, amount) in self.internal_proposal_payouts(proposal_kind) {
+            if check_funds {
+                assert!(
+                    self.internal_has_funds(&token_id, amount),
+                    "ERR_NOT_ENOUGH_FUNDS"
+                );
+            }
+            self.internal_reserve(&token_id, amount);
+        }
+    }
+
+    /// Releases funds reserved by given kind of the proposal.
+    pub(crate) fn internal_release_payouts(&mut self, proposal_kind: &ProposalKind) {
+        for (token_id, amount) in self.internal_proposal_payouts(proposal_kind) {
+            self.internal_release(&token_id, amount);
+        }
+    }
+
+    /// Reserves given amount of the token, so it's not available to other proposals.
+    pub(crate) fn internal_reserve(&mut self, token_id: &OldAccountId, amount: Balance) {
+        let reserved = self.reserved_amounts.get(token_id).unwrap_or_default();
+        self.reserved_amounts.insert(token_id, &(reserved + amount));
+    }
+
+    /// Releases given reserved amount of the token.
+    pub(crate) fn internal_release(&mut self, token_id: &OldAccountId, amount: Balance) {
+        let reserved = self
+            .reserved_amounts
+            .get(token_id)
+            .unwrap_or_default()
+            .saturating_sub(amount);
+        if reserved == 0 {
+            self.reserved_amounts.remove(token_id);
+        } else {
+            self.reserved_amounts.insert(token_id, &reserved);
+        }
+    }
+
+    /// Records given amount of the token as received by the treasury, if it tracks the token.
+    pub(crate) fn internal_deposit_token(&mut self, token_id: &OldAccountId, amount: Balance) {
+        if let Some(balance) = self.token_balances.get(token_id) {
+            self.token_balances.insert(token_id, &(balance + amount));
+        }
+    }
+
+    /// Records given amount of the token as paid out by the treasury, if it tracks the token.
+    pub(crate) fn internal_withdraw_token(&mut self, token_id: &OldAccountId, amount: Balance) {
+        if let Some(balance) = self.token_balances.get(token_id) {
+            self.token_balances
+                .insert(token_id, &balance.saturating_sub(amount));
+        }
+    }
+
+    /// Records tokens of given kind of the proposal back to the treasury after its payout failed.
+    pub(crate) fn internal_restore_payouts(&mut self, proposal_kind: &ProposalKind) {
+        for (token_id, amount) in self.internal_proposal_payouts(proposal_kind) {
+            if token_id != OLD_BASE_TOKEN {
+                self.internal_deposit_token(&token_id, amount);
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// Records tokens transferred to the DAO via `ft_transfer_call` in the treasury, if it tracks the token.
+    /// If the message is given, it's the proposal to add by the sender with tokens paying the bond.
+    /// Bond token is tracked from its first bond on.
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        if msg.is_empty() {
+            self.internal_deposit_token(&token_id.to_string(), amount.0);
+            log!("Deposit of {} {} from {}", amount.0, token_id, sender_id);
+        } else {
+            let proposal: ProposalInput = serde_json::from_str(&msg).expect("ERR_INVALID_MSG");
+            let policy = self.policy.get().unwrap().to_policy();
+            assert_eq!(
+                policy.proposal_bond_token.as_ref(),
+                Some(&token_id),
+                "ERR_INVALID_BOND_TOKEN"
+            );
+            assert_eq!(amount.0, policy.proposal_bond.0, "ERR_MIN_BOND");
+            let balance = self
+                .token_balances
+                .get(&token_id.to_string())
+                .unwrap_or_default();
+            self.token_balances
+                .insert(&token_id.to_string(), &(balance + amount.0));
+            self.internal_add_proposal(sender_id, proposal, Some(token_id), amount.0);
+        }
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets the treasury balance of given token to what the token contract reports.
+    /// Records tokens received without `ft_transfer_call` or refunded after a payout. Can be called by anyone.
+    /// Starts tracking the token if the treasury doesn't yet, with the caller paying for its storage.
+    /// Deposit beyond the storage cost is refunded to the caller.
+    #[payable]
+    pub fn sync_token_balance(&mut self, token_id: AccountId) -> Promise {
+        let mut storage_cost = 0;
+        if self.token_balances.get(&token_id.to_string()).is_none() {
+            let initial_storage_usage = env::storage_usage();
+            self.token_balances.insert(&token_id.to_string(), &0);
+            storage_cost = (env::storage_usage() - initial_storage_usage) as Balance
+                * env::storage_byte_cost();
+            assert!(
+                env::attached_deposit() >= storage_cost,
+                "ERR_NOT_ENOUGH_DEPOSIT:{}",
+                storage_cost
+            );
+        }
+        let refund = env::attached_deposit() - storage_cost;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.clone(),
+            0,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_self::on_token_balance(
+            token_id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_FT_TRANSFER,
+        ))
+    }
+
+    /// Receiving callback with the balance of given token held by the DAO.
+    #[private]
+    pub fn on_token_balance(&mut self, token_id: AccountId) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "ERR_UNEXPECTED_CALLBACK_PROMISES"
+        );
+        match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                let balance: U128 = serde_json::from_slice(&value).expect("ERR_INVALID_BALANCE");
+                self.token_balances
+                    .insert(&token_id.to_string(), &balance.0);
+            }
+            _ => env::panic_str("ERR_TOKEN_BALANCE_FAILED"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use near_sdk_sim::to_yocto;
+
+    use crate::proposals::{ProposalInput, ProposalKind};
+    use crate::{Action, BondSlashing, Config};
+
+    use super::*;
+
+    fn setup_treasury() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut contract = Contract::new(
+            Config::test_config(),
+            VersionedPolicy::Default(vec![accounts(1)]),
+        );
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.sync_token_balance(accounts(3));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(0)
+            .build());
+        contract.ft_on_transfer(accounts(2), U128(100), String::new());
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        (context, contract)
+    }
+
+    fn add_transfer_proposal(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        token_id: OldAccountId,
+        amount: Balance,
+    ) -> u64 {
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::Transfer {
+                token_id,
+                receiver_id: accounts(2),
+                amount: U128(amount),
+                msg: None,
+            },
+        })
+    }
+
+    /// Reserves deposited tokens for a pending transfer and releases them once it's rejected.
+    #[test]
+    fn test_treasury_reservations() {
+        let (mut context, mut contract) = setup_treasury();
+        let token_id = accounts(3).to_string();
+        let id = add_transfer_proposal(&mut context, &mut contract, token_id.clone(), 60);
+        let balance = contract.get_treasury_balance(accounts(3));
+        assert_eq!(balance.balance, U128(100));
+        assert_eq!(balance.reserved, U128(60));
+        assert_eq!(contract.get_treasury(0, 10).len(), 2);
+        assert_eq!(contract.get_treasury(1, 10)[0].token_id, token_id);
+
+        contract.act_proposal(id, Action::VoteReject, None);
+        assert_eq!(contract.get_treasury_balance(accounts(3)).reserved, U128(0));
+        add_transfer_proposal(&mut context, &mut contract, token_id, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_FUNDS")]
+    fn test_treasury_not_enough_tokens() {
+        let (mut context, mut contract) = setup_treasury();
+        let token_id = accounts(3).to_string();
+        add_transfer_proposal(&mut context, &mut contract, token_id.clone(), 60);
+        add_transfer_proposal(&mut context, &mut contract, token_id, 60);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_FUNDS")]
+    fn test_treasury_not_enough_near() {
+        let (mut context, mut contract) = setup_treasury();
+        add_transfer_proposal(
+            &mut context,
+            &mut contract,
+            String::from(OLD_BASE_TOKEN),
+            to_yocto("1000"),
+        );
+    }
+
+    /// Tokens the treasury doesn't track are paid out without checking the funds, and stay untracked.
+    #[test]
+    fn test_treasury_untracked_token() {
+        let (mut context, mut contract) = setup_treasury();
+        let id = add_transfer_proposal(&mut context, &mut contract, accounts(4).to_string(), 1_000);
+        assert_eq!(
+            contract.get_treasury_balance(accounts(4)).reserved,
+            U128(1_000)
+        );
+        contract.act_proposal(id, Action::VoteApprove, None);
+        assert!(contract
+            .token_balances
+            .get(&accounts(4).to_string())
+            .is_none());
+        assert_eq!(contract.get_treasury_balance(accounts(4)).reserved, U128(0));
+    }
+
+    /// Deposits of tokens the treasury doesn't track aren't recorded.
+    #[test]
+    fn test_treasury_untracked_deposit() {
+        let (mut context, mut contract) = setup_treasury();
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.ft_on_transfer(accounts(2), U128(100), String::new());
+        assert!(contract
+            .token_balances
+            .get(&accounts(4).to_string())
+            .is_none());
+        assert_eq!(contract.get_treasury(0, 10).len(), 2);
+    }
+
+    #[test]
+    fn test_sync_refunds_deposit() {
+        let (mut context, mut contract) = setup_treasury();
+        testing_env!(context.attached_deposit(to_yocto("1")).build());
+        contract.sync_token_balance(accounts(3));
+        let receivers: Vec<AccountId> = get_created_receipts()
+            .into_iter()
+            .map(|receipt| receipt.receiver_id)
+            .collect();
+        assert!(receivers.contains(&accounts(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_DEPOSIT")]
+    fn test_treasury_track_without_deposit() {
+        let (_, mut contract) = setup_treasury();
+        contract.sync_token_balance(accounts(4));
+    }
+
+    /// DAO with proposal bond of 10 tokens of accounts(3).
+    fn setup_token_bond() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        policy.to_policy_mut().proposal_bond = U128(10);
+        policy.to_policy_mut().proposal_bond_token = Some(accounts(3));
+        let contract = Contract::new(Config::test_config(), policy);
+        (context, contract)
+    }
+
+    /// Adds proposal with the bond in tokens and refunds it in the same token once approved.
+    #[test]
+    fn test_token_bond() {
+        let (mut context, mut contract) = setup_token_bond();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(10),
+            r#"{"description": "test", "kind": "Vote"}"#.to_string(),
+        );
+        let proposal = contract.get_proposal(0).proposal;
+        assert_eq!(proposal.proposer, accounts(1));
+        assert_eq!(proposal.bond_token, Some(accounts(3)));
+        let balance = contract.get_treasury_balance(accounts(3));
+        assert_eq!(balance.balance, U128(10));
+        assert_eq!(balance.reserved, U128(10));
+        assert_eq!(contract.locked_amount, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(0, Action::VoteApprove, None);
+        let balance = contract.get_treasury_balance(accounts(3));
+        assert_eq!(balance.balance, U128(0));
+        assert_eq!(balance.reserved, U128(0));
+    }
+
+    /// Token bond of removed proposal stays in the treasury.
+    #[test]
+    fn test_token_bond_slashed() {
+        let (mut context, mut contract) = setup_token_bond();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(10),
+            r#"{"description": "test", "kind": "Vote"}"#.to_string(),
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.act_proposal(0, Action::VoteRemove, None);
+        let balance = contract.get_treasury_balance(accounts(3));
+        assert_eq!(balance.balance, U128(10));
+        assert_eq!(balance.reserved, U128(0));
+        assert!(get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_BOND_SLASHING")]
+    fn test_token_bond_burnt() {
+        let mut policy = VersionedPolicy::Default(vec![accounts(1)]).upgrade();
+        policy.to_policy_mut().proposal_bond_token = Some(accounts(3));
+        policy.to_policy_mut().bond_slashing = BondSlashing::Burn;
+        Contract::new(Config::test_config(), policy);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_BOND_SLASHING")]
+    fn test_change_policy_token_bond_to_voters() {
+        let (mut context, mut contract) = setup_token_bond();
+        let mut policy = contract.policy.get().unwrap();
+        policy.to_policy_mut().bond_slashing = BondSlashing::Voters;
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(10),
+            serde_json::to_string(&ProposalInput {
+                description: "test".to_string(),
+                kind: ProposalKind::ChangePolicy { policy },
+            })
+            .unwrap(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_BOND_TOKEN")]
+    fn test_token_bond_wrong_token() {
+        let (mut context, mut contract) = setup_token_bond();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.ft_on_transfer(
+            accounts(1),
+            U128(10),
+            r#"{"description": "test", "kind": "Vote"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BOND_IN_TOKEN")]
+    fn test_token_bond_paid_in_near() {
+        let (mut context, mut contract) = setup_token_bond();
+        testing_env!(context.attached_deposit(10).build());
+        contract.add_proposal(ProposalInput {
+            description: "test".to_string(),
+            kind: ProposalKind::Vote,
+        });
+    }
+}
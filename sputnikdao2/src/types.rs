@@ -11,12 +11,19 @@ pub const OLD_BASE_TOKEN: &str = "";
 /// Need to keep it around for backward compatibility.
 pub type OldAccountId = String;
 
+/// Implicit account no one has the key of. Funds sent there are burned.
+pub const BURN_ACCOUNT_ID: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
 /// 1 yN to prevent access key fraud.
 pub const ONE_YOCTO_NEAR: Balance = 1;
 
 /// Gas for single ft_transfer call.
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
 
+/// Gas kept to finish the call executing a batch step. The rest goes to the callback running the next steps.
+pub const GAS_FOR_BATCH_CALLBACK_RESERVE: Gas = Gas(20_000_000_000_000);
+
 /// Configuration of the DAO.
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -60,6 +67,12 @@ pub enum Action {
     Finalize,
     /// Move a proposal to the hub to shift into another DAO.
     MoveToHub,
+    /// Retract own vote from given proposal, while it's in progress.
+    VoteRetract,
+    /// Execute queued proposal after its timelock passed. Allowed to anyone.
+    Execute,
+    /// Cancel queued proposal before its timelock passed. Allowed to the guardian role.
+    Cancel,
 }
 
 impl Action {
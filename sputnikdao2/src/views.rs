@@ -1,4 +1,5 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
 
 use std::cmp::min;
 
@@ -24,6 +25,38 @@ pub struct BountyOutput {
     pub bounty: Bounty,
 }
 
+/// This is format of output via JSON for the stream.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StreamOutput {
+    /// Id of the stream.
+    pub id: u64,
+    #[serde(flatten)]
+    pub stream: Stream,
+}
+
+/// This is format of output via JSON for the recurring payment.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecurringPaymentOutput {
+    /// Id of the recurring payment.
+    pub id: u64,
+    #[serde(flatten)]
+    pub recurring_payment: RecurringPayment,
+}
+
+/// This is format of output via JSON for the treasury balance of a token.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryBalance {
+    /// Token of the balance. Can be "" for $NEAR or a valid account id.
+    pub token_id: OldAccountId,
+    /// Amount held by the DAO. For $NEAR, excludes storage and bonds.
+    pub balance: U128,
+    /// Amount reserved by pending proposals, including their bonds paid in the token.
+    pub reserved: U128,
+}
+
 #[near_bindgen]
 impl Contract {
     /// Returns semver of this contract.
@@ -72,6 +105,16 @@ impl Contract {
         U128(self.delegations.get(&account_id).unwrap_or_default())
     }
 
+    /// Returns delegated stake to given account before the block at given timestamp.
+    pub fn delegation_balance_at(&self, account_id: AccountId, timestamp: U64) -> U128 {
+        U128(self.get_user_weight_at(&account_id, timestamp.0))
+    }
+
+    /// Returns total delegated stake before the block at given timestamp.
+    pub fn delegation_total_supply_at(&self, timestamp: U64) -> U128 {
+        U128(self.get_total_delegation_at(timestamp.0))
+    }
+
     /// Combines balance and total amount for calling from external contracts.
     pub fn delegation_balance_ratio(&self, account_id: AccountId) -> (U128, U128) {
         (
@@ -141,4 +184,97 @@ impl Contract {
     pub fn get_bounty_number_of_claims(&self, id: u64) -> u32 {
         self.bounty_claims_count.get(&id).unwrap_or_default()
     }
+
+    /// Get given stream by id.
+    pub fn get_stream(&self, id: u64) -> StreamOutput {
+        let stream = self.streams.get(&id).expect("ERR_NO_STREAM");
+        StreamOutput {
+            id,
+            stream: stream.into(),
+        }
+    }
+
+    /// Get number of streams.
+    pub fn get_last_stream_id(&self) -> u64 {
+        self.last_stream_id
+    }
+
+    /// Get `limit` of streams from given index.
+    pub fn get_streams(&self, from_index: u64, limit: u64) -> Vec<StreamOutput> {
+        (from_index..std::cmp::min(from_index + limit, self.last_stream_id))
+            .filter_map(|id| {
+                self.streams.get(&id).map(|stream| StreamOutput {
+                    id,
+                    stream: stream.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Get given recurring payment by id.
+    pub fn get_recurring_payment(&self, id: u64) -> RecurringPaymentOutput {
+        let recurring_payment = self
+            .recurring_payments
+            .get(&id)
+            .expect("ERR_NO_RECURRING_PAYMENT");
+        RecurringPaymentOutput {
+            id,
+            recurring_payment: recurring_payment.into(),
+        }
+    }
+
+    /// Get number of recurring payments.
+    pub fn get_last_recurring_payment_id(&self) -> u64 {
+        self.last_recurring_payment_id
+    }
+
+    /// Get `limit` of recurring payments from given index.
+    pub fn get_recurring_payments(
+        &self,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<RecurringPaymentOutput> {
+        (from_index..std::cmp::min(from_index + limit, self.last_recurring_payment_id))
+            .filter_map(|id| {
+                self.recurring_payments
+                    .get(&id)
+                    .map(|recurring_payment| RecurringPaymentOutput {
+                        id,
+                        recurring_payment: recurring_payment.into(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns treasury balances from given index: $NEAR at 0, followed by tokens the treasury tracks.
+    pub fn get_treasury(&self, from_index: u64, limit: u64) -> Vec<TreasuryBalance> {
+        let token_ids = self.token_balances.keys_as_vector();
+        (from_index..std::cmp::min(from_index + limit, token_ids.len() + 1))
+            .map(|index| match index {
+                0 => String::from(OLD_BASE_TOKEN),
+                _ => token_ids.get(index - 1).unwrap(),
+            })
+            .map(|token_id| self.internal_treasury_balance(token_id))
+            .collect()
+    }
+
+    /// Returns treasury balance of given token.
+    pub fn get_treasury_balance(&self, token_id: AccountId) -> TreasuryBalance {
+        self.internal_treasury_balance(token_id.to_string())
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_treasury_balance(&self, token_id: OldAccountId) -> TreasuryBalance {
+        let balance = if token_id == OLD_BASE_TOKEN {
+            self.get_available_amount().0
+        } else {
+            self.token_balances.get(&token_id).unwrap_or_default()
+        };
+        TreasuryBalance {
+            balance: U128(balance),
+            reserved: U128(self.reserved_amounts.get(&token_id).unwrap_or_default()),
+            token_id,
+        }
+    }
 }